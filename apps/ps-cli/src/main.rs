@@ -1,5 +1,5 @@
 use clap::{Parser, Subcommand};
-use ps_factory::{builder, converter, debugger, downloader, runner};
+use ps_factory::{builder, converter, debugger, downloader, inspector, runner};
 
 #[derive(Parser)]
 #[command(name = "Pixel Shell Factory")]
@@ -17,6 +17,7 @@ enum Commands {
         #[arg(short, long)]
         url: Option<String>,
 
+        /// 1080p, or any WIDTHxHEIGHT such as 1080x1920
         #[arg(short, long)]
         resolution: Option<String>,
 
@@ -25,6 +26,10 @@ enum Commands {
 
         #[arg(short, long)]
         project: Option<String>,
+
+        /// Soundtrack format: ogg, wav, flac or mp3 (default: ogg)
+        #[arg(long)]
+        audio_format: Option<String>,
     },
 
     // 2. Convert
@@ -32,11 +37,89 @@ enum Commands {
         #[arg(short, long)]
         project: Option<String>,
 
+        /// Comma-separated sizes, e.g. "1080p,1080x1920"
         #[arg(short, long)]
         resolutions: Option<String>,
 
+        /// Frame rate override, e.g. 30000/1001 (default: detected)
+        #[arg(long)]
+        fps: Option<String>,
+
+        /// Keep the source's per-frame timestamps (variable frame rate)
+        #[arg(long, default_value_t = false)]
+        vfr: bool,
+
         #[arg(long, default_value_t = false)]
         gpu: bool,
+
+        /// Store frames as deltas against the previous frame
+        #[arg(long, default_value_t = false)]
+        delta: bool,
+
+        /// Frames between full keyframes in delta mode (default: 2s of video)
+        #[arg(long)]
+        keyframe_interval: Option<u32>,
+
+        /// Use the compact variable-length rect encoding
+        #[arg(long, default_value_t = false)]
+        compact: bool,
+
+        /// Draw dark pixels instead of light ones (line art, documents)
+        #[arg(long, default_value_t = false)]
+        invert: bool,
+
+        /// Rect merging: "snowplow" (fast) or "greedy" (fewer rects for text and line art)
+        #[arg(long, default_value = "snowplow")]
+        merge: converter::RectMerge,
+
+        /// Lossy simplification: pixels rect edges may move to save rects (0 = exact)
+        #[arg(long, default_value_t = 0)]
+        simplify: u8,
+
+        /// Intensity levels for anti-aliased edges (e.g. 4 or 16, 2 = 1-bit, up to 256)
+        #[arg(long, default_value_t = 2)]
+        levels: u16,
+
+        /// Keep source colors, quantised to a palette of up to N entries (1-255)
+        #[arg(long, conflicts_with = "levels")]
+        colors: Option<u16>,
+
+        /// Luma (0-255) at which pixels are drawn in 1-bit output, or "auto"
+        /// to pick one per frame
+        #[arg(long, default_value = "127")]
+        threshold: converter::Threshold,
+
+        /// Gaussian blur sigma applied before thresholding (0 = off)
+        #[arg(long, default_value_t = 1.0)]
+        blur: f32,
+
+        /// Contrast boost for 1-bit output, up to 1000 (1 = off)
+        #[arg(long, default_value_t = 1000.0)]
+        contrast: f32,
+
+        /// Extra ffmpeg filters appended to the chain, e.g. "hqdn3d"
+        #[arg(long, default_value = "")]
+        extra_filters: String,
+
+        /// Key out a green/blue screen of this colour (RRGGBB) instead of using luminance
+        #[arg(long, value_parser = converter::parse_rgb_hex)]
+        chroma_key: Option<u32>,
+
+        /// Colour distance from the key that still counts as background
+        #[arg(long, default_value_t = 40)]
+        key_tolerance: u8,
+
+        /// Width of the soft edge beyond the tolerance (0 = hard edges)
+        #[arg(long, default_value_t = 20)]
+        key_spill: u8,
+
+        /// Extra layer drawn over the video: NAME=PATH[,opacity=PERCENT][,loop]
+        #[arg(long = "layer")]
+        layers: Vec<String>,
+
+        /// Pick up an interrupted conversion where it stopped
+        #[arg(long, default_value_t = false)]
+        resume: bool,
     },
 
     // 3. Build
@@ -49,6 +132,14 @@ enum Commands {
 
         #[arg(short, long, default_value_t = false)]
         all: bool,
+
+        /// Store the video LZ4-compressed inside the exe
+        #[arg(long, default_value_t = false)]
+        compress: bool,
+
+        /// Pack all selected resolutions of a project into one exe
+        #[arg(long, default_value_t = false)]
+        multi: bool,
     },
 
     // 4. Run
@@ -72,6 +163,12 @@ enum Commands {
         file: Option<String>,
     },
 
+    /// Prints the format details and metadata of a .bin or built .exe
+    Inspect {
+        #[arg(short, long)]
+        file: String,
+    },
+
     // 6. ALL (Pipeline)
     /// Runs Download -> Convert -> Build -> Run in sequence
     All {
@@ -92,6 +189,55 @@ enum Commands {
         #[arg(long, default_value_t = false)]
         gpu: bool,
 
+        #[arg(long, default_value_t = false)]
+        delta: bool,
+
+        #[arg(long)]
+        keyframe_interval: Option<u32>,
+
+        #[arg(long, default_value_t = false)]
+        compact: bool,
+
+        #[arg(long, default_value_t = false)]
+        invert: bool,
+
+        #[arg(long, default_value = "snowplow")]
+        merge: converter::RectMerge,
+
+        #[arg(long, default_value_t = 0)]
+        simplify: u8,
+
+        #[arg(long, default_value_t = 2)]
+        levels: u16,
+
+        #[arg(long, conflicts_with = "levels")]
+        colors: Option<u16>,
+
+        #[arg(long, default_value = "127")]
+        threshold: converter::Threshold,
+
+        #[arg(long, default_value_t = 1.0)]
+        blur: f32,
+
+        #[arg(long, default_value_t = 1000.0)]
+        contrast: f32,
+
+        #[arg(long, default_value = "")]
+        extra_filters: String,
+
+        #[arg(long, value_parser = converter::parse_rgb_hex)]
+        chroma_key: Option<u32>,
+
+        #[arg(long, default_value_t = 40)]
+        key_tolerance: u8,
+
+        #[arg(long, default_value_t = 20)]
+        key_spill: u8,
+
+        // --- Builder Args ---
+        #[arg(long, default_value_t = false)]
+        compress: bool,
+
         // --- Runner Args ---
         #[arg(short, long, default_value_t = false)]
         silent: bool,
@@ -111,12 +257,14 @@ fn main() {
             resolution,
             fps,
             project,
+            audio_format,
         } => {
             let args = downloader::DownloadArgs {
                 url: url.clone(),
                 resolution: resolution.clone(),
                 fps: *fps,
                 project_name: project.clone(),
+                audio_format: audio_format.clone(),
             };
             if let Err(e) = downloader::run_cli(args) {
                 eprintln!("❌ Download Error: {}", e);
@@ -127,12 +275,54 @@ fn main() {
         Commands::Convert {
             project,
             resolutions,
+            fps,
+            vfr,
             gpu,
+            delta,
+            keyframe_interval,
+            compact,
+            invert,
+            merge,
+            simplify,
+            levels,
+            colors,
+            threshold,
+            blur,
+            contrast,
+            extra_filters,
+            chroma_key,
+            key_tolerance,
+            key_spill,
+            layers,
+            resume,
         } => {
             let args = converter::ConvertArgs {
                 project_name: project.clone(),
                 resolutions: resolutions.clone(),
+                fps: fps.clone(),
+                variable_rate: *vfr,
                 use_gpu: *gpu,
+                delta: *delta,
+                keyframe_interval: *keyframe_interval,
+                compact: *compact,
+                levels: *levels,
+                colors: *colors,
+                invert: *invert,
+                merge: *merge,
+                simplify: *simplify,
+                filters: converter::FilterOptions {
+                    threshold: *threshold,
+                    blur: *blur,
+                    contrast: *contrast,
+                    extra: extra_filters.clone(),
+                    chroma_key: chroma_key.map(|color| converter::ChromaKey {
+                        color,
+                        tolerance: *key_tolerance,
+                        spill: *key_spill,
+                    }),
+                },
+                layers: layers.clone(),
+                resume: *resume,
             };
             if let Err(e) = converter::run_cli(args) {
                 eprintln!("❌ Conversion Error: {}", e);
@@ -144,11 +334,15 @@ fn main() {
             project,
             resolutions,
             all,
+            compress,
+            multi,
         } => {
             let args = builder::BuildArgs {
                 project_name: project.clone(),
                 resolutions: resolutions.clone(),
                 build_all: *all,
+                compress: *compress,
+                multi: *multi,
             };
             if let Err(e) = builder::run_cli(args) {
                 eprintln!("❌ Build Error: {}", e);
@@ -182,6 +376,13 @@ fn main() {
             }
         }
 
+        Commands::Inspect { file } => {
+            let args = inspector::InspectArgs { file: file.into() };
+            if let Err(e) = inspector::run_cli(args) {
+                eprintln!("❌ Inspect Error: {:#}", e);
+            }
+        }
+
         // [6] ALL (The Pipeline)
         Commands::All {
             url,
@@ -189,6 +390,22 @@ fn main() {
             fps,
             project,
             gpu,
+            delta,
+            keyframe_interval,
+            compact,
+            invert,
+            merge,
+            simplify,
+            levels,
+            colors,
+            threshold,
+            blur,
+            contrast,
+            extra_filters,
+            chroma_key,
+            key_tolerance,
+            key_spill,
+            compress,
             silent,
             detach,
         } => {
@@ -201,6 +418,7 @@ fn main() {
                 resolution: resolution.clone(),
                 fps: *fps,
                 project_name: project.clone(),
+                audio_format: None,
             };
             if let Err(e) = downloader::run_cli(dl_args) {
                 eprintln!("❌ Pipeline stopped at Download: {}", e);
@@ -213,7 +431,30 @@ fn main() {
             let cv_args = converter::ConvertArgs {
                 project_name: project.clone(),
                 resolutions: resolution.clone(), // Use same resolution preference
+                fps: None,                       // The download step already fixed the rate
+                variable_rate: false,
                 use_gpu: *gpu,
+                delta: *delta,
+                keyframe_interval: *keyframe_interval,
+                compact: *compact,
+                levels: *levels,
+                colors: *colors,
+                invert: *invert,
+                merge: *merge,
+                simplify: *simplify,
+                filters: converter::FilterOptions {
+                    threshold: *threshold,
+                    blur: *blur,
+                    contrast: *contrast,
+                    extra: extra_filters.clone(),
+                    chroma_key: chroma_key.map(|color| converter::ChromaKey {
+                        color,
+                        tolerance: *key_tolerance,
+                        spill: *key_spill,
+                    }),
+                },
+                layers: Vec::new(),
+                resume: false,
             };
             if let Err(e) = converter::run_cli(cv_args) {
                 eprintln!("❌ Pipeline stopped at Conversion: {}", e);
@@ -226,6 +467,8 @@ fn main() {
                 project_name: project.clone(),
                 resolutions: resolution.clone(),
                 build_all: false,
+                compress: *compress,
+                multi: false,
             };
             if let Err(e) = builder::run_cli(bd_args) {
                 eprintln!("❌ Pipeline stopped at Build: {}", e);
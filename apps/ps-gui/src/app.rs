@@ -1,6 +1,7 @@
 use crate::{tabs, theme, utils};
 use eframe::egui;
-use ps_factory::builder;
+use ps_core::Resolution;
+use ps_factory::{builder, converter};
 use std::path::PathBuf;
 use std::sync::mpsc::{channel, Receiver, Sender};
 
@@ -27,16 +28,27 @@ pub struct PsApp {
 
     // Inputs (Download)
     pub dl_url: String,
-    pub dl_res: String,
+    pub dl_res: Resolution,
     pub dl_fps: String,
     pub dl_project: String,
 
     // Inputs (Convert)
     pub cv_gpu: bool,
+    pub cv_delta: bool,
+    pub cv_compact: bool,
+    pub cv_invert: bool,
+    pub cv_resume: bool,
+    pub cv_merge: converter::RectMerge,
+    pub cv_simplify: u8,
+    pub cv_levels: u16,
+    pub cv_colors: Option<u16>,
+    pub cv_filters: converter::FilterOptions,
     pub cv_res_720: bool,
     pub cv_res_1080: bool,
     pub cv_res_1440: bool,
     pub cv_res_2160: bool,
+    /// Comma-separated extra sizes, e.g. `1080x1920, 2560x1080`.
+    pub cv_res_custom: String,
     pub cv_files: Vec<PathBuf>,
     pub cv_selected_idx: usize,
     pub cv_manual_path: Option<PathBuf>,
@@ -45,6 +57,8 @@ pub struct PsApp {
     pub bd_targets: Vec<builder::BuildTarget>,
     pub bd_selected_idx: usize,
     pub bd_manual_path: Option<PathBuf>,
+    pub bd_compress: bool,
+    pub bd_multi: bool,
 
     // Inputs (Runner)
     pub rn_files: Vec<PathBuf>,
@@ -75,15 +89,25 @@ impl Default for PsApp {
             current_task: "Idle".into(),
 
             dl_url: "".into(),
-            dl_res: "1080p".into(),
+            dl_res: Resolution::FULL_HD,
             dl_fps: "30".into(),
             dl_project: "new_project".into(),
 
             cv_gpu: false,
+            cv_delta: false,
+            cv_compact: false,
+            cv_invert: false,
+            cv_resume: false,
+            cv_merge: converter::RectMerge::Snowplow,
+            cv_simplify: 0,
+            cv_levels: 2,
+            cv_colors: None,
+            cv_filters: converter::FilterOptions::default(),
             cv_res_720: false,
             cv_res_1080: true,
             cv_res_1440: false,
             cv_res_2160: false,
+            cv_res_custom: String::new(),
             cv_files: utils::scan_video_files(),
             cv_selected_idx: 0,
             cv_manual_path: None,
@@ -91,6 +115,8 @@ impl Default for PsApp {
             bd_targets: utils::scan_build_targets(),
             bd_selected_idx: 0,
             bd_manual_path: None,
+            bd_compress: false,
+            bd_multi: false,
 
             rn_files: utils::scan_dist_files(),
             rn_selected_idx: 0,
@@ -113,10 +139,7 @@ impl eframe::App for PsApp {
             theme::Theme::Light => false,
             theme::Theme::System => {
                 // Query eframe for the system theme. Default to Dark if unknown.
-                match _frame.info().system_theme {
-                    Some(eframe::Theme::Light) => false,
-                    _ => true,
-                }
+                !matches!(_frame.info().system_theme, Some(eframe::Theme::Light))
             }
         };
 
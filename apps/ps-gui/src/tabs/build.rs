@@ -83,6 +83,12 @@ pub fn show(app: &mut PsApp, ui: &mut egui::Ui) {
                     }
                 });
             });
+
+            ui.add_space(5.0);
+            ui.checkbox(&mut app.bd_compress, "Compress Video Payload (LZ4)");
+            ui.add_enabled_ui(app.bd_manual_path.is_none(), |ui| {
+                ui.checkbox(&mut app.bd_multi, "Pack All Resolutions Into One EXE");
+            });
         },
     );
 
@@ -118,37 +124,46 @@ fn start_build(app: &mut PsApp) {
     app.is_busy = true;
     let tx = app.status_tx.clone();
 
-    // 1. Construct the Target
+    // 1. Construct the Targets
     // We either use the manually selected path OR the one from the scanned list.
-    let target = if let Some(path) = &app.bd_manual_path {
+    let multi = app.bd_multi && app.bd_manual_path.is_none();
+    let targets = if multi {
+        // Every scanned resolution of the selected project
+        let project = &app.bd_targets[app.bd_selected_idx].project;
+        app.bd_targets
+            .iter()
+            .filter(|t| &t.project == project)
+            .cloned()
+            .collect()
+    } else if let Some(path) = &app.bd_manual_path {
         let filename = path.file_stem().unwrap().to_string_lossy().to_string();
 
         // Use shared utils to detect metadata
         let audio_path = builder_utils::detect_audio_path(path);
-        let (res_str, w, h) = builder_utils::detect_resolution(&filename);
+        let resolution = builder_utils::detect_resolution(&filename);
 
-        builder::BuildTarget {
+        vec![builder::BuildTarget {
             project: filename,
-            resolution: res_str,
-            width: w,
-            height: h,
+            resolution,
             bin_path: path.clone(),
             audio_path,
-        }
+        }]
     } else {
         // Use the pre-scanned target directly
-        app.bd_targets[app.bd_selected_idx].clone()
+        vec![app.bd_targets[app.bd_selected_idx].clone()]
     };
 
+    let compress = app.bd_compress;
+
     // 2. Spawn Build Thread
     thread::spawn(move || {
-        tx.send(format!("Building target: {}", target.project)).ok();
+        tx.send(format!("Building target: {}", targets[0].project))
+            .ok();
 
-        let targets = vec![target];
         let (internal_tx, internal_rx) = std::sync::mpsc::channel();
 
         // Run the async builder logic
-        let _ = builder::run_async(targets, internal_tx);
+        let _ = builder::run_async(targets, compress, multi, internal_tx);
 
         // Forward status updates to the main app
         while let Ok(status) = internal_rx.recv() {
@@ -156,9 +171,14 @@ fn start_build(app: &mut PsApp) {
                 builder::BuildStatus::Building(name) => {
                     tx.send(format!("Building: {}", name)).ok();
                 }
-                builder::BuildStatus::Finished(path) => {
-                    tx.send(format!("Success: {:?}", path.file_name().unwrap()))
-                        .ok();
+                builder::BuildStatus::Finished(output) => {
+                    tx.send(format!(
+                        "Success: {:?} (video {} -> {})",
+                        output.path.file_name().unwrap(),
+                        builder_utils::format_size(output.video_len),
+                        builder_utils::format_size(output.payload_len)
+                    ))
+                    .ok();
                 }
                 builder::BuildStatus::Error(e) => {
                     tx.send(format!("Error: {}", e)).ok();
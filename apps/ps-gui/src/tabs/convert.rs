@@ -1,5 +1,6 @@
 use crate::{app::PsApp, theme, utils};
 use eframe::egui;
+use ps_core::Resolution;
 use ps_factory::converter;
 use std::thread;
 
@@ -19,7 +20,8 @@ pub fn show(app: &mut PsApp, ui: &mut egui::Ui) {
             ui.horizontal(|ui| {
                 if ui.button("📂 Browse System...").clicked() {
                     if let Some(path) = rfd::FileDialog::new()
-                        .add_filter("Video", &["mp4", "mkv", "mov"])
+                        .add_filter("Video", &["mp4", "mkv", "mov", "y4m"])
+                        .add_filter("PNG Sequence", &["png"])
                         .pick_file()
                     {
                         app.cv_manual_path = Some(path);
@@ -110,6 +112,14 @@ pub fn show(app: &mut PsApp, ui: &mut egui::Ui) {
                 ui.end_row();
             });
 
+        ui.horizontal(|ui| {
+            ui.label("Custom:");
+            ui.add(
+                egui::TextEdit::singleline(&mut app.cv_res_custom)
+                    .hint_text("e.g. 1080x1920, 2560x1080"),
+            );
+        });
+
         ui.add_space(10.0);
         ui.separator();
         ui.add_space(5.0);
@@ -120,12 +130,150 @@ pub fn show(app: &mut PsApp, ui: &mut egui::Ui) {
                 .small()
                 .weak(),
         );
+
+        ui.add_space(5.0);
+        ui.checkbox(&mut app.cv_resume, "Resume Interrupted Conversions");
+        ui.label(
+            egui::RichText::new("Continues from the last checkpoint if the settings match.")
+                .small()
+                .weak(),
+        );
+
+        ui.add_space(5.0);
+        ui.checkbox(&mut app.cv_delta, "Delta Encoding");
+        ui.label(
+            egui::RichText::new("Stores only changes between frames. Much smaller output.")
+                .small()
+                .weak(),
+        );
+
+        ui.add_space(5.0);
+        ui.checkbox(&mut app.cv_compact, "Compact Rect Encoding");
+        ui.label(
+            egui::RichText::new("Variable-length coordinates instead of 8 bytes per rect.")
+                .small()
+                .weak(),
+        );
+
+        ui.add_space(5.0);
+        ui.checkbox(&mut app.cv_invert, "Invert");
+        ui.label(
+            egui::RichText::new("Draws dark pixels, for line art on a light background.")
+                .small()
+                .weak(),
+        );
+
+        ui.add_space(5.0);
+        let mut greedy = app.cv_merge == converter::RectMerge::Greedy;
+        ui.checkbox(&mut greedy, "Greedy Rect Merging");
+        app.cv_merge = if greedy {
+            converter::RectMerge::Greedy
+        } else {
+            converter::RectMerge::Snowplow
+        };
+        ui.label(
+            egui::RichText::new("Fewer rects for text and line art. Slower to convert.")
+                .small()
+                .weak(),
+        );
+
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            ui.label("Simplify");
+            ui.add(egui::Slider::new(&mut app.cv_simplify, 0..=8).suffix(" px"));
+        });
+        ui.label(
+            egui::RichText::new("Lets edges move a few pixels to save rects. 0 keeps them exact.")
+                .small()
+                .weak(),
+        );
+
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            ui.label("Edge Levels:");
+            ui.selectable_value(&mut app.cv_levels, 2, "1-bit");
+            ui.selectable_value(&mut app.cv_levels, 4, "4");
+            ui.selectable_value(&mut app.cv_levels, 16, "16");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Colors:");
+            ui.selectable_value(&mut app.cv_colors, None, "Mono");
+            ui.selectable_value(&mut app.cv_colors, Some(8), "8");
+            ui.selectable_value(&mut app.cv_colors, Some(16), "16");
+            ui.selectable_value(&mut app.cv_colors, Some(32), "32");
+        });
+
+        ui.add_space(10.0);
+        ui.separator();
+        ui.add_space(5.0);
+
+        ui.label("Filters:");
+        egui::Grid::new("cv_filters_grid")
+            .spacing([20.0, 8.0])
+            .show(ui, |ui| {
+                let filters = &mut app.cv_filters;
+                ui.label("Threshold");
+                ui.horizontal(|ui| {
+                    let mut auto = filters.threshold == converter::Threshold::Otsu;
+                    let mut luma = match filters.threshold {
+                        converter::Threshold::Fixed(luma) => luma,
+                        converter::Threshold::Otsu => converter::Threshold::DEFAULT_LUMA,
+                    };
+                    ui.add_enabled(!auto, egui::Slider::new(&mut luma, 0..=255));
+                    ui.checkbox(&mut auto, "Auto (per frame)");
+                    filters.threshold = if auto {
+                        converter::Threshold::Otsu
+                    } else {
+                        converter::Threshold::Fixed(luma)
+                    };
+                });
+                ui.end_row();
+
+                ui.label("Blur");
+                ui.add(egui::Slider::new(&mut filters.blur, 0.0..=5.0).step_by(0.1));
+                ui.end_row();
+
+                ui.label("Contrast");
+                ui.add(egui::Slider::new(&mut filters.contrast, 1.0..=1000.0).logarithmic(true));
+                ui.end_row();
+
+                ui.label("Extra FFmpeg");
+                ui.add(egui::TextEdit::singleline(&mut filters.extra).hint_text("e.g. hqdn3d"));
+                ui.end_row();
+
+                ui.label("Chroma Key");
+                ui.horizontal(|ui| {
+                    let mut enabled = filters.chroma_key.is_some();
+                    ui.checkbox(&mut enabled, "");
+                    if enabled != filters.chroma_key.is_some() {
+                        filters.chroma_key = enabled.then(converter::ChromaKey::default);
+                    }
+                    if let Some(key) = &mut filters.chroma_key {
+                        let [_, r, g, b] = key.color.to_be_bytes();
+                        let mut rgb = [r, g, b];
+                        ui.color_edit_button_srgb(&mut rgb);
+                        key.color = u32::from_be_bytes([0, rgb[0], rgb[1], rgb[2]]);
+                        ui.add(egui::Slider::new(&mut key.tolerance, 0..=180).text("tolerance"));
+                        ui.add(egui::Slider::new(&mut key.spill, 0..=100).text("spill"));
+                    }
+                });
+                ui.end_row();
+            });
+        ui.label(
+            egui::RichText::new("Blur applies to mono output, contrast to 1-bit output only.")
+                .small()
+                .weak(),
+        );
     });
 
     ui.add_space(20.0);
 
     // ACTION BUTTON
-    let has_res = app.cv_res_720 || app.cv_res_1080 || app.cv_res_1440 || app.cv_res_2160;
+    let has_res = app.cv_res_720
+        || app.cv_res_1080
+        || app.cv_res_1440
+        || app.cv_res_2160
+        || !app.cv_res_custom.trim().is_empty();
     let has_file = app.cv_manual_path.is_some() || !app.cv_files.is_empty();
 
     ui.horizontal(|ui| {
@@ -147,7 +295,6 @@ pub fn show(app: &mut PsApp, ui: &mut egui::Ui) {
 }
 
 fn start_conversion(app: &mut PsApp) {
-    app.is_busy = true;
     let tx = app.status_tx.clone();
 
     // Determine Input Path
@@ -157,42 +304,90 @@ fn start_conversion(app: &mut PsApp) {
         .unwrap_or_else(|| app.cv_files[app.cv_selected_idx].clone());
 
     // Prepare Jobs
-    let mut jobs = Vec::new();
-    if app.cv_res_720 {
-        jobs.push((1280, 720, "720p"));
-    }
-    if app.cv_res_1080 {
-        jobs.push((1920, 1080, "1080p"));
-    }
-    if app.cv_res_1440 {
-        jobs.push((2560, 1440, "1440p"));
-    }
-    if app.cv_res_2160 {
-        jobs.push((3840, 2160, "2160p"));
+    let presets = [
+        app.cv_res_720,
+        app.cv_res_1080,
+        app.cv_res_1440,
+        app.cv_res_2160,
+    ];
+    let mut jobs: Vec<Resolution> = Resolution::PRESETS
+        .into_iter()
+        .zip(presets)
+        .filter_map(|(resolution, checked)| checked.then_some(resolution))
+        .collect();
+    for custom in app
+        .cv_res_custom
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+    {
+        match custom.parse() {
+            Ok(resolution) if !jobs.contains(&resolution) => jobs.push(resolution),
+            Ok(_) => {}
+            Err(e) => {
+                tx.send(format!("Error: {}", e)).ok();
+                return;
+            }
+        }
     }
+    app.is_busy = true;
     let gpu = app.cv_gpu;
+    let delta = app.cv_delta;
+    let compact = app.cv_compact;
+    let invert = app.cv_invert;
+    let resume = app.cv_resume;
+    let merge = app.cv_merge;
+    let simplify = app.cv_simplify;
+    let levels = app.cv_levels;
+    let colors = app.cv_colors;
+    let filters = app.cv_filters.clone();
 
     thread::spawn(move || {
-        let fps = 30; // Ideally detected via ffprobe, currently hardcoded fallback
+        let is_sequence =
+            converter::SourceKind::detect(&input_path) == converter::SourceKind::PngSequence;
+        let detected = converter::detect_frame_rate(&input_path)
+            .or(is_sequence.then_some(converter::DEFAULT_SEQUENCE_RATE));
+        let Some(frame_rate) = detected else {
+            tx.send("Error: could not detect the frame rate".into())
+                .ok();
+            return;
+        };
+        tx.send(format!("Frame rate: {} FPS", frame_rate)).ok();
+        let variable_rate = converter::looks_variable(&input_path);
+        if variable_rate {
+            tx.send("Variable frame rate source, keeping timestamps".into())
+                .ok();
+        }
 
-        for (w, h, label) in jobs {
-            tx.send(format!("Starting {} conversion...", label)).ok();
+        for resolution in jobs {
+            tx.send(format!("Starting {} conversion...", resolution))
+                .ok();
             let (internal_tx, internal_rx) = std::sync::mpsc::channel();
 
             let out_name = format!(
                 "{}_{}.bin",
                 input_path.file_stem().unwrap().to_string_lossy(),
-                label
+                resolution
             );
             let output_path = input_path.parent().unwrap().join(out_name);
 
             let job = converter::ConvertJob {
                 input_path: input_path.clone(),
                 output_path,
-                width: w,
-                height: h,
-                fps,
+                width: resolution.width as u32,
+                height: resolution.height as u32,
+                frame_rate,
+                variable_rate,
                 use_gpu: gpu,
+                keyframe_interval: delta.then_some(frame_rate.rounded() as u32 * 2),
+                compact,
+                levels,
+                colors,
+                invert,
+                merge,
+                simplify,
+                filters: filters.clone(),
+                layers: Vec::new(),
+                resume,
             };
 
             let _ = converter::run_async(job, internal_tx);
@@ -207,6 +402,18 @@ fn start_conversion(app: &mut PsApp) {
                         let p = current_frame as f32 / total_frames as f32;
                         tx.send(format!("[Processing] {:.0}%", p * 100.0)).ok();
                     }
+                    converter::ConverterStatus::Resumed(frame) => {
+                        tx.send(format!("Resuming from frame {}", frame)).ok();
+                    }
+                    converter::ConverterStatus::Simplified(report) => {
+                        tx.send(format!("Simplified: {}", report)).ok();
+                    }
+                    converter::ConverterStatus::Stats(stats) => {
+                        tx.send(format!("Stats: {}", stats)).ok();
+                    }
+                    converter::ConverterStatus::Warning(message) => {
+                        tx.send(format!("Warning: {}", message)).ok();
+                    }
                     converter::ConverterStatus::Finished => {}
                     converter::ConverterStatus::Error(e) => {
                         tx.send(format!("Error: {}", e)).ok();
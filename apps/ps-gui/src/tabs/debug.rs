@@ -1,6 +1,6 @@
 use crate::{app::PsApp, theme, utils};
 use eframe::egui;
-use ps_factory::debugger;
+use ps_factory::{debugger, inspector};
 use std::thread;
 
 pub fn show(app: &mut PsApp, ui: &mut egui::Ui) {
@@ -152,11 +152,37 @@ pub fn show(app: &mut PsApp, ui: &mut egui::Ui) {
                 {
                     start_debug(app);
                 }
+                if theme::styled_button(ui, "ℹ️ Show Info", theme::ButtonVariant::Secondary)
+                    .clicked()
+                {
+                    show_info(app);
+                }
             });
         });
     });
 }
 
+/// Logs the format details and metadata of the selected file.
+fn show_info(app: &mut PsApp) {
+    let target_path = app
+        .db_manual_path
+        .clone()
+        .unwrap_or_else(|| app.db_files[app.db_selected_idx].clone());
+
+    match inspector::inspect_file(&target_path) {
+        Ok(report) => {
+            app.logs.push(format!(
+                "Info: {}",
+                target_path.file_name().unwrap().to_string_lossy()
+            ));
+            for (key, value) in report.rows() {
+                app.logs.push(format!("  {}: {}", key, value));
+            }
+        }
+        Err(e) => app.logs.push(format!("Error: {:#}", e)),
+    }
+}
+
 fn start_debug(app: &mut PsApp) {
     app.is_busy = true;
     let tx = app.status_tx.clone();
@@ -176,6 +202,8 @@ fn start_debug(app: &mut PsApp) {
                 debugger::DebugStatus::Starting => {
                     tx.send("Debugger Window Opening...".into()).ok();
                 }
+                // Shown with the Info button instead
+                debugger::DebugStatus::Info(_) => {}
                 debugger::DebugStatus::Playing { frame, rect_count } => {
                     if frame % 60 == 0 {
                         tx.send(format!("Playing Frame: {} ({} rects)", frame, rect_count))
@@ -1,5 +1,6 @@
 use crate::{app::PsApp, theme};
 use eframe::egui;
+use ps_core::Resolution;
 use ps_factory::downloader;
 use std::thread;
 
@@ -29,16 +30,19 @@ pub fn show(app: &mut PsApp, ui: &mut egui::Ui) {
         |ui| {
             ui.label("Target Resolution:");
 
-            let res_options = [("1080p (FHD)", "1080p"), ("720p (HD)", "720p")];
-            let current_res = app.dl_res.clone();
+            let res_options = [
+                ("2160p (4K)", Resolution::UHD),
+                ("1440p (2K)", Resolution::QHD),
+                ("1080p (FHD)", Resolution::FULL_HD),
+                ("720p (HD)", Resolution::HD),
+                ("1080x1920 (Portrait 9:16)", Resolution::new(1080, 1920)),
+                ("2560x1080 (Ultrawide 21:9)", Resolution::new(2560, 1080)),
+            ];
+            let current_res = app.dl_res.to_string();
 
-            // 2. Pass &app.dl_res directly. Do NOT clone.
-            // Cloning here creates a temporary string that disappears immediately.
             theme::combo_box(ui, "dl_res", &current_res, |ui| {
                 for (label, value) in res_options {
-                    // We only convert to String here because app.dl_res is likely a String.
-                    // This is unavoidable unless you change app.dl_res to an Enum (the ideal Rust way).
-                    ui.selectable_value(&mut app.dl_res, value.to_string(), label);
+                    ui.selectable_value(&mut app.dl_res, value, label);
                 }
             });
 
@@ -57,7 +61,7 @@ pub fn show(app: &mut PsApp, ui: &mut egui::Ui) {
 
     // Handle Reset Action
     if reset_clicked {
-        app.dl_res = "1080p".into();
+        app.dl_res = Resolution::FULL_HD;
         app.dl_fps = "30".into();
     }
 
@@ -85,10 +89,11 @@ fn start_download(app: &mut PsApp) {
     let job = downloader::DownloadJob {
         url: app.dl_url.clone(),
         project_name: app.dl_project.clone(),
-        width: if app.dl_res == "1080p" { 1920 } else { 1280 },
-        height: if app.dl_res == "1080p" { 1080 } else { 720 },
+        width: app.dl_res.width as u32,
+        height: app.dl_res.height as u32,
         fps: app.dl_fps.parse().unwrap_or(30),
         use_gpu: false,
+        audio_format: "ogg".to_string(),
     };
 
     thread::spawn(move || {
@@ -22,7 +22,7 @@ pub fn scan_build_targets() -> Vec<builder::BuildTarget> {
 }
 
 pub fn scan_video_files() -> Vec<PathBuf> {
-    scan_files_with_ext(&["mp4", "mkv", "avi", "mov"], "assets")
+    scan_files_with_ext(&["mp4", "mkv", "avi", "mov", "y4m"], "assets")
 }
 
 pub fn scan_bin_files() -> Vec<PathBuf> {
@@ -0,0 +1,71 @@
+use kira::{
+    clock::{ClockHandle, ClockSpeed},
+    manager::{backend::DefaultBackend, AudioManager, AudioManagerSettings},
+    sound::streaming::{StreamingSoundData, StreamingSoundSettings},
+};
+use std::{io::Cursor, time::Instant};
+
+/// Resolution of the audio clock. Frames are looked up by time rather
+/// than counted in ticks, so fractional and variable rates stay in sync.
+const CLOCK_TICKS_PER_SECOND: f64 = 1000.0;
+
+/// Drives frame timing: the soundtrack's clock when there is one, so the
+/// video stays in sync with it, otherwise a monotonic system clock.
+pub enum PlaybackClock {
+    Audio {
+        // Keeps the sound playing
+        _manager: Box<AudioManager<DefaultBackend>>,
+        clock: ClockHandle,
+        first_tick: u64,
+    },
+    Monotonic(Instant),
+}
+
+impl PlaybackClock {
+    /// Starts playback. Silent overlays, and soundtracks that can't be
+    /// played (no output device, undecodable data), run on the system clock.
+    pub fn start(audio: Option<&'static [u8]>) -> Self {
+        audio
+            .and_then(Self::start_audio)
+            .unwrap_or_else(|| Self::Monotonic(Instant::now()))
+    }
+
+    /// Every payload audio codec is decoded as-is; the format is probed
+    /// from the data.
+    fn start_audio(audio: &'static [u8]) -> Option<Self> {
+        let mut manager =
+            AudioManager::<DefaultBackend>::new(AudioManagerSettings::default()).ok()?;
+        let clock = manager
+            .add_clock(ClockSpeed::TicksPerSecond(CLOCK_TICKS_PER_SECOND))
+            .ok()?;
+
+        let sound_data = StreamingSoundData::from_cursor(
+            Cursor::new(audio),
+            StreamingSoundSettings::new().start_time(clock.time()),
+        )
+        .ok()?;
+        manager.play(sound_data).ok()?;
+        clock.start().ok()?;
+
+        let first_tick = clock.time().ticks;
+        Some(Self::Audio {
+            _manager: Box::new(manager),
+            clock,
+            first_tick,
+        })
+    }
+
+    pub fn has_audio(&self) -> bool {
+        matches!(self, Self::Audio { .. })
+    }
+
+    /// Seconds since playback started.
+    pub fn elapsed(&self) -> f64 {
+        match self {
+            Self::Audio {
+                clock, first_tick, ..
+            } => (clock.time().ticks - first_tick) as f64 / CLOCK_TICKS_PER_SECOND,
+            Self::Monotonic(start) => start.elapsed().as_secs_f64(),
+        }
+    }
+}
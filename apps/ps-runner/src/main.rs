@@ -1,16 +1,15 @@
 #![windows_subsystem = "windows"]
 
 mod audio;
+mod clock;
 mod desktop;
 mod payload;
 mod renderer;
+mod video;
 
-use kira::{
-    clock::ClockSpeed,
-    manager::{backend::DefaultBackend, AudioManager, AudioManagerSettings},
-    sound::streaming::{StreamingSoundData, StreamingSoundSettings},
-};
-use std::{io::Cursor, mem, ptr, slice, thread, time::Duration};
+use clock::PlaybackClock;
+use ps_core::Brush;
+use std::{ptr, thread, time::Duration};
 use windows::Win32::{
     System::Threading::{GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_HIGHEST},
     UI::{
@@ -21,15 +20,8 @@ use windows::Win32::{
     },
 };
 
-use ps_core::{file_header, PixelRect};
-
 fn main() {
-    let assets = payload::load();
-
-    let bin_data: &'static [u8] = Box::leak(assets.video_data.into_boxed_slice());
-    let audio_data: &'static [u8] = Box::leak(assets.audio_data.into_boxed_slice());
-    let video_width = assets.width;
-    let video_height = assets.height;
+    let payload = payload::Payload::open();
 
     unsafe {
         let _ = SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
@@ -37,35 +29,28 @@ fn main() {
         let _ = SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_HIGHEST);
         let audio_sys = audio::AudioSystem::new();
 
-        let (fps_bytes, frames_bytes) = bin_data.split_at(file_header::DATA_START);
-        let video_fps = u16::from_le_bytes(fps_bytes[0..2].try_into().unwrap()) as f64;
-
-        let frames: &[PixelRect] = slice::from_raw_parts(
-            frames_bytes.as_ptr() as *const _,
-            frames_bytes.len() / mem::size_of::<PixelRect>(),
-        );
-        let mut frames_iter = frames.iter();
-
         let (hwnd, w, h) = desktop::create_overlay_window();
-        let mut renderer = renderer::GdiRenderer::new(w, h, video_width, video_height);
-
-        let mut audio_manager =
-            AudioManager::<DefaultBackend>::new(AudioManagerSettings::default()).unwrap();
-        let clock = audio_manager
-            .add_clock(ClockSpeed::TicksPerSecond(video_fps))
-            .unwrap();
-
-        let sound_data = StreamingSoundData::from_cursor(
-            Cursor::new(audio_data),
-            StreamingSoundSettings::new().start_time(clock.time()),
-        )
-        .unwrap();
-
-        let volume_ctl = audio_sys.get_volume_control();
-        audio_manager.play(sound_data).unwrap();
-        clock.start().unwrap();
 
-        let mut next_tick = clock.time().ticks;
+        // Pick the resolution that best fits this screen
+        let assets = payload.load(w, h);
+        let bin_data: &'static [u8] = Box::leak(assets.video_data.into_boxed_slice());
+        let audio_data: Option<&'static [u8]> = assets
+            .audio_data
+            .map(|data| &*Box::leak(data.into_boxed_slice()));
+        let mut renderer = renderer::GdiRenderer::new(w, h, assets.width, assets.height);
+
+        // The main video first, then its layers on top
+        let mut tracks = vec![video::Track::new(bin_data, assets.video_codec)];
+        let layers = tracks[0].info.layers.clone();
+        tracks.extend(layers.iter().map(video::Track::from_layer));
+
+        let clock = PlaybackClock::start(audio_data);
+        // Silent overlays leave the system volume alone
+        let volume_ctl = if clock.has_audio() {
+            audio_sys.get_volume_control()
+        } else {
+            None
+        };
 
         'main_loop: loop {
             if let Some(ref v) = volume_ctl {
@@ -82,32 +67,28 @@ fn main() {
                 DispatchMessageA(&msg);
             }
 
-            let current_tick = clock.time().ticks;
-
-            if current_tick >= next_tick {
-                while current_tick > next_tick {
-                    for c in frames_iter.by_ref() {
-                        if c.is_frame_end() {
-                            break;
-                        }
-                    }
-                    next_tick += 1;
-                }
+            let elapsed = clock.elapsed();
+            let mut changed = false;
+            for track in &mut tracks {
+                changed |= track.update(elapsed);
+            }
+            // The main video decides when playback is over
+            if tracks[0].ended {
+                break 'main_loop;
+            }
 
+            if changed {
                 renderer.clear();
-                loop {
-                    let c = match frames_iter.next() {
-                        Some(val) => val,
-                        None => break 'main_loop,
-                    };
-                    if c.is_frame_end() {
-                        break;
+                for track in &tracks {
+                    let mut brush = Brush::new(&track.info.header, track.info.palette.as_ref())
+                        .with_opacity(track.opacity);
+                    for c in &track.rects {
+                        if !brush.apply(c) {
+                            renderer.draw_sparse_rect(c.x, c.y, c.w, c.h, brush.color());
+                        }
                     }
-
-                    renderer.draw_sparse_rect(c.x, c.y, c.w, c.h);
                 }
                 renderer.present(hwnd);
-                next_tick += 1;
             } else {
                 thread::sleep(Duration::from_millis(1));
             }
@@ -1,62 +1,55 @@
-use std::{
-    env,
-    fs::File,
-    io::{Read, Seek, SeekFrom},
-    mem,
-};
-
-const MAGIC: &[u8; 8] = b"PS_PATCH";
+use ps_core::PayloadFooter;
+use std::{env, fs::File};
 
 pub struct LoadedAssets {
     pub video_data: Vec<u8>,
-    pub audio_data: Vec<u8>,
+    /// How `video_data` is encoded, see `ps_core::payload`.
+    pub video_codec: u16,
+    /// `None` for silent overlays.
+    pub audio_data: Option<Vec<u8>>,
     pub width: u16,
     pub height: u16,
 }
 
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-struct PayloadFooter {
-    video_offset: u64,
-    video_len: u64,
-    audio_offset: u64,
-    audio_len: u64,
-    width: u16,
-    height: u16,
-    magic: [u8; 8],
+/// The assets patched onto this executable.
+pub struct Payload {
+    file: File,
+    footer: PayloadFooter,
 }
 
-pub fn load() -> LoadedAssets {
-    let current_exe = env::current_exe().expect("Failed to get exe path");
-    let mut file = File::open(current_exe).expect("Failed to open self");
-
-    let footer_size = mem::size_of::<PayloadFooter>() as i64;
-    file.seek(SeekFrom::End(-footer_size)).expect("Seek failed");
-
-    let mut footer_buffer = vec![0u8; footer_size as usize];
-    file.read_exact(&mut footer_buffer)
-        .expect("Failed to read footer");
+impl Payload {
+    pub fn open() -> Self {
+        let current_exe = env::current_exe().expect("Failed to get exe path");
+        let mut file = File::open(current_exe).expect("Failed to open self");
 
-    let footer: PayloadFooter = unsafe { std::ptr::read(footer_buffer.as_ptr() as *const _) };
-
-    if &footer.magic != MAGIC {
-        panic!("❌ FATAL: This runner is a template. It has not been patched with assets.");
+        let Ok(footer) = PayloadFooter::read_from(&mut file) else {
+            panic!("❌ FATAL: This runner is a template. It has not been patched with assets.");
+        };
+        Self { file, footer }
     }
 
-    let mut video_data = vec![0u8; footer.video_len as usize];
-    file.seek(SeekFrom::Start(footer.video_offset))
-        .expect("Seek video failed");
-    file.read_exact(&mut video_data).expect("Read video failed");
-
-    let mut audio_data = vec![0u8; footer.audio_len as usize];
-    file.seek(SeekFrom::Start(footer.audio_offset))
-        .expect("Seek audio failed");
-    file.read_exact(&mut audio_data).expect("Read audio failed");
-
-    LoadedAssets {
-        video_data,
-        audio_data,
-        width: footer.width,
-        height: footer.height,
+    /// Loads the video stream closest to the screen size, plus the shared
+    /// audio track.
+    pub fn load(mut self, screen_width: i32, screen_height: i32) -> LoadedAssets {
+        let clamp = |v: i32| v.clamp(0, u16::MAX as i32) as u16;
+        let footer = &self.footer;
+        let video = footer
+            .closest_video(clamp(screen_width), clamp(screen_height))
+            .expect("Payload has no video chunk");
+        let (width, height) = footer.video_size(video);
+        let video_data =
+            PayloadFooter::read_chunk(&mut self.file, video).expect("Read video failed");
+
+        let audio_data = footer.audio().map(|audio| {
+            PayloadFooter::read_chunk(&mut self.file, audio).expect("Read audio failed")
+        });
+
+        LoadedAssets {
+            video_data,
+            video_codec: video.codec,
+            audio_data,
+            width,
+            height,
+        }
     }
 }
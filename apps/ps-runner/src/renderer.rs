@@ -16,8 +16,9 @@ pub struct GdiRenderer {
     buffer: &'static mut [u32],
     screen_w: i32,
     screen_h: i32,
-    scale_x: f64,
-    scale_y: f64,
+    scale: f64,
+    offset_x: usize,
+    offset_y: usize,
 }
 
 impl GdiRenderer {
@@ -48,6 +49,12 @@ impl GdiRenderer {
             let buffer_size = (screen_w * screen_h) as usize;
             let buffer = slice::from_raw_parts_mut(bits_ptr as *mut u32, buffer_size);
 
+            // Fit the video inside the screen and centre it, so other aspect
+            // ratios are letterboxed instead of stretched
+            let scale = (screen_w as f64 / base_w as f64).min(screen_h as f64 / base_h as f64);
+            let offset_x = ((screen_w as f64 - base_w as f64 * scale) / 2.0).round() as usize;
+            let offset_y = ((screen_h as f64 - base_h as f64 * scale) / 2.0).round() as usize;
+
             Self {
                 mem_dc,
                 hbitmap,
@@ -56,8 +63,9 @@ impl GdiRenderer {
                 screen_w,
                 screen_h,
 
-                scale_x: screen_w as f64 / base_w as f64,
-                scale_y: screen_h as f64 / base_h as f64,
+                scale,
+                offset_x,
+                offset_y,
             }
         }
     }
@@ -66,20 +74,30 @@ impl GdiRenderer {
         self.buffer.fill(0x00000000);
     }
 
-    pub fn draw_sparse_rect(&mut self, x: u16, y: u16, w: u16, h: u16) {
-        let left = (x as f64 * self.scale_x).round() as usize;
-        let top = (y as f64 * self.scale_y).round() as usize;
-        let w_scaled = (w as f64 * self.scale_x).round() as usize;
-        let h_scaled = (h as f64 * self.scale_y).round() as usize;
+    /// Draws a rect in a premultiplied `0xAARRGGBB` color, as
+    /// `UpdateLayeredWindow` expects. Translucent colors are blended over
+    /// what is already there, so layers and soft edges composite.
+    pub fn draw_sparse_rect(&mut self, x: u16, y: u16, w: u16, h: u16, color: u32) {
+        let left = self.offset_x + (x as f64 * self.scale).round() as usize;
+        let top = self.offset_y + (y as f64 * self.scale).round() as usize;
+        let w_scaled = (w as f64 * self.scale).round() as usize;
+        let h_scaled = (h as f64 * self.scale).round() as usize;
 
         let right = (left + w_scaled).min(self.screen_w as usize);
         let bottom = (top + h_scaled).min(self.screen_h as usize);
 
         if right > left && bottom > top {
             let width = self.screen_w as usize;
+            let alpha = color >> 24;
             for r in top..bottom {
-                let row_offset = r * width;
-                self.buffer[row_offset + left..row_offset + right].fill(0xFFFFFFFF);
+                let row = &mut self.buffer[r * width + left..r * width + right];
+                match alpha {
+                    0 => {}
+                    255 => row.fill(color),
+                    _ => row
+                        .iter_mut()
+                        .for_each(|dst| *dst = blend_over(color, *dst)),
+                }
             }
         }
     }
@@ -113,6 +131,18 @@ impl GdiRenderer {
     }
 }
 
+/// Premultiplied source-over: `src + dst * (255 - src_alpha) / 255` per
+/// channel.
+#[inline]
+fn blend_over(src: u32, dst: u32) -> u32 {
+    let keep = 255 - (src >> 24);
+    let channel = |shift: u32| {
+        let d = (dst >> shift) & 0xFF;
+        (((src >> shift) & 0xFF) + (d * keep + 127) / 255).min(255) << shift
+    };
+    channel(24) | channel(16) | channel(8) | channel(0)
+}
+
 impl Drop for GdiRenderer {
     fn drop(&mut self) {
         unsafe {
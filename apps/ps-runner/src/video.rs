@@ -0,0 +1,186 @@
+use ps_core::{
+    chunk::{self, Chunks},
+    layer, payload, BlockDecoder, FileHeader, FormatError, FrameDecoder, FrameIndex, Layer,
+    Palette, PixelRect, Timing,
+};
+
+/// What the player needs besides the frames themselves.
+pub struct VideoInfo {
+    pub header: FileHeader,
+    pub palette: Option<Palette>,
+    pub timing: Timing,
+    pub layers: Vec<Layer<'static>>,
+}
+
+/// Frames of the embedded video, either borrowed in place or inflated one
+/// compressed block at a time.
+pub enum VideoStream {
+    Raw {
+        frames: FrameDecoder<'static>,
+        index: Option<FrameIndex>,
+    },
+    Compressed(BlockDecoder<'static>),
+}
+
+impl VideoStream {
+    pub fn new(data: &'static [u8], codec: u16) -> (Self, VideoInfo) {
+        match codec {
+            payload::CODEC_RAW => {
+                let header = FileHeader::parse(data).expect("Corrupt video payload");
+                let info = VideoInfo {
+                    header,
+                    palette: Palette::load(data, &header).expect("Corrupt palette"),
+                    timing: Timing::load(data, &header).expect("Corrupt frame timestamps"),
+                    layers: layer::load(data, &header).expect("Corrupt layer"),
+                };
+                (Self::open(data, codec, &header), info)
+            }
+            payload::CODEC_LZ4 => {
+                let frames = BlockDecoder::new(data).expect("Corrupt video payload");
+                let header = *frames.header();
+                // Layers borrow from the trailer for the rest of the run
+                let trailer: &'static [u8] = Box::leak(
+                    frames
+                        .trailer()
+                        .expect("Corrupt video payload")
+                        .into_boxed_slice(),
+                );
+                let chunk = |tag| Chunks::trailing(trailer).find(tag).expect("Corrupt chunk");
+                let info = VideoInfo {
+                    header,
+                    palette: chunk(chunk::TAG_PALETTE)
+                        .map(|d| Palette::parse(d).expect("Corrupt palette")),
+                    timing: Timing::from_chunk(&header, chunk(chunk::TAG_TIMESTAMPS))
+                        .expect("Corrupt frame timestamps"),
+                    layers: layer::collect(Chunks::trailing(trailer)).expect("Corrupt layer"),
+                };
+                (Self::Compressed(frames), info)
+            }
+            other => panic!("Unsupported video codec {}", other),
+        }
+    }
+
+    /// Opens the stream at its first frame.
+    fn open(data: &'static [u8], codec: u16, header: &FileHeader) -> Self {
+        match codec {
+            payload::CODEC_RAW => Self::Raw {
+                frames: FrameDecoder::for_file(data, header),
+                index: FrameIndex::load(data, header).expect("Corrupt frame index"),
+            },
+            _ => Self::Compressed(BlockDecoder::new(data).expect("Corrupt video payload")),
+        }
+    }
+
+    pub fn next_frame(&mut self) -> Option<Result<&[PixelRect], FormatError>> {
+        match self {
+            Self::Raw { frames, .. } => frames.next_frame(),
+            Self::Compressed(frames) => frames.next_frame(),
+        }
+    }
+
+    /// Catches up so the next frame drawn is `frame`. Returns `false` once
+    /// the video is over.
+    pub fn skip_to(&mut self, frame: usize) -> bool {
+        match self {
+            Self::Raw {
+                frames,
+                index: Some(index),
+            } => matches!(frames.seek(index, frame), Ok(true)),
+            Self::Raw {
+                frames,
+                index: None,
+            } => {
+                while frames.frame() < frame {
+                    if !matches!(frames.skip_frame(), Ok(true)) {
+                        return false;
+                    }
+                }
+                true
+            }
+            Self::Compressed(frames) => matches!(frames.skip_to(frame), Ok(true)),
+        }
+    }
+}
+
+/// The main video or one of its layers, as currently on screen.
+pub struct Track {
+    data: &'static [u8],
+    codec: u16,
+    stream: VideoStream,
+    pub info: VideoInfo,
+    pub opacity: u8,
+    looped: bool,
+    /// Records of the frame on screen.
+    pub rects: Vec<PixelRect>,
+    next_frame: usize,
+    /// Set once the stream has run out; the last frame stays up.
+    pub ended: bool,
+}
+
+impl Track {
+    pub fn new(data: &'static [u8], codec: u16) -> Self {
+        let (stream, info) = VideoStream::new(data, codec);
+        Self {
+            data,
+            codec,
+            stream,
+            info,
+            opacity: 255,
+            looped: false,
+            rects: Vec::new(),
+            next_frame: 0,
+            ended: false,
+        }
+    }
+
+    pub fn from_layer(layer: &Layer<'static>) -> Self {
+        Self {
+            opacity: layer.opacity,
+            looped: layer.is_looped(),
+            ..Self::new(layer.bin, payload::CODEC_RAW)
+        }
+    }
+
+    /// Brings `rects` up to date `seconds` into playback. Returns whether
+    /// the frame changed.
+    pub fn update(&mut self, seconds: f64) -> bool {
+        let mut seconds = seconds;
+        if self.looped {
+            let duration = self
+                .info
+                .timing
+                .time_of(self.info.header.frame_count as usize);
+            if duration > 0.0 {
+                seconds %= duration;
+            }
+        }
+        let due = self.info.timing.frame_at(seconds);
+        if due + 1 < self.next_frame {
+            // Wrapped around
+            self.stream = VideoStream::open(self.data, self.codec, &self.info.header);
+            self.next_frame = 0;
+            self.ended = false;
+        }
+        if self.ended || due < self.next_frame {
+            return false;
+        }
+
+        // Fell behind: jump straight to the frame that is due
+        if due > self.next_frame && !self.stream.skip_to(due) {
+            self.ended = true;
+            return false;
+        }
+        match self.stream.next_frame() {
+            Some(Ok(rects)) => {
+                self.rects.clear();
+                self.rects.extend_from_slice(rects);
+                self.next_frame = due + 1;
+                true
+            }
+            _ => {
+                self.ended = true;
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::{level, palette, FileHeader, Palette, PixelRect};
+
+/// Tracks the fill set by a frame's control records (intensity level and
+/// palette color) while drawing it.
+pub struct Brush<'p> {
+    levels: u16,
+    palette: Option<&'p Palette>,
+    rgb: u32,
+    alpha: u8,
+    opacity: u8,
+}
+
+impl<'p> Brush<'p> {
+    pub fn new(header: &FileHeader, palette: Option<&'p Palette>) -> Self {
+        Self {
+            levels: header.levels,
+            palette,
+            rgb: palette::WHITE,
+            alpha: 255,
+            opacity: 255,
+        }
+    }
+
+    /// Scales every color by `opacity`, e.g. for a translucent layer.
+    pub fn with_opacity(mut self, opacity: u8) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    /// Back to opaque white; call at the start of every frame.
+    pub fn reset(&mut self) {
+        self.rgb = palette::WHITE;
+        self.alpha = 255;
+    }
+
+    /// Applies `record` if it is a control record. Returns `false` for
+    /// rects that should be drawn.
+    pub fn apply(&mut self, record: &PixelRect) -> bool {
+        if !record.is_control() {
+            return false;
+        }
+        if let Some(l) = level::level_of(record) {
+            self.alpha = level::level_alpha(l, self.levels);
+        } else if let Some(i) = palette::color_of(record) {
+            self.rgb = self.palette.map_or(palette::WHITE, |p| p.get(i));
+        }
+        true
+    }
+
+    /// Current fill as premultiplied `0xAARRGGBB`.
+    pub fn color(&self) -> u32 {
+        let alpha = self.alpha as u32 * self.opacity as u32 / 255;
+        palette::premultiplied(self.rgb, alpha as u8)
+    }
+}
@@ -0,0 +1,108 @@
+//! Optional chunks stored after the rect stream of a `.bin` file.
+//!
+//! Each chunk is a 4-byte tag, a little-endian `u32` length and the payload.
+//! Readers skip tags they don't know, so new chunk kinds can be added without
+//! bumping the format version.
+
+use crate::{FileHeader, FormatError};
+
+pub const CHUNK_HEADER_SIZE: usize = 8;
+
+/// Frame offset table, see [`crate::FrameIndex`].
+pub const TAG_FRAME_INDEX: [u8; 4] = *b"FIDX";
+/// Color table, see [`crate::palette`].
+pub const TAG_PALETTE: [u8; 4] = *b"PLTE";
+/// Per-frame presentation times, see [`crate::timing`].
+pub const TAG_TIMESTAMPS: [u8; 4] = *b"TIME";
+/// Extra layer, see [`crate::layer`].
+pub const TAG_LAYER: [u8; 4] = *b"LAYR";
+/// Key/value metadata, see [`crate::metadata`].
+pub const TAG_METADATA: [u8; 4] = *b"META";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk<'a> {
+    pub tag: [u8; 4],
+    pub data: &'a [u8],
+}
+
+/// Iterates the chunks that follow the rect stream.
+pub struct Chunks<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Chunks<'a> {
+    pub fn new(bytes: &'a [u8], header: &FileHeader) -> Self {
+        Self {
+            bytes,
+            pos: header.data_range().end.min(bytes.len()),
+        }
+    }
+
+    /// Iterates chunks stored back to back from the start of `bytes`.
+    pub fn trailing(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// Returns the first chunk with the given tag.
+    pub fn find(self, tag: [u8; 4]) -> Result<Option<&'a [u8]>, FormatError> {
+        for chunk in self {
+            let chunk = chunk?;
+            if chunk.tag == tag {
+                return Ok(Some(chunk.data));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Result<Chunk<'a>, FormatError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.bytes[self.pos..];
+        if rest.is_empty() {
+            return None;
+        }
+        if rest.len() < CHUNK_HEADER_SIZE {
+            self.pos = self.bytes.len();
+            return Some(Err(FormatError::Truncated {
+                needed: CHUNK_HEADER_SIZE,
+                available: rest.len(),
+            }));
+        }
+
+        let tag = [rest[0], rest[1], rest[2], rest[3]];
+        let len = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        let end = CHUNK_HEADER_SIZE + len;
+        if rest.len() < end {
+            self.pos = self.bytes.len();
+            return Some(Err(FormatError::Truncated {
+                needed: end,
+                available: rest.len(),
+            }));
+        }
+
+        self.pos += end;
+        Some(Ok(Chunk {
+            tag,
+            data: &rest[CHUNK_HEADER_SIZE..end],
+        }))
+    }
+}
+
+/// Returns the first chunk with the given tag, if the file has one.
+pub fn find_chunk<'a>(
+    bytes: &'a [u8],
+    header: &FileHeader,
+    tag: [u8; 4],
+) -> Result<Option<&'a [u8]>, FormatError> {
+    Chunks::new(bytes, header).find(tag)
+}
+
+/// Appends a chunk (tag, length, payload) to `out`.
+pub fn encode_chunk(out: &mut Vec<u8>, tag: [u8; 4], data: &[u8]) {
+    out.extend_from_slice(&tag);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+}
@@ -0,0 +1,496 @@
+//! LZ4 block compression of whole `.bin` files for embedding in the runner.
+//!
+//! The rect stream is split into blocks that each hold whole frames, so a
+//! player can inflate one block at a time while it plays. Layout, all fields
+//! little-endian:
+//!
+//! ```text
+//! 0   magic        [u8; 4]  "PSLZ"
+//! 4   version      u16
+//! 6   reserved     u16
+//! 8   raw_len      u64      size of the original .bin file
+//! 16  prefix_len   u32      header bytes, stored uncompressed
+//! 20  block_count  u32
+//! 24  block table  block_count x { first_frame u32, raw_len u32, comp_len u32 }
+//!     prefix       [u8; prefix_len]
+//!     blocks       LZ4 block data, in table order
+//! ```
+//!
+//! Bytes after the rect stream (index chunks etc.) form the last block.
+
+use crate::{
+    chunk::Chunks,
+    decoder::DeltaState,
+    file_header::{FLAG_DELTA, FLAG_VARINT},
+    frame::{self, check_bounds},
+    varint, FileHeader, FormatError, FrameIndex, PixelRect,
+};
+use std::ops::Range;
+
+pub const COMPRESSED_MAGIC: [u8; 4] = *b"PSLZ";
+pub const COMPRESSED_VERSION: u16 = 1;
+
+/// Target amount of raw data per block.
+pub const BLOCK_SIZE: usize = 1 << 20;
+
+const PREAMBLE_SIZE: usize = 24;
+const ENTRY_SIZE: usize = 12;
+
+/// LZ4 spends at least a byte per 255 bytes of output, so no block inflates
+/// to more than this many times its compressed size (plus a few bytes).
+const MAX_RATIO: usize = 255;
+
+#[derive(Debug, Clone)]
+struct Block {
+    first_frame: usize,
+    /// Range in the original file.
+    raw: Range<usize>,
+    /// Range in the compressed data.
+    comp: Range<usize>,
+}
+
+/// Compresses a complete `.bin` file.
+pub fn compress(bin: &[u8]) -> Result<Vec<u8>, FormatError> {
+    let header = FileHeader::parse(bin)?;
+    let index = FrameIndex::load_or_scan(bin, &header)?;
+    let stream = header.data_range();
+
+    // Group whole frames into blocks of roughly BLOCK_SIZE
+    let mut ranges: Vec<(usize, Range<usize>)> = Vec::new();
+    let (mut first_frame, mut start) = (0, 0);
+    for frame in 0..index.len() {
+        let end = index.frame_range(frame).map_or(start, |r| r.end);
+        if end - start >= BLOCK_SIZE {
+            ranges.push((first_frame, stream.start + start..stream.start + end));
+            (first_frame, start) = (frame + 1, end);
+        }
+    }
+    if stream.start + start < stream.end {
+        ranges.push((first_frame, stream.start + start..stream.end));
+    }
+    if stream.end < bin.len() {
+        ranges.push((index.len(), stream.end..bin.len()));
+    }
+
+    let packed: Vec<Vec<u8>> = ranges
+        .iter()
+        .map(|(_, raw)| lz4_flex::block::compress(&bin[raw.clone()]))
+        .collect();
+
+    let mut out = Vec::with_capacity(
+        PREAMBLE_SIZE
+            + ranges.len() * ENTRY_SIZE
+            + stream.start
+            + packed.iter().map(Vec::len).sum::<usize>(),
+    );
+    out.extend_from_slice(&COMPRESSED_MAGIC);
+    out.extend_from_slice(&COMPRESSED_VERSION.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&(bin.len() as u64).to_le_bytes());
+    out.extend_from_slice(&(stream.start as u32).to_le_bytes());
+    out.extend_from_slice(&(ranges.len() as u32).to_le_bytes());
+    for ((first_frame, raw), data) in ranges.iter().zip(&packed) {
+        out.extend_from_slice(&(*first_frame as u32).to_le_bytes());
+        out.extend_from_slice(&(raw.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    }
+    out.extend_from_slice(&bin[..stream.start]);
+    for data in &packed {
+        out.extend_from_slice(data);
+    }
+    Ok(out)
+}
+
+/// Inflates compressed data back into the original `.bin` file.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, FormatError> {
+    let (raw_len, prefix, blocks) = parse(data)?;
+    let mut out = Vec::with_capacity(raw_len);
+    out.extend_from_slice(prefix);
+    for (i, block) in blocks.iter().enumerate() {
+        out.resize(block.raw.end, 0);
+        inflate(data, block, i, &mut out[block.raw.clone()])?;
+    }
+    Ok(out)
+}
+
+fn parse(data: &[u8]) -> Result<(usize, &[u8], Vec<Block>), FormatError> {
+    if data.len() < PREAMBLE_SIZE {
+        return Err(FormatError::Truncated {
+            needed: PREAMBLE_SIZE,
+            available: data.len(),
+        });
+    }
+    if data[0..4] != COMPRESSED_MAGIC {
+        return Err(FormatError::BadMagic);
+    }
+    let u32_at = |o: usize| u32::from_le_bytes(data[o..o + 4].try_into().unwrap()) as usize;
+    let version = u16::from_le_bytes([data[4], data[5]]);
+    if version != COMPRESSED_VERSION {
+        return Err(FormatError::UnsupportedVersion(version));
+    }
+
+    let raw_len = usize::try_from(u64::from_le_bytes(data[8..16].try_into().unwrap()))
+        .map_err(|_| FormatError::InvalidField("raw_len"))?;
+    let prefix_len = u32_at(16);
+    let count = u32_at(20);
+    let needed = count
+        .checked_mul(ENTRY_SIZE)
+        .and_then(|table| (PREAMBLE_SIZE + prefix_len).checked_add(table))
+        .unwrap_or(usize::MAX);
+    if data.len() < needed {
+        return Err(FormatError::Truncated {
+            needed,
+            available: data.len(),
+        });
+    }
+    let prefix_start = needed - prefix_len;
+
+    // Blocks follow each other in both the original and the compressed
+    // data. Sizes are checked against what their data could inflate to
+    // before anything is allocated for them.
+    let mut blocks = Vec::with_capacity(count);
+    let (mut raw, mut comp) = (prefix_len, needed);
+    for i in 0..count {
+        let e = PREAMBLE_SIZE + i * ENTRY_SIZE;
+        let (block_raw, block_comp) = (u32_at(e + 4), u32_at(e + 8));
+        if block_raw > block_comp.saturating_mul(MAX_RATIO) + 16 {
+            return Err(FormatError::CorruptBlock { block: i });
+        }
+        let raw_end = raw
+            .checked_add(block_raw)
+            .filter(|&end| end <= raw_len)
+            .ok_or(FormatError::InvalidField("raw_len"))?;
+        let comp_end = comp
+            .checked_add(block_comp)
+            .filter(|&end| end <= data.len())
+            .ok_or(FormatError::Truncated {
+                needed: comp.saturating_add(block_comp),
+                available: data.len(),
+            })?;
+        blocks.push(Block {
+            first_frame: u32_at(e),
+            raw: raw..raw_end,
+            comp: comp..comp_end,
+        });
+        (raw, comp) = (raw_end, comp_end);
+    }
+    if raw != raw_len {
+        return Err(FormatError::InvalidField("raw_len"));
+    }
+    Ok((raw_len, &data[prefix_start..needed], blocks))
+}
+
+fn inflate(data: &[u8], block: &Block, i: usize, out: &mut [u8]) -> Result<(), FormatError> {
+    match lz4_flex::block::decompress_into(&data[block.comp.clone()], out) {
+        Ok(n) if n == out.len() => Ok(()),
+        _ => Err(FormatError::CorruptBlock { block: i }),
+    }
+}
+
+/// Plays compressed video, inflating one block at a time.
+pub struct BlockDecoder<'a> {
+    data: &'a [u8],
+    header: FileHeader,
+    blocks: Vec<Block>,
+    next_block: usize,
+    block: Vec<u8>,
+    pos: usize,
+    frame: usize,
+    varint: bool,
+    /// Decoded records of compact streams.
+    rects: Vec<PixelRect>,
+    delta: Option<DeltaState>,
+}
+
+impl<'a> BlockDecoder<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, FormatError> {
+        let (raw_len, prefix, blocks) = parse(data)?;
+        let header = FileHeader::parse_prefix(prefix, raw_len)?;
+        Ok(Self {
+            data,
+            header,
+            blocks,
+            next_block: 0,
+            block: Vec::new(),
+            pos: 0,
+            frame: 0,
+            varint: header.flags & FLAG_VARINT != 0,
+            rects: Vec::new(),
+            delta: (header.flags & FLAG_DELTA != 0).then(DeltaState::default),
+        })
+    }
+
+    pub fn header(&self) -> &FileHeader {
+        &self.header
+    }
+
+    /// Index of the frame the next call to `next_frame` returns.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Inflates the bytes after the rect stream, i.e. the trailing chunks.
+    /// Iterate them with [`Chunks::trailing`].
+    pub fn trailer(&self) -> Result<Vec<u8>, FormatError> {
+        let stream_end = self.header.data_range().end;
+        let mut trailer = Vec::new();
+        for (i, block) in self.blocks.iter().enumerate() {
+            if block.raw.start >= stream_end {
+                let start = trailer.len();
+                trailer.resize(start + block.raw.len(), 0);
+                inflate(self.data, block, i, &mut trailer[start..])?;
+            }
+        }
+        Ok(trailer)
+    }
+
+    /// Returns the first trailing chunk with the given tag.
+    pub fn find_chunk(&self, tag: [u8; 4]) -> Result<Option<Vec<u8>>, FormatError> {
+        let trailer = self.trailer()?;
+        Ok(Chunks::trailing(&trailer).find(tag)?.map(<[u8]>::to_vec))
+    }
+
+    /// Inflates the next block of the rect stream. Returns `false` once the
+    /// stream is exhausted.
+    fn load_next_block(&mut self) -> Result<bool, FormatError> {
+        let i = self.next_block;
+        let Some(block) = self.blocks.get(i) else {
+            return Ok(false);
+        };
+        if block.raw.start >= self.header.data_range().end {
+            return Ok(false);
+        }
+        self.block.resize(block.raw.len(), 0);
+        inflate(self.data, block, i, &mut self.block)?;
+        self.next_block += 1;
+        self.pos = 0;
+        Ok(true)
+    }
+
+    pub fn next_frame(&mut self) -> Option<Result<&[PixelRect], FormatError>> {
+        if self.pos >= self.block.len() {
+            match self.load_next_block() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        let (w, h) = (self.header.width, self.header.height);
+        let rest = &self.block[self.pos..];
+        let decode = if self.varint {
+            varint::decode_frame
+        } else {
+            frame::decode_frame
+        };
+        let read = decode(rest, &mut self.rects, self.frame).and_then(|used| {
+            check_bounds(&self.rects, w, h, self.frame)?;
+            Ok((used, &self.rects[..]))
+        });
+        let records = match read {
+            Ok((used, records)) => {
+                self.pos += used;
+                records
+            }
+            Err(e) => {
+                self.pos = self.block.len();
+                self.next_block = self.blocks.len();
+                return Some(Err(e));
+            }
+        };
+        let frame = self.frame;
+        self.frame += 1;
+
+        match &mut self.delta {
+            Some(state) => Some(state.decode(records, frame)),
+            None => Some(Ok(records)),
+        }
+    }
+
+    /// Decodes and discards one frame. Returns `false` at the end of the
+    /// stream.
+    pub fn skip_frame(&mut self) -> Result<bool, FormatError> {
+        match self.next_frame() {
+            Some(Ok(_)) => Ok(true),
+            Some(Err(e)) => Err(e),
+            None => Ok(false),
+        }
+    }
+
+    /// Advances so that the next call to `next_frame` returns `frame`.
+    /// Returns `false` if the stream ends first. Only moves forward.
+    ///
+    /// Plain files skip whole blocks without inflating them; delta files
+    /// have to replay every frame.
+    pub fn skip_to(&mut self, frame: usize) -> Result<bool, FormatError> {
+        if self.delta.is_none() {
+            let stream_end = self.header.data_range().end;
+            let ahead = self.blocks[self.next_block..]
+                .iter()
+                .rposition(|b| b.first_frame <= frame && b.raw.start < stream_end);
+            if let Some(offset) = ahead.filter(|&o| o > 0) {
+                self.next_block += offset;
+                self.frame = self.blocks[self.next_block].first_frame;
+                self.block.clear();
+                self.pos = 0;
+            }
+        }
+        while self.frame < frame {
+            if !self.skip_frame()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BinWriter, FrameDecoder};
+    use std::io::Cursor;
+
+    /// A small delta file, or a plain one spanning several blocks.
+    fn sample_bin(delta: bool) -> Vec<u8> {
+        let mut writer = BinWriter::new(Cursor::new(Vec::new()), 640, 480, 30)
+            .unwrap()
+            .with_frame_index();
+        let (frames, rects) = if delta {
+            writer = writer.with_delta(4).unwrap();
+            (40, 12)
+        } else {
+            (300, 600)
+        };
+        for i in 0..frames {
+            let rects: Vec<PixelRect> = (0..rects)
+                .map(|j| PixelRect {
+                    x: (i + j) % 600,
+                    y: j % 470,
+                    w: 4,
+                    h: 2,
+                })
+                .collect();
+            writer.write_frame(&rects).unwrap();
+        }
+        writer.finish().unwrap().0.into_inner()
+    }
+
+    #[test]
+    fn decompress_restores_the_file() {
+        for delta in [true, false] {
+            let bin = sample_bin(delta);
+            let packed = compress(&bin).unwrap();
+            assert!(packed.len() < bin.len());
+            assert_eq!(decompress(&packed).unwrap(), bin);
+        }
+    }
+
+    #[test]
+    fn block_decoder_matches_the_raw_file() {
+        for delta in [true, false] {
+            let bin = sample_bin(delta);
+            let packed = compress(&bin).unwrap();
+            let header = FileHeader::parse(&bin).unwrap();
+            let mut expected = FrameDecoder::for_file(&bin, &header);
+            let mut blocks = BlockDecoder::new(&packed).unwrap();
+            assert_eq!(blocks.header(), &header);
+            while let Some(frame) = expected.next_frame() {
+                assert_eq!(blocks.next_frame().unwrap(), frame);
+            }
+            assert!(blocks.next_frame().is_none());
+        }
+    }
+
+    #[test]
+    fn skip_to_lands_on_the_frame() {
+        let bin = sample_bin(false);
+        let header = FileHeader::parse(&bin).unwrap();
+        let index = FrameIndex::load_or_scan(&bin, &header).unwrap();
+        let mut expected = FrameDecoder::for_file(&bin, &header);
+        assert_eq!(expected.seek(&index, 250), Ok(true));
+
+        let packed = compress(&bin).unwrap();
+        let mut blocks = BlockDecoder::new(&packed).unwrap();
+        assert_eq!(blocks.skip_to(250), Ok(true));
+        assert_eq!(blocks.frame(), 250);
+        assert_eq!(blocks.next_frame(), expected.next_frame());
+        assert_eq!(blocks.skip_to(1000), Ok(false));
+    }
+
+    #[test]
+    fn truncated_data_is_an_error() {
+        let packed = compress(&sample_bin(true)).unwrap();
+        for len in 0..packed.len() {
+            assert!(decompress(&packed[..len]).is_err(), "{len} bytes");
+            assert!(BlockDecoder::new(&packed[..len]).is_err(), "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn inflated_sizes_are_bounded() {
+        let bin = sample_bin(true);
+        let packed = compress(&bin).unwrap();
+        let u64_at = |data: &mut [u8], at: usize, v: u64| {
+            data[at..at + 8].copy_from_slice(&v.to_le_bytes());
+        };
+
+        // A huge total that the blocks don't add up to
+        let mut bad = packed.clone();
+        u64_at(&mut bad, 8, u64::MAX / 2);
+        assert_eq!(decompress(&bad), Err(FormatError::InvalidField("raw_len")));
+
+        // A block claiming far more than its data could hold, with the
+        // total raised to match
+        let mut bad = packed.clone();
+        let first = PREAMBLE_SIZE;
+        let comp_len = u32::from_le_bytes(bad[first + 8..first + 12].try_into().unwrap());
+        let claimed = comp_len * MAX_RATIO as u32 + 17;
+        let old_len = u32::from_le_bytes(bad[first + 4..first + 8].try_into().unwrap());
+        bad[first + 4..first + 8].copy_from_slice(&claimed.to_le_bytes());
+        u64_at(
+            &mut bad,
+            8,
+            (bin.len() + (claimed - old_len) as usize) as u64,
+        );
+        assert_eq!(
+            decompress(&bad),
+            Err(FormatError::CorruptBlock { block: 0 })
+        );
+        assert!(BlockDecoder::new(&bad).is_err());
+
+        // A block table too long for the data
+        let mut bad = packed.clone();
+        bad[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            decompress(&bad),
+            Err(FormatError::Truncated { .. })
+        ));
+
+        // Highly compressible data is still within the bound
+        let zeros = vec![0; 1 << 20];
+        let packed = lz4_flex::block::compress(&zeros);
+        assert!(zeros.len() <= packed.len() * MAX_RATIO + 16);
+    }
+
+    #[test]
+    fn damaged_data_never_panics() {
+        let packed = compress(&sample_bin(true)).unwrap();
+
+        let mut bad = packed.clone();
+        bad[0] ^= 0xFF;
+        assert_eq!(decompress(&bad), Err(FormatError::BadMagic));
+        let mut bad = packed.clone();
+        bad[4] = 9;
+        assert_eq!(decompress(&bad), Err(FormatError::UnsupportedVersion(9)));
+
+        // A flipped byte may still decode; it just mustn't crash
+        for i in 0..packed.len() {
+            let mut bad = packed.clone();
+            bad[i] ^= 0x5A;
+            let _ = decompress(&bad);
+            if let Ok(mut blocks) = BlockDecoder::new(&bad) {
+                while let Some(Ok(_)) = blocks.next_frame() {}
+            }
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{
+    delta::{self, is_delta_frame},
+    file_header::{FLAG_DELTA, FLAG_VARINT},
+    varint::VarintReader,
+    FileHeader, FormatError, FrameIndex, FrameReader, PixelRect,
+};
+
+/// Frame reconstruction state for delta-encoded streams.
+#[derive(Default)]
+pub(crate) struct DeltaState {
+    current: Vec<PixelRect>,
+}
+
+impl DeltaState {
+    /// Applies one frame's records and returns the complete frame.
+    pub(crate) fn decode(
+        &mut self,
+        records: &[PixelRect],
+        frame: usize,
+    ) -> Result<&[PixelRect], FormatError> {
+        if is_delta_frame(records) {
+            delta::apply_delta(&mut self.current, records, frame)?;
+        } else {
+            self.current.clear();
+            self.current.extend_from_slice(records);
+        }
+        Ok(&self.current)
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.current.clear();
+    }
+}
+
+/// Reads the records of each frame in either stream encoding.
+enum Records<'a> {
+    Fixed(FrameReader<'a>),
+    Varint(VarintReader<'a>),
+}
+
+impl Records<'_> {
+    fn frame(&self) -> usize {
+        match self {
+            Self::Fixed(r) => r.frame(),
+            Self::Varint(r) => r.frame(),
+        }
+    }
+
+    fn seek(&mut self, index: &FrameIndex, frame: usize) -> bool {
+        match self {
+            Self::Fixed(r) => r.seek(index, frame),
+            Self::Varint(r) => r.seek(index, frame),
+        }
+    }
+
+    fn next(&mut self) -> Option<Result<&[PixelRect], FormatError>> {
+        match self {
+            Self::Fixed(r) => r.next_frame(),
+            Self::Varint(r) => r.next_frame(),
+        }
+    }
+}
+
+/// Turns the records of a rect stream into complete frames.
+///
+/// Plain files are passed through from the underlying reader; delta-encoded
+/// files are reconstructed against the previous frame.
+pub struct FrameDecoder<'a> {
+    reader: Records<'a>,
+    delta: Option<DeltaState>,
+}
+
+impl<'a> FrameDecoder<'a> {
+    pub fn new(reader: FrameReader<'a>, delta: bool) -> Self {
+        Self {
+            reader: Records::Fixed(reader),
+            delta: delta.then(DeltaState::default),
+        }
+    }
+
+    /// Decoder for a stream in the compact encoding.
+    pub fn new_varint(reader: VarintReader<'a>, delta: bool) -> Self {
+        Self {
+            reader: Records::Varint(reader),
+            delta: delta.then(DeltaState::default),
+        }
+    }
+
+    pub fn for_file(bytes: &'a [u8], header: &FileHeader) -> Self {
+        let delta = header.flags & FLAG_DELTA != 0;
+        if header.flags & FLAG_VARINT != 0 {
+            let stream = &bytes[header.data_range()];
+            Self::new_varint(
+                VarintReader::new(stream, header.width, header.height),
+                delta,
+            )
+        } else {
+            Self::new(FrameReader::for_file(bytes, header), delta)
+        }
+    }
+
+    /// Index of the frame the next call to `next_frame` returns.
+    pub fn frame(&self) -> usize {
+        self.reader.frame()
+    }
+
+    pub fn next_frame(&mut self) -> Option<Result<&[PixelRect], FormatError>> {
+        let frame = self.reader.frame();
+        let records = match self.reader.next()? {
+            Ok(records) => records,
+            Err(e) => return Some(Err(e)),
+        };
+        match &mut self.delta {
+            Some(state) => Some(state.decode(records, frame)),
+            None => Some(Ok(records)),
+        }
+    }
+
+    /// Decodes and discards one frame. Returns `false` at the end of the
+    /// stream.
+    pub fn skip_frame(&mut self) -> Result<bool, FormatError> {
+        match self.next_frame() {
+            Some(Ok(_)) => Ok(true),
+            Some(Err(e)) => Err(e),
+            None => Ok(false),
+        }
+    }
+
+    /// Positions the decoder so that the next call to `next_frame` returns
+    /// `frame`. Returns `false` if the frame is out of range.
+    ///
+    /// Delta files are rewound to the closest keyframe and replayed forward,
+    /// unless `frame` is ahead with no keyframe in between; then decoding
+    /// simply carries on from the current frame.
+    pub fn seek(&mut self, index: &FrameIndex, frame: usize) -> Result<bool, FormatError> {
+        if frame >= index.len() {
+            return Ok(false);
+        }
+        if self.delta.is_none() {
+            return Ok(self.reader.seek(index, frame));
+        }
+
+        let current = self.reader.frame();
+        let mut key = frame;
+        let rewind = loop {
+            if key == current {
+                break false;
+            }
+            if !self.reader.seek(index, key) {
+                return Ok(false);
+            }
+            match self.reader.next() {
+                Some(Ok(records)) if !is_delta_frame(records) || key == 0 => break true,
+                Some(Ok(_)) => key -= 1,
+                Some(Err(e)) => return Err(e),
+                None => return Ok(false),
+            }
+        };
+
+        self.reader.seek(index, key);
+        if rewind {
+            if let Some(state) = &mut self.delta {
+                state.reset();
+            }
+        }
+        for _ in key..frame {
+            if !self.skip_frame()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BinWriter;
+    use std::io::Cursor;
+
+    /// Frames where one rect moves per step, so most become deltas.
+    fn frames() -> Vec<Vec<PixelRect>> {
+        (0..30u16)
+            .map(|f| {
+                (0..10u16)
+                    .map(|i| PixelRect {
+                        x: i * 6 + (f + i) / 10 % 3,
+                        y: i * 4,
+                        w: 5,
+                        h: 3,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn sorted(rects: &[PixelRect]) -> Vec<PixelRect> {
+        let mut rects = rects.to_vec();
+        rects.sort_by_key(|r| (r.y, r.x, r.w, r.h));
+        rects
+    }
+
+    fn write_bin(delta: bool, compact: bool) -> Vec<u8> {
+        let mut writer = BinWriter::new(Cursor::new(Vec::new()), 64, 48, 30)
+            .unwrap()
+            .with_frame_index();
+        if compact {
+            writer = writer.with_varint().unwrap();
+        }
+        if delta {
+            writer = writer.with_delta(8).unwrap();
+        }
+        for frame in frames() {
+            writer.write_frame(&frame).unwrap();
+        }
+        writer.finish().unwrap().0.into_inner()
+    }
+
+    #[test]
+    fn decodes_every_frame() {
+        for (delta, compact) in [(false, false), (true, false), (false, true), (true, true)] {
+            let bytes = write_bin(delta, compact);
+            let header = FileHeader::parse(&bytes).unwrap();
+            let mut decoder = FrameDecoder::for_file(&bytes, &header);
+            for frame in frames() {
+                assert_eq!(sorted(decoder.next_frame().unwrap().unwrap()), frame);
+            }
+            assert!(decoder.next_frame().is_none());
+        }
+    }
+
+    #[test]
+    fn seeks_to_any_frame() {
+        let expected = frames();
+        for (delta, compact) in [(false, false), (true, false), (false, true), (true, true)] {
+            let bytes = write_bin(delta, compact);
+            let header = FileHeader::parse(&bytes).unwrap();
+            let index = FrameIndex::load_or_scan(&bytes, &header).unwrap();
+            let mut decoder = FrameDecoder::for_file(&bytes, &header);
+            for target in [5, 6, 7, 9, 29, 0, 17, 16, 3, 12] {
+                assert_eq!(decoder.seek(&index, target), Ok(true));
+                let frame = decoder.next_frame().unwrap().unwrap();
+                assert_eq!(sorted(frame), expected[target], "frame {target}");
+            }
+            assert_eq!(decoder.seek(&index, expected.len()), Ok(false));
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//! Inter-frame delta encoding.
+//!
+//! In files with [`FLAG_DELTA`](crate::file_header::FLAG_DELTA) set, a frame
+//! is either a keyframe (a plain rect list) or a delta against the previous
+//! frame:
+//!
+//! ```text
+//! DELTA_REMOVE  removed rects...  DELTA_ADD  added rects...  EOS
+//! ```
+//!
+//! The markers are control records (zero width, non-zero height), which never
+//! describe pixels.
+//!
+//! Other control records (intensity levels, ...) split a frame into groups:
+//! each run of controls applies to the rects that follow it. Rects are
+//! matched by their group as well as their position, copies included, and
+//! the removed/added lists repeat the controls of every group they touch.
+//! Applying a delta keeps the groups of the previous frame in their order
+//! and appends new ones, so frames that reorder their groups are stored as
+//! keyframes.
+
+use crate::{FormatError, PixelRect};
+use std::collections::HashMap;
+
+/// Opens the list of rects removed since the previous frame.
+pub const DELTA_REMOVE: PixelRect = PixelRect::control(1);
+/// Opens the list of rects added since the previous frame.
+pub const DELTA_ADD: PixelRect = PixelRect::control(2);
+
+#[inline]
+pub fn is_delta_frame(records: &[PixelRect]) -> bool {
+    records.first() == Some(&DELTA_REMOVE)
+}
+
+/// Splits records into `(controls, rects)` groups, in order.
+pub fn groups(records: &[PixelRect]) -> Vec<(&[PixelRect], &[PixelRect])> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < records.len() {
+        let key = i;
+        while i < records.len() && records[i].is_control() {
+            i += 1;
+        }
+        let rects = i;
+        while i < records.len() && !records[i].is_control() {
+            i += 1;
+        }
+        out.push((&records[key..rects], &records[rects..i]));
+    }
+    out
+}
+
+/// How many copies of each rect a frame has, by group.
+type Counts<'a> = HashMap<(&'a [PixelRect], &'a PixelRect), usize>;
+
+fn counts(records: &[PixelRect]) -> Counts<'_> {
+    let mut counts = Counts::new();
+    for (key, rects) in groups(records) {
+        for rect in rects {
+            *counts.entry((key, rect)).or_default() += 1;
+        }
+    }
+    counts
+}
+
+/// Appends the rects of `records` that `other` has fewer copies of, with
+/// their controls.
+fn push_missing<'a>(out: &mut Vec<PixelRect>, records: &'a [PixelRect], other: &Counts<'a>) {
+    let mut seen = Counts::new();
+    for (key, rects) in groups(records) {
+        let mut missing = rects
+            .iter()
+            .filter(|&rect| {
+                let n = seen.entry((key, rect)).or_default();
+                *n += 1;
+                *n > other.get(&(key, rect)).copied().unwrap_or(0)
+            })
+            .peekable();
+        if missing.peek().is_some() {
+            out.extend_from_slice(key);
+            out.extend(missing);
+        }
+    }
+}
+
+/// Whether applying a delta to `previous` gives the groups of `current` in
+/// their order: those already in `previous` keep its order, new ones follow.
+fn keeps_group_order(previous: &[PixelRect], current: &[PixelRect]) -> bool {
+    let mut order: Vec<&[PixelRect]> = Vec::new();
+    for (key, _) in groups(previous) {
+        if !order.contains(&key) {
+            order.push(key);
+        }
+    }
+
+    let mut seen: Vec<&[PixelRect]> = Vec::new();
+    let (mut last, mut new_groups) = (None, false);
+    for (key, rects) in groups(current) {
+        // Empty groups are dropped and repeated ones merged
+        if rects.is_empty() || seen.contains(&key) {
+            return false;
+        }
+        seen.push(key);
+        match order.iter().position(|&k| k == key) {
+            Some(i) if !new_groups && last.is_none_or(|l| i > l) => last = Some(i),
+            Some(_) => return false,
+            None => new_groups = true,
+        }
+    }
+    true
+}
+
+/// Encodes `current` against `previous` into `out`.
+///
+/// Returns `false` (leaving `out` empty) when the delta would not be smaller
+/// than storing `current` as a keyframe.
+pub fn encode_delta(
+    previous: &[PixelRect],
+    current: &[PixelRect],
+    out: &mut Vec<PixelRect>,
+) -> bool {
+    out.clear();
+    if !keeps_group_order(previous, current) {
+        return false;
+    }
+    out.push(DELTA_REMOVE);
+    push_missing(out, previous, &counts(current));
+    out.push(DELTA_ADD);
+    push_missing(out, current, &counts(previous));
+
+    if out.len() >= current.len() {
+        out.clear();
+        return false;
+    }
+    true
+}
+
+type Group = (Vec<PixelRect>, Vec<PixelRect>);
+
+/// Index of the group with `key`, added if missing.
+fn group(grouped: &mut Vec<Group>, key: &[PixelRect]) -> usize {
+    match grouped.iter().position(|(k, _)| k == key) {
+        Some(i) => i,
+        None => {
+            grouped.push((key.to_vec(), Vec::new()));
+            grouped.len() - 1
+        }
+    }
+}
+
+/// Removes one copy of each of `removed` from `rects`, keeping the order of
+/// the rest. Returns `false` if `rects` lacks any of them.
+fn remove_each(rects: &mut Vec<PixelRect>, removed: &[PixelRect]) -> bool {
+    let mut pending: HashMap<PixelRect, usize> = HashMap::new();
+    for &rect in removed {
+        *pending.entry(rect).or_default() += 1;
+    }
+    let before = rects.len();
+    rects.retain(|rect| match pending.get_mut(rect) {
+        Some(n) if *n > 0 => {
+            *n -= 1;
+            false
+        }
+        _ => true,
+    });
+    before - rects.len() == removed.len()
+}
+
+/// Applies a delta frame's records to `current` in place.
+pub fn apply_delta(
+    current: &mut Vec<PixelRect>,
+    records: &[PixelRect],
+    frame: usize,
+) -> Result<(), FormatError> {
+    let invalid = FormatError::InvalidDelta { frame };
+    let body = records
+        .strip_prefix(&[DELTA_REMOVE])
+        .ok_or(invalid.clone())?;
+    let split = body
+        .iter()
+        .position(|r| *r == DELTA_ADD)
+        .ok_or(invalid.clone())?;
+    let (removed, added) = (&body[..split], &body[split + 1..]);
+
+    // Plain frames are a single group; skip the regrouping.
+    if !current
+        .iter()
+        .chain(body)
+        .any(|r| r.is_control() && *r != DELTA_ADD)
+    {
+        if !remove_each(current, removed) {
+            return Err(invalid);
+        }
+        current.extend_from_slice(added);
+        return Ok(());
+    }
+
+    let mut grouped: Vec<Group> = Vec::new();
+    for (key, rects) in groups(current) {
+        let i = group(&mut grouped, key);
+        grouped[i].1.extend_from_slice(rects);
+    }
+    for (key, rects) in groups(removed) {
+        let i = group(&mut grouped, key);
+        if !remove_each(&mut grouped[i].1, rects) {
+            return Err(invalid);
+        }
+    }
+    for (key, rects) in groups(added) {
+        let i = group(&mut grouped, key);
+        grouped[i].1.extend_from_slice(rects);
+    }
+
+    current.clear();
+    for (key, rects) in grouped.iter().filter(|(_, rects)| !rects.is_empty()) {
+        current.extend_from_slice(key);
+        current.extend_from_slice(rects);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rects(range: std::ops::Range<u16>) -> Vec<PixelRect> {
+        range
+            .map(|i| PixelRect {
+                x: i,
+                y: i * 2,
+                w: 3,
+                h: 4,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn delta_rebuilds_the_frame() {
+        let previous = rects(0..20);
+        let current = [&previous[3..], &rects(40..42)].concat();
+
+        let mut encoded = Vec::new();
+        assert!(encode_delta(&previous, &current, &mut encoded));
+        assert!(is_delta_frame(&encoded));
+        let mut decoded = previous.clone();
+        apply_delta(&mut decoded, &encoded, 1).unwrap();
+        assert_eq!(decoded, current);
+    }
+
+    #[test]
+    fn delta_keeps_rects_in_their_groups() {
+        let level = |l: u16| vec![PixelRect::control(10 + l)];
+        let previous = [
+            rects(0..10),
+            level(1),
+            rects(10..20),
+            level(2),
+            rects(20..30),
+        ]
+        .concat();
+        let current = [
+            rects(1..10),
+            level(1),
+            rects(10..20),
+            rects(50..52),
+            level(2),
+            rects(20..29),
+            level(3),
+            rects(60..61),
+        ]
+        .concat();
+
+        let mut encoded = Vec::new();
+        assert!(encode_delta(&previous, &current, &mut encoded));
+        let mut decoded = previous.clone();
+        apply_delta(&mut decoded, &encoded, 1).unwrap();
+        assert_eq!(decoded, current);
+    }
+
+    #[test]
+    fn delta_counts_repeated_rects() {
+        let level = |l: u16| vec![PixelRect::control(10 + l)];
+        let a = rects(0..1);
+        let b = rects(1..2);
+        let previous = [
+            rects(2..12),
+            level(1),
+            a.clone(),
+            a.clone(),
+            b.clone(),
+            level(2),
+            rects(20..30),
+            b.clone(),
+        ]
+        .concat();
+        // One copy of `a` goes, `b` gains a copy in level 1 and loses its
+        // one in level 2, whose other rects stay
+        let current = [
+            rects(2..12),
+            level(1),
+            a.clone(),
+            b.clone(),
+            b.clone(),
+            level(2),
+            rects(20..30),
+        ]
+        .concat();
+
+        let mut encoded = Vec::new();
+        assert!(encode_delta(&previous, &current, &mut encoded));
+        assert_eq!(
+            encoded,
+            [
+                vec![DELTA_REMOVE],
+                level(1),
+                a.clone(),
+                level(2),
+                b.clone(),
+                vec![DELTA_ADD],
+                level(1),
+                b.clone(),
+            ]
+            .concat()
+        );
+        let mut decoded = previous.clone();
+        apply_delta(&mut decoded, &encoded, 1).unwrap();
+        assert_eq!(decoded, current);
+
+        // Removing more copies than there are is an error
+        let twice = [vec![DELTA_REMOVE], a.clone(), a.clone(), vec![DELTA_ADD]].concat();
+        let mut plain = [rects(5..8), a.clone()].concat();
+        assert!(apply_delta(&mut plain, &twice, 1).is_err());
+    }
+
+    #[test]
+    fn groups_keep_their_order() {
+        let level = |l: u16| vec![PixelRect::control(10 + l)];
+        let previous = [level(2), rects(0..10), level(1), rects(10..20)].concat();
+
+        // New groups go after the existing ones, in the order given
+        let current = [
+            level(2),
+            rects(0..10),
+            level(1),
+            rects(10..20),
+            level(4),
+            rects(30..31),
+            level(3),
+            rects(31..32),
+        ]
+        .concat();
+        let mut encoded = Vec::new();
+        assert!(encode_delta(&previous, &current, &mut encoded));
+        let mut decoded = previous.clone();
+        apply_delta(&mut decoded, &encoded, 1).unwrap();
+        assert_eq!(decoded, current);
+
+        // Swapping groups, or adding one in front, needs a keyframe
+        let swapped = [level(1), rects(10..20), level(2), rects(0..10)].concat();
+        let in_front = [rects(40..41), previous.clone()].concat();
+        for current in [swapped, in_front] {
+            assert!(!encode_delta(&previous, &current, &mut encoded));
+            assert!(encoded.is_empty());
+        }
+    }
+
+    #[test]
+    fn unrelated_frames_stay_keyframes() {
+        let mut encoded = vec![DELTA_ADD];
+        assert!(!encode_delta(&rects(0..10), &rects(20..30), &mut encoded));
+        assert!(encoded.is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_deltas() {
+        let previous = rects(0..10);
+        let malformed = [
+            // Not a delta frame
+            rects(0..2),
+            // Missing the added list
+            vec![DELTA_REMOVE, previous[0]],
+            // Removes a rect the previous frame doesn't have
+            vec![DELTA_REMOVE, rects(30..31)[0], DELTA_ADD],
+            // Removes from a group the previous frame doesn't have
+            vec![DELTA_REMOVE, PixelRect::control(11), previous[0], DELTA_ADD],
+        ];
+        for records in malformed {
+            let mut current = previous.clone();
+            assert_eq!(
+                apply_delta(&mut current, &records, 7),
+                Err(FormatError::InvalidDelta { frame: 7 }),
+                "{records:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use crate::PixelRect;
+use std::fmt;
+
+/// Errors raised while parsing Pixel Shell binary formats.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatError {
+    /// The input ended before a complete structure could be read.
+    Truncated { needed: usize, available: usize },
+    /// The data does not start with a known signature.
+    BadMagic,
+    /// The file was written by a newer version of the tools.
+    UnsupportedVersion(u16),
+    /// The header sets flag bits this version doesn't understand.
+    UnsupportedFlags(u16),
+    /// A header field holds a value that cannot be valid.
+    InvalidField(&'static str),
+    /// The stream ended in the middle of a frame.
+    TruncatedFrame { frame: usize },
+    /// A rect extends past the declared frame size.
+    RectOutOfBounds { frame: usize, rect: PixelRect },
+    /// A delta frame is malformed or doesn't match the previous frame.
+    InvalidDelta { frame: usize },
+    /// A compact rect record holds a value that doesn't fit.
+    BadVarint { frame: usize },
+    /// A compressed block could not be inflated.
+    CorruptBlock { block: usize },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated { needed, available } => write!(
+                f,
+                "data truncated: needed {} bytes, only {} available",
+                needed, available
+            ),
+            Self::BadMagic => write!(f, "missing Pixel Shell signature"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported format version {}", v),
+            Self::UnsupportedFlags(flags) => write!(f, "unsupported header flags {:#06x}", flags),
+            Self::InvalidField(name) => write!(f, "invalid header field '{}'", name),
+            Self::TruncatedFrame { frame } => write!(f, "frame {} is truncated", frame),
+            Self::RectOutOfBounds { frame, rect } => {
+                let PixelRect { x, y, w, h } = *rect;
+                write!(
+                    f,
+                    "frame {}: rect {}x{} at ({}, {}) is outside the frame",
+                    frame, w, h, x, y
+                )
+            }
+            Self::InvalidDelta { frame } => write!(f, "frame {}: invalid delta", frame),
+            Self::BadVarint { frame } => write!(f, "frame {}: malformed compact rect", frame),
+            Self::CorruptBlock { block } => write!(f, "compressed block {} is corrupt", block),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
@@ -0,0 +1,371 @@
+//! Header at the start of every `.bin` video file.
+//!
+//! Layout (all fields little-endian):
+//!
+//! ```text
+//! 0   magic        [u8; 4]  "PSHL"
+//! 4   version      u16
+//! 6   header_len   u16      offset of the rect stream
+//! 8   flags        u16
+//! 10  width        u16
+//! 12  height       u16
+//! 14  fps          u16      rounded, see `fps_num` / `fps_den`
+//! 16  frame_count  u32
+//! 20  levels       u16      intensity levels, see `level`
+//! 22  reserved     u16
+//! 24  data_len     u64      length of the rect stream in bytes
+//! 32  fps_num      u32      exact frame rate, see `timing`
+//! 36  fps_den      u32
+//! ```
+//!
+//! Fields past offset 32 were added later and are only read when
+//! `header_len` covers them; older files play at `fps / 1`.
+//!
+//! Files written before the header existed start with a bare `u16` FPS
+//! followed by the rect stream. [`FileHeader::parse`] still accepts them and
+//! reports them with `version == 0`.
+
+use crate::{level::MAX_LEVELS, timing::FrameRate, FormatError};
+use std::ops::Range;
+
+pub const MAGIC: [u8; 4] = *b"PSHL";
+pub const VERSION: u16 = 1;
+pub const HEADER_SIZE: usize = 40;
+
+/// Size of the header before the frame rate fields were added.
+const BASE_HEADER_SIZE: usize = 32;
+
+/// Start of the rect stream in legacy (FPS-only) files.
+pub const LEGACY_DATA_START: usize = 2;
+
+/// Set once the writer has patched the frame count and stream length.
+/// Missing on files left behind by an interrupted conversion.
+pub const FLAG_FINALIZED: u16 = 1 << 0;
+
+/// Frames after the first may be stored as deltas, see [`crate::delta`].
+pub const FLAG_DELTA: u16 = 1 << 1;
+
+/// The rect stream uses the compact encoding from [`crate::varint`].
+pub const FLAG_VARINT: u16 = 1 << 2;
+
+/// Rects carry intensity levels, see [`crate::level`].
+pub const FLAG_LEVELS: u16 = 1 << 3;
+
+/// Frames select colors from a palette chunk, see [`crate::palette`].
+pub const FLAG_PALETTE: u16 = 1 << 4;
+
+/// Frames carry their own presentation times, see [`crate::timing`].
+pub const FLAG_TIMESTAMPS: u16 = 1 << 5;
+
+const KNOWN_FLAGS: u16 =
+    FLAG_FINALIZED | FLAG_DELTA | FLAG_VARINT | FLAG_LEVELS | FLAG_PALETTE | FLAG_TIMESTAMPS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileHeader {
+    /// Format version, `0` for legacy FPS-only files.
+    pub version: u16,
+    pub flags: u16,
+    /// Frame size in pixels, `0` when unknown (legacy files).
+    pub width: u16,
+    pub height: u16,
+    /// `frame_rate` rounded, for tools that only need an approximation.
+    pub fps: u16,
+    pub frame_rate: FrameRate,
+    pub frame_count: u32,
+    /// Number of intensity levels, `0` for 1-bit files.
+    pub levels: u16,
+    /// Offset of the rect stream from the start of the file.
+    pub data_start: usize,
+    /// Length of the rect stream in bytes.
+    pub data_len: u64,
+}
+
+impl FileHeader {
+    pub fn new(width: u16, height: u16, fps: u16) -> Self {
+        Self {
+            version: VERSION,
+            flags: 0,
+            width,
+            height,
+            fps,
+            frame_rate: FrameRate::from_fps(fps),
+            frame_count: 0,
+            levels: 0,
+            data_start: HEADER_SIZE,
+            data_len: 0,
+        }
+    }
+
+    #[inline]
+    pub fn is_legacy(&self) -> bool {
+        self.version == 0
+    }
+
+    #[inline]
+    pub fn is_finalized(&self) -> bool {
+        self.flags & FLAG_FINALIZED != 0
+    }
+
+    /// Byte range of the rect stream within the file.
+    pub fn data_range(&self) -> Range<usize> {
+        self.data_start..self.data_start + self.data_len as usize
+    }
+
+    /// Parses the header from the start of a `.bin` file.
+    ///
+    /// `bytes` must hold the whole file so the stream length of legacy and
+    /// unfinalized files can be derived from it.
+    pub fn parse(bytes: &[u8]) -> Result<Self, FormatError> {
+        Self::parse_prefix(bytes, bytes.len())
+    }
+
+    /// Parses the header from the first bytes of a `.bin` file that is
+    /// `file_len` bytes long in total.
+    pub fn parse_prefix(bytes: &[u8], file_len: usize) -> Result<Self, FormatError> {
+        if bytes.len() < MAGIC.len() || bytes[0..4] != MAGIC {
+            return Self::parse_legacy(bytes, file_len);
+        }
+        if bytes.len() < BASE_HEADER_SIZE {
+            return Err(FormatError::Truncated {
+                needed: BASE_HEADER_SIZE,
+                available: bytes.len(),
+            });
+        }
+
+        let u16_at = |o: usize| u16::from_le_bytes([bytes[o], bytes[o + 1]]);
+        let version = u16_at(4);
+        if version == 0 || version > VERSION {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+
+        let data_start = u16_at(6) as usize;
+        if data_start < BASE_HEADER_SIZE || data_start > file_len {
+            return Err(FormatError::InvalidField("header_len"));
+        }
+        if bytes.len() < data_start.min(HEADER_SIZE) {
+            return Err(FormatError::Truncated {
+                needed: data_start.min(HEADER_SIZE),
+                available: bytes.len(),
+            });
+        }
+
+        let flags = u16_at(8);
+        if flags & !KNOWN_FLAGS != 0 {
+            return Err(FormatError::UnsupportedFlags(flags));
+        }
+
+        let u32_at = |o: usize| u32::from_le_bytes(bytes[o..o + 4].try_into().unwrap());
+        let fps = u16_at(14);
+        let frame_rate = if data_start >= HEADER_SIZE {
+            FrameRate::new(u32_at(32), u32_at(36))
+        } else {
+            FrameRate::from_fps(fps)
+        };
+
+        let mut header = Self {
+            version,
+            flags,
+            width: u16_at(10),
+            height: u16_at(12),
+            fps,
+            frame_rate,
+            frame_count: u32_at(16),
+            levels: u16_at(20),
+            data_start,
+            data_len: u64::from_le_bytes(bytes[24..32].try_into().unwrap()),
+        };
+
+        let available = (file_len - data_start) as u64;
+        if !header.is_finalized() {
+            // Interrupted write: the stream runs to the end of the file.
+            header.data_len = available;
+        } else if header.data_len > available {
+            return Err(FormatError::Truncated {
+                needed: data_start + header.data_len as usize,
+                available: file_len,
+            });
+        }
+        if header.fps == 0 {
+            return Err(FormatError::InvalidField("fps"));
+        }
+        if header.frame_rate.num == 0 || header.frame_rate.den == 0 {
+            return Err(FormatError::InvalidField("frame_rate"));
+        }
+        if header.flags & FLAG_LEVELS != 0 && !(2..=MAX_LEVELS).contains(&header.levels) {
+            return Err(FormatError::InvalidField("levels"));
+        }
+        Ok(header)
+    }
+
+    fn parse_legacy(bytes: &[u8], file_len: usize) -> Result<Self, FormatError> {
+        if bytes.len() < LEGACY_DATA_START {
+            return Err(FormatError::Truncated {
+                needed: LEGACY_DATA_START,
+                available: bytes.len(),
+            });
+        }
+        let fps = u16::from_le_bytes([bytes[0], bytes[1]]);
+        if fps == 0 {
+            return Err(FormatError::BadMagic);
+        }
+        Ok(Self {
+            version: 0,
+            flags: FLAG_FINALIZED,
+            width: 0,
+            height: 0,
+            fps,
+            frame_rate: FrameRate::from_fps(fps),
+            frame_count: 0,
+            levels: 0,
+            data_start: LEGACY_DATA_START,
+            data_len: (file_len - LEGACY_DATA_START) as u64,
+        })
+    }
+
+    /// Serializes the header in the current format version.
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut out = [0u8; HEADER_SIZE];
+        out[0..4].copy_from_slice(&MAGIC);
+        out[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        out[6..8].copy_from_slice(&(HEADER_SIZE as u16).to_le_bytes());
+        out[8..10].copy_from_slice(&self.flags.to_le_bytes());
+        out[10..12].copy_from_slice(&self.width.to_le_bytes());
+        out[12..14].copy_from_slice(&self.height.to_le_bytes());
+        out[14..16].copy_from_slice(&self.fps.to_le_bytes());
+        out[16..20].copy_from_slice(&self.frame_count.to_le_bytes());
+        out[20..22].copy_from_slice(&self.levels.to_le_bytes());
+        out[24..32].copy_from_slice(&self.data_len.to_le_bytes());
+        out[32..36].copy_from_slice(&self.frame_rate.num.to_le_bytes());
+        out[36..40].copy_from_slice(&self.frame_rate.den.to_le_bytes());
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BinWriter, PixelRect};
+    use std::io::Cursor;
+
+    const RECT: PixelRect = PixelRect {
+        x: 1,
+        y: 2,
+        w: 3,
+        h: 4,
+    };
+
+    #[test]
+    fn parses_written_header() {
+        let mut writer = BinWriter::new(Cursor::new(Vec::new()), 640, 360, 24).unwrap();
+        writer.write_frame(&[RECT]).unwrap();
+        writer.write_frame(&[]).unwrap();
+        let bytes = writer.finish().unwrap().0.into_inner();
+
+        let header = FileHeader::parse(&bytes).unwrap();
+        assert_eq!(header.version, VERSION);
+        assert!(header.is_finalized() && !header.is_legacy());
+        assert_eq!((header.width, header.height, header.fps), (640, 360, 24));
+        assert_eq!(header.frame_count, 2);
+        assert_eq!(header.data_range(), HEADER_SIZE..bytes.len());
+    }
+
+    #[test]
+    fn parses_legacy_header() {
+        let mut bytes = 30u16.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&RECT.to_le_bytes());
+        bytes.extend_from_slice(&PixelRect::EOS_MARKER.to_le_bytes());
+
+        let header = FileHeader::parse(&bytes).unwrap();
+        assert!(header.is_legacy() && header.is_finalized());
+        assert_eq!((header.width, header.height, header.fps), (0, 0, 30));
+        assert_eq!(header.data_range(), LEGACY_DATA_START..bytes.len());
+    }
+
+    #[test]
+    fn reads_the_exact_frame_rate() {
+        let ntsc = FrameRate::new(30000, 1001);
+        let writer = BinWriter::new(Cursor::new(Vec::new()), 64, 64, 30)
+            .unwrap()
+            .with_frame_rate(ntsc)
+            .unwrap();
+        let bytes = writer.finish().unwrap().0.into_inner();
+
+        let header = FileHeader::parse(&bytes).unwrap();
+        assert_eq!(header.frame_rate, ntsc);
+        assert_eq!(header.fps, 30);
+
+        for zero in [FrameRate::new(0, 1), FrameRate::new(30, 0)] {
+            let writer = BinWriter::new(Cursor::new(Vec::new()), 64, 64, 30).unwrap();
+            let err = writer.with_frame_rate(zero).err().unwrap();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn short_v1_header_falls_back_to_fps() {
+        // Written before the frame rate fields existed
+        let mut bytes = FileHeader::new(64, 64, 25).to_bytes()[..BASE_HEADER_SIZE].to_vec();
+        bytes[6..8].copy_from_slice(&(BASE_HEADER_SIZE as u16).to_le_bytes());
+        bytes.extend_from_slice(&RECT.to_le_bytes());
+
+        let header = FileHeader::parse(&bytes).unwrap();
+        assert_eq!(header.version, 1);
+        assert_eq!(header.frame_rate, FrameRate::from_fps(25));
+        assert_eq!(header.data_range(), BASE_HEADER_SIZE..bytes.len());
+    }
+
+    #[test]
+    fn unfinalized_stream_runs_to_the_end() {
+        let mut bytes = FileHeader::new(64, 64, 30).to_bytes().to_vec();
+        bytes.extend_from_slice(&RECT.to_le_bytes());
+
+        let header = FileHeader::parse(&bytes).unwrap();
+        assert!(!header.is_finalized());
+        assert_eq!(header.data_len, PixelRect::SIZE as u64);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let good = FileHeader::new(64, 64, 30).to_bytes();
+        assert_eq!(
+            FileHeader::parse(&good[..20]),
+            Err(FormatError::Truncated {
+                needed: BASE_HEADER_SIZE,
+                available: 20
+            })
+        );
+        assert_eq!(FileHeader::parse(&[0, 0, 1]), Err(FormatError::BadMagic));
+
+        let mut bad = good;
+        bad[4] = 9;
+        assert_eq!(
+            FileHeader::parse(&bad),
+            Err(FormatError::UnsupportedVersion(9))
+        );
+
+        let mut bad = good;
+        bad[36..40].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(
+            FileHeader::parse(&bad),
+            Err(FormatError::InvalidField("frame_rate"))
+        );
+
+        let mut bad = good;
+        bad[14] = 0;
+        assert_eq!(
+            FileHeader::parse(&bad),
+            Err(FormatError::InvalidField("fps"))
+        );
+
+        // Finalized, but claims more stream than the file holds
+        let mut header = FileHeader::new(64, 64, 30);
+        header.flags = FLAG_FINALIZED;
+        header.data_len = 16;
+        let mut bytes = header.to_bytes().to_vec();
+        bytes.extend_from_slice(&RECT.to_le_bytes());
+        assert!(matches!(
+            FileHeader::parse(&bytes),
+            Err(FormatError::Truncated { .. })
+        ));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{FileHeader, FormatError, FrameIndex, PixelRect};
+
+/// Decodes one frame from the start of `bytes` into `out`, without the
+/// end-of-frame marker. Returns the number of bytes consumed.
+pub(crate) fn decode_frame(
+    bytes: &[u8],
+    out: &mut Vec<PixelRect>,
+    frame: usize,
+) -> Result<usize, FormatError> {
+    out.clear();
+    for (i, chunk) in bytes.chunks_exact(PixelRect::SIZE).enumerate() {
+        let rect = PixelRect::from_le_bytes(chunk.try_into().unwrap());
+        if rect.is_frame_end() {
+            return Ok((i + 1) * PixelRect::SIZE);
+        }
+        out.push(rect);
+    }
+    Err(FormatError::TruncatedFrame { frame })
+}
+
+/// Checks every rect against the frame size; `0` disables the check.
+pub(crate) fn check_bounds(
+    rects: &[PixelRect],
+    width: u16,
+    height: u16,
+    frame: usize,
+) -> Result<(), FormatError> {
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+    let (fw, fh) = (width as u32, height as u32);
+    let bad = rects
+        .iter()
+        .filter(|r| !r.is_control())
+        .find(|r| r.x as u32 + r.w as u32 > fw || r.y as u32 + r.h as u32 > fh);
+    match bad {
+        Some(&rect) => Err(FormatError::RectOutOfBounds { frame, rect }),
+        None => Ok(()),
+    }
+}
+
+/// Reads the frames of a rect stream, yielding each frame's rects (without
+/// the end-of-frame marker).
+///
+/// Every rect (but not control records) is checked against the declared
+/// frame size; a width or height of `0` (legacy files) disables the check.
+/// After the first error the reader is exhausted.
+pub struct FrameReader<'a> {
+    stream: &'a [u8],
+    pos: usize,
+    frame: usize,
+    width: u16,
+    height: u16,
+    done: bool,
+    rects: Vec<PixelRect>,
+}
+
+impl<'a> FrameReader<'a> {
+    pub fn new(stream: &'a [u8], width: u16, height: u16) -> Self {
+        Self {
+            stream,
+            pos: 0,
+            frame: 0,
+            width,
+            height,
+            done: false,
+            rects: Vec::new(),
+        }
+    }
+
+    /// Reader over the rect stream of a whole `.bin` file.
+    pub fn for_file(bytes: &'a [u8], header: &FileHeader) -> Self {
+        Self::new(&bytes[header.data_range()], header.width, header.height)
+    }
+
+    /// Index of the frame the next call to `next_frame` returns.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Jumps to `frame` using the index. Returns `false` if the frame is out
+    /// of range, leaving the reader where it was.
+    pub fn seek(&mut self, index: &FrameIndex, frame: usize) -> bool {
+        match index.frame_range(frame) {
+            Some(range)
+                if range.start.is_multiple_of(PixelRect::SIZE)
+                    && range.start <= self.stream.len() =>
+            {
+                self.pos = range.start;
+                self.frame = frame;
+                self.done = false;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Decodes the next frame. After the first error the reader is
+    /// exhausted.
+    pub fn next_frame(&mut self) -> Option<Result<&[PixelRect], FormatError>> {
+        if self.done || self.pos >= self.stream.len() {
+            self.done = true;
+            return None;
+        }
+        let decoded =
+            decode_frame(&self.stream[self.pos..], &mut self.rects, self.frame).and_then(|used| {
+                check_bounds(&self.rects, self.width, self.height, self.frame)?;
+                Ok(used)
+            });
+        match decoded {
+            Ok(used) => {
+                self.pos += used;
+                self.frame += 1;
+                Some(Ok(&self.rects))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: u16, y: u16, w: u16, h: u16) -> PixelRect {
+        PixelRect { x, y, w, h }
+    }
+
+    fn stream(frames: &[&[PixelRect]]) -> Vec<u8> {
+        frames
+            .iter()
+            .flat_map(|f| f.iter().chain([&PixelRect::EOS_MARKER]))
+            .flat_map(PixelRect::to_le_bytes)
+            .collect()
+    }
+
+    #[test]
+    fn yields_each_frame() {
+        let frames: [&[PixelRect]; 3] = [
+            &[rect(0, 0, 4, 4), rect(4, 4, 4, 4)],
+            &[],
+            &[rect(7, 7, 1, 1)],
+        ];
+        let bytes = stream(&frames);
+        // The stream needn't be aligned for `PixelRect`
+        let mut shifted = vec![0];
+        shifted.extend_from_slice(&bytes);
+        for stream in [&bytes[..], &shifted[1..]] {
+            let mut reader = FrameReader::new(stream, 8, 8);
+            for frame in frames {
+                assert_eq!(reader.next_frame(), Some(Ok(frame)));
+            }
+            assert_eq!(reader.next_frame(), None);
+        }
+    }
+
+    #[test]
+    fn seeks_with_the_index() {
+        let frames: [&[PixelRect]; 3] = [&[rect(0, 0, 1, 1)], &[rect(1, 1, 1, 1)], &[]];
+        let bytes = stream(&frames);
+        let index = FrameIndex::scan(&bytes);
+        let mut reader = FrameReader::new(&bytes, 8, 8);
+        assert!(reader.seek(&index, 1));
+        assert_eq!(reader.frame(), 1);
+        assert_eq!(reader.next_frame(), Some(Ok(frames[1])));
+        assert!(!reader.seek(&index, 3));
+        assert_eq!(reader.frame(), 2);
+    }
+
+    #[test]
+    fn reports_truncated_frames() {
+        let mut bytes = stream(&[&[rect(0, 0, 1, 1)], &[rect(1, 1, 1, 1)]]);
+        bytes.truncate(bytes.len() - 3);
+        let mut reader = FrameReader::new(&bytes, 8, 8);
+        assert!(reader.next_frame().unwrap().is_ok());
+        assert_eq!(
+            reader.next_frame(),
+            Some(Err(FormatError::TruncatedFrame { frame: 1 }))
+        );
+        assert_eq!(reader.next_frame(), None);
+    }
+
+    #[test]
+    fn checks_rects_against_the_frame_size() {
+        let bad = rect(6, 0, 4, 1);
+        let bytes = stream(&[&[rect(0, 0, 8, 8)], &[bad]]);
+        let mut reader = FrameReader::new(&bytes, 8, 8);
+        assert!(reader.next_frame().unwrap().is_ok());
+        assert_eq!(
+            reader.next_frame(),
+            Some(Err(FormatError::RectOutOfBounds {
+                frame: 1,
+                rect: bad
+            }))
+        );
+        assert_eq!(reader.next_frame(), None);
+
+        // Legacy files don't know their size
+        let mut reader = FrameReader::new(&bytes, 0, 0);
+        assert!(reader.next_frame().unwrap().is_ok());
+        assert_eq!(reader.next_frame(), Some(Ok(&[bad][..])));
+    }
+}
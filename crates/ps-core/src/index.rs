@@ -0,0 +1,163 @@
+use crate::{chunk, file_header::FLAG_VARINT, varint, FileHeader, FormatError, PixelRect};
+use std::ops::Range;
+
+/// Byte offset of every frame within the rect stream.
+///
+/// Stored in the `FIDX` chunk by the converter so players can jump straight
+/// to frame N instead of counting end-of-frame markers. Offsets are relative
+/// to the start of the rect stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameIndex {
+    offsets: Vec<u64>,
+    stream_len: u64,
+}
+
+impl FrameIndex {
+    pub fn new(offsets: Vec<u64>, stream_len: u64) -> Self {
+        Self {
+            offsets,
+            stream_len,
+        }
+    }
+
+    /// Loads the index chunk of a `.bin` file, if it has one.
+    pub fn load(bytes: &[u8], header: &FileHeader) -> Result<Option<Self>, FormatError> {
+        let Some(data) = chunk::find_chunk(bytes, header, chunk::TAG_FRAME_INDEX)? else {
+            return Ok(None);
+        };
+        if !data.len().is_multiple_of(8) {
+            return Err(FormatError::InvalidField("frame index length"));
+        }
+
+        let offsets: Vec<u64> = data
+            .chunks_exact(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+
+        let in_order = offsets.windows(2).all(|w| w[0] <= w[1]);
+        let in_bounds = offsets.last().is_none_or(|&o| o <= header.data_len);
+        if !in_order || !in_bounds {
+            return Err(FormatError::InvalidField("frame index offsets"));
+        }
+        Ok(Some(Self::new(offsets, header.data_len)))
+    }
+
+    /// Rebuilds the index by scanning the stream for end-of-frame markers.
+    /// Used for files written before the index chunk existed.
+    pub fn scan(stream: &[u8]) -> Self {
+        let mut offsets = Vec::new();
+        let mut frame_start = true;
+        for (i, chunk) in stream.chunks_exact(PixelRect::SIZE).enumerate() {
+            if frame_start {
+                offsets.push((i * PixelRect::SIZE) as u64);
+            }
+            frame_start = PixelRect::from_le_bytes(chunk.try_into().unwrap()).is_frame_end();
+        }
+        Self::new(offsets, stream.len() as u64)
+    }
+
+    /// Like [`FrameIndex::scan`], for streams in the compact encoding.
+    pub fn scan_varint(stream: &[u8]) -> Self {
+        Self::new(varint::scan_offsets(stream), stream.len() as u64)
+    }
+
+    /// Loads the stored index, falling back to a scan of the stream.
+    pub fn load_or_scan(bytes: &[u8], header: &FileHeader) -> Result<Self, FormatError> {
+        let stream = &bytes[header.data_range()];
+        match Self::load(bytes, header)? {
+            Some(index) => Ok(index),
+            None if header.flags & FLAG_VARINT != 0 => Ok(Self::scan_varint(stream)),
+            None => Ok(Self::scan(stream)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Byte range of `frame` within the rect stream, end-of-frame marker included.
+    pub fn frame_range(&self, frame: usize) -> Option<Range<usize>> {
+        let start = *self.offsets.get(frame)?;
+        let end = self
+            .offsets
+            .get(frame + 1)
+            .copied()
+            .unwrap_or(self.stream_len);
+        Some(start as usize..end as usize)
+    }
+
+    /// Serializes the offsets as the payload of an index chunk.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.offsets.iter().flat_map(|o| o.to_le_bytes()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BinWriter;
+    use std::io::Cursor;
+
+    fn write_bin(indexed: bool) -> Vec<u8> {
+        let mut writer = BinWriter::new(Cursor::new(Vec::new()), 32, 32, 30).unwrap();
+        if indexed {
+            writer = writer.with_frame_index();
+        }
+        for n in [2u16, 0, 5] {
+            let rects: Vec<PixelRect> = (0..n)
+                .map(|i| PixelRect {
+                    x: i,
+                    y: i,
+                    w: 1,
+                    h: 1,
+                })
+                .collect();
+            writer.write_frame(&rects).unwrap();
+        }
+        writer.finish().unwrap().0.into_inner()
+    }
+
+    #[test]
+    fn stored_index_matches_scan() {
+        let bytes = write_bin(true);
+        let header = FileHeader::parse(&bytes).unwrap();
+        let scanned = FrameIndex::scan(&bytes[header.data_range()]);
+        assert_eq!(FrameIndex::load(&bytes, &header), Ok(Some(scanned.clone())));
+
+        assert_eq!(scanned.len(), 3);
+        assert_eq!(scanned.frame_range(0), Some(0..24));
+        assert_eq!(scanned.frame_range(1), Some(24..32));
+        assert_eq!(scanned.frame_range(2), Some(32..80));
+        assert_eq!(scanned.frame_range(3), None);
+    }
+
+    #[test]
+    fn falls_back_to_scanning() {
+        let bytes = write_bin(false);
+        let header = FileHeader::parse(&bytes).unwrap();
+        assert_eq!(FrameIndex::load(&bytes, &header), Ok(None));
+        assert_eq!(
+            FrameIndex::load_or_scan(&bytes, &header),
+            Ok(FrameIndex::scan(&bytes[header.data_range()]))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_offsets() {
+        let mut bytes = write_bin(false);
+        let header = FileHeader::parse(&bytes).unwrap();
+        let index = FrameIndex::new(vec![24, 0, 32], header.data_len);
+        chunk::encode_chunk(&mut bytes, chunk::TAG_FRAME_INDEX, &index.to_bytes());
+        assert_eq!(
+            FrameIndex::load(&bytes, &header),
+            Err(FormatError::InvalidField("frame index offsets"))
+        );
+
+        bytes.pop();
+        assert!(FrameIndex::load(&bytes, &header).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+//! Extra layers composited over the main video.
+//!
+//! Each layer is a `LAYR` chunk holding a complete `.bin` file of its own,
+//! so it keeps its own frame rate, encoding and palette. Readers that don't
+//! know the chunk just play the main video. Layout, little-endian:
+//!
+//! ```text
+//! 0   name_len  u16
+//! 2   name      [u8; name_len]  UTF-8
+//!     opacity   u8              applied on top of the layer's own levels
+//!     flags     u8              LAYER_*
+//!     bin       [u8]            rest of the chunk
+//! ```
+//!
+//! Layers are drawn in file order, over the main video.
+
+use crate::{
+    chunk::{self, Chunks},
+    FileHeader, FormatError,
+};
+
+/// Restart the layer when it ends instead of holding its last frame.
+pub const LAYER_LOOP: u8 = 1 << 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layer<'a> {
+    pub name: &'a str,
+    pub opacity: u8,
+    pub flags: u8,
+    /// The layer's own `.bin` file.
+    pub bin: &'a [u8],
+}
+
+impl<'a> Layer<'a> {
+    #[inline]
+    pub fn is_looped(&self) -> bool {
+        self.flags & LAYER_LOOP != 0
+    }
+
+    pub fn parse(data: &'a [u8]) -> Result<Self, FormatError> {
+        let truncated = |needed| FormatError::Truncated {
+            needed,
+            available: data.len(),
+        };
+        if data.len() < 2 {
+            return Err(truncated(2));
+        }
+        let name_end = 2 + u16::from_le_bytes([data[0], data[1]]) as usize;
+        if data.len() < name_end + 2 {
+            return Err(truncated(name_end + 2));
+        }
+        let name = std::str::from_utf8(&data[2..name_end])
+            .map_err(|_| FormatError::InvalidField("layer name"))?;
+        Ok(Self {
+            name,
+            opacity: data[name_end],
+            flags: data[name_end + 1],
+            bin: &data[name_end + 2..],
+        })
+    }
+
+    /// Serializes the layer; `name` must be shorter than 64 KiB.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let name = self.name.as_bytes();
+        let mut out = Vec::with_capacity(4 + name.len() + self.bin.len());
+        out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        out.extend_from_slice(name);
+        out.push(self.opacity);
+        out.push(self.flags);
+        out.extend_from_slice(self.bin);
+        out
+    }
+}
+
+/// Every layer among `chunks`, in order.
+pub fn collect<'a>(chunks: Chunks<'a>) -> Result<Vec<Layer<'a>>, FormatError> {
+    let mut layers = Vec::new();
+    for chunk in chunks {
+        let chunk = chunk?;
+        if chunk.tag == chunk::TAG_LAYER {
+            layers.push(Layer::parse(chunk.data)?);
+        }
+    }
+    Ok(layers)
+}
+
+/// Layers of a `.bin` file.
+pub fn load<'a>(bytes: &'a [u8], header: &FileHeader) -> Result<Vec<Layer<'a>>, FormatError> {
+    collect(Chunks::new(bytes, header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress, BinWriter, BlockDecoder};
+    use std::io::Cursor;
+
+    fn empty_bin(fps: u16) -> Vec<u8> {
+        let writer = BinWriter::new(Cursor::new(Vec::new()), 16, 16, fps).unwrap();
+        writer.finish().unwrap().0.into_inner()
+    }
+
+    #[test]
+    fn layers_are_stored_in_order() {
+        let (rain, logo) = (empty_bin(12), empty_bin(60));
+        let layers = [
+            Layer {
+                name: "rain",
+                opacity: 128,
+                flags: LAYER_LOOP,
+                bin: &rain,
+            },
+            Layer {
+                name: "lögo",
+                opacity: 255,
+                flags: 0,
+                bin: &logo,
+            },
+        ];
+        let mut writer = BinWriter::new(Cursor::new(Vec::new()), 16, 16, 30).unwrap();
+        for layer in &layers {
+            writer.add_layer(layer).unwrap();
+        }
+        let bytes = writer.finish().unwrap().0.into_inner();
+        let header = FileHeader::parse(&bytes).unwrap();
+
+        let loaded = load(&bytes, &header).unwrap();
+        assert_eq!(loaded, layers);
+        assert!(loaded[0].is_looped() && !loaded[1].is_looped());
+        assert_eq!(FileHeader::parse(loaded[1].bin).unwrap().fps, 60);
+
+        // Compressed files keep them in the trailer
+        let packed = compress::compress(&bytes).unwrap();
+        let trailer = BlockDecoder::new(&packed).unwrap().trailer().unwrap();
+        assert_eq!(collect(Chunks::trailing(&trailer)).unwrap(), layers);
+    }
+
+    #[test]
+    fn rejects_malformed_layers() {
+        let bytes = Layer {
+            name: "fx",
+            opacity: 1,
+            flags: 0,
+            bin: &[],
+        }
+        .to_bytes();
+        for len in 0..bytes.len() {
+            assert!(
+                matches!(
+                    Layer::parse(&bytes[..len]),
+                    Err(FormatError::Truncated { .. })
+                ),
+                "{len} bytes"
+            );
+        }
+
+        let mut bad = bytes.clone();
+        bad[2] = 0xFF;
+        assert_eq!(
+            Layer::parse(&bad),
+            Err(FormatError::InvalidField("layer name"))
+        );
+    }
+}
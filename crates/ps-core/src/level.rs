@@ -0,0 +1,73 @@
+//! Quantised intensity levels for anti-aliased output.
+//!
+//! Files with [`FLAG_LEVELS`](crate::file_header::FLAG_LEVELS) set record
+//! the number of levels in the header. Within a frame, a level marker sets
+//! the intensity of the rects that follow it; rects before any marker are
+//! drawn at full intensity. Level `0` is transparent and never stored.
+
+use crate::PixelRect;
+
+/// Opcode of level markers; the low byte holds the level.
+pub const LEVEL_OPCODE: u16 = 0x100;
+
+/// Largest supported level count.
+pub const MAX_LEVELS: u16 = 256;
+
+pub const fn level_marker(level: u8) -> PixelRect {
+    PixelRect::control(LEVEL_OPCODE | level as u16)
+}
+
+/// Level set by `rect`, if it is a level marker.
+#[inline]
+pub fn level_of(rect: &PixelRect) -> Option<u8> {
+    (rect.is_control() && rect.h & 0xFF00 == LEVEL_OPCODE).then_some(rect.h as u8)
+}
+
+/// Highest level of a file with `levels` levels (`0` means 1-bit).
+#[inline]
+pub fn full_level(levels: u16) -> u8 {
+    (levels.clamp(2, MAX_LEVELS) - 1) as u8
+}
+
+/// Opacity (0-255) of `level` out of `levels`.
+#[inline]
+pub fn level_alpha(level: u8, levels: u16) -> u8 {
+    let full = full_level(levels) as u32;
+    ((level as u32).min(full) * 255 / full) as u8
+}
+
+/// Maps a grey value to the nearest of `levels` levels.
+#[inline]
+pub fn quantize(value: u8, levels: u16) -> u8 {
+    let full = full_level(levels) as u32;
+    ((value as u32 * full + 127) / 255) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markers_carry_their_level() {
+        for level in [0, 1, 128, 255] {
+            assert_eq!(level_of(&level_marker(level)), Some(level));
+        }
+        assert_eq!(level_of(&PixelRect::control(2)), None);
+        assert_eq!(level_of(&PixelRect::EOS_MARKER), None);
+    }
+
+    #[test]
+    fn levels_span_the_full_range() {
+        for levels in [0, 2, 4, 16, 255, MAX_LEVELS] {
+            let full = full_level(levels);
+            assert_eq!(quantize(0, levels), 0);
+            assert_eq!(quantize(255, levels), full);
+            assert_eq!(level_alpha(0, levels), 0);
+            assert_eq!(level_alpha(full, levels), 255);
+        }
+        assert_eq!(full_level(0), 1);
+        assert_eq!(full_level(MAX_LEVELS), 255);
+        assert_eq!(quantize(100, 4), 1);
+        assert_eq!(level_alpha(1, 4), 85);
+    }
+}
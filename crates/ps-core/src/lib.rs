@@ -1,5 +1,39 @@
-#[repr(packed)]
-#[derive(Debug, Copy, Clone, PartialEq)]
+pub mod brush;
+pub mod chunk;
+pub mod compress;
+pub mod decoder;
+pub mod delta;
+pub mod error;
+pub mod file_header;
+pub mod frame;
+pub mod index;
+pub mod layer;
+pub mod level;
+pub mod metadata;
+pub mod palette;
+pub mod payload;
+pub mod resolution;
+pub mod timing;
+pub mod varint;
+pub mod writer;
+
+pub use brush::Brush;
+pub use compress::BlockDecoder;
+pub use decoder::FrameDecoder;
+pub use error::FormatError;
+pub use file_header::FileHeader;
+pub use frame::FrameReader;
+pub use index::FrameIndex;
+pub use layer::Layer;
+pub use metadata::Metadata;
+pub use palette::Palette;
+pub use payload::PayloadFooter;
+pub use resolution::Resolution;
+pub use timing::{FrameRate, Timing};
+pub use writer::BinWriter;
+
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct PixelRect {
     pub x: u16,
     pub y: u16,
@@ -8,6 +42,9 @@ pub struct PixelRect {
 }
 
 impl PixelRect {
+    /// Size of one encoded rect in the `.bin` stream.
+    pub const SIZE: usize = 8;
+
     pub const EOS_MARKER: Self = Self {
         x: 0,
         y: 0,
@@ -19,13 +56,41 @@ impl PixelRect {
     pub fn is_frame_end(&self) -> bool {
         self.w == 0 && self.h == 0
     }
-}
 
-pub mod file_header {
-    use std::mem;
+    /// Builds a control record. Control records have zero width and a
+    /// non-zero opcode in `h`, so they never describe pixels.
+    pub const fn control(opcode: u16) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            w: 0,
+            h: opcode,
+        }
+    }
+
+    #[inline]
+    pub fn is_control(&self) -> bool {
+        self.w == 0 && self.h != 0
+    }
 
-    pub const FPS_OFFSET: usize = 0;
-    pub const FPS_SIZE: usize = mem::size_of::<u16>();
+    #[inline]
+    pub fn to_le_bytes(&self) -> [u8; Self::SIZE] {
+        let (x, y, w, h) = (self.x, self.y, self.w, self.h);
+        let mut out = [0u8; Self::SIZE];
+        out[0..2].copy_from_slice(&x.to_le_bytes());
+        out[2..4].copy_from_slice(&y.to_le_bytes());
+        out[4..6].copy_from_slice(&w.to_le_bytes());
+        out[6..8].copy_from_slice(&h.to_le_bytes());
+        out
+    }
 
-    pub const DATA_START: usize = FPS_OFFSET + FPS_SIZE;
+    #[inline]
+    pub fn from_le_bytes(bytes: [u8; Self::SIZE]) -> Self {
+        Self {
+            x: u16::from_le_bytes([bytes[0], bytes[1]]),
+            y: u16::from_le_bytes([bytes[2], bytes[3]]),
+            w: u16::from_le_bytes([bytes[4], bytes[5]]),
+            h: u16::from_le_bytes([bytes[6], bytes[7]]),
+        }
+    }
 }
@@ -0,0 +1,208 @@
+//! Free-form key/value metadata: where a video came from and how it was
+//! made.
+//!
+//! Stored in a `META` chunk of `.bin` files and as a payload chunk of built
+//! executables. Layout, little-endian, repeated until the end of the chunk:
+//!
+//! ```text
+//! key_len    u16
+//! key        [u8; key_len]    UTF-8
+//! value_len  u32
+//! value      [u8; value_len]  UTF-8
+//! ```
+
+use crate::{chunk, FileHeader, FormatError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const KEY_TITLE: &str = "title";
+pub const KEY_SOURCE_URL: &str = "source_url";
+pub const KEY_TOOL_VERSION: &str = "tool_version";
+/// When the `.bin` was converted, see [`utc_timestamp`].
+pub const KEY_CREATED: &str = "created";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Entries in insertion order; keys are unique.
+    pub entries: Vec<(String, String)>,
+}
+
+impl Metadata {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Sets `key`, replacing any previous value.
+    pub fn set(&mut self, key: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_string(), value)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Loads the metadata chunk of a `.bin` file; empty if there is none.
+    pub fn load(bytes: &[u8], header: &FileHeader) -> Result<Self, FormatError> {
+        match chunk::find_chunk(bytes, header, chunk::TAG_METADATA)? {
+            Some(data) => Self::parse(data),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, FormatError> {
+        let mut entries = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let key = read_str(data, &mut pos, 2)?;
+            let value = read_str(data, &mut pos, 4)?;
+            entries.push((key, value));
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (key, value) in &self.entries {
+            out.extend_from_slice(&(key.len() as u16).to_le_bytes());
+            out.extend_from_slice(key.as_bytes());
+            out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            out.extend_from_slice(value.as_bytes());
+        }
+        out
+    }
+}
+
+/// Reads a string prefixed by a `len_size`-byte length.
+fn read_str(data: &[u8], pos: &mut usize, len_size: usize) -> Result<String, FormatError> {
+    let truncated = |needed| FormatError::Truncated {
+        needed,
+        available: data.len(),
+    };
+    let start = *pos + len_size;
+    if data.len() < start {
+        return Err(truncated(start));
+    }
+    let mut len = [0u8; 4];
+    len[..len_size].copy_from_slice(&data[*pos..start]);
+    let end = start + u32::from_le_bytes(len) as usize;
+    if data.len() < end {
+        return Err(truncated(end));
+    }
+    *pos = end;
+    String::from_utf8(data[start..end].to_vec()).map_err(|_| FormatError::InvalidField("metadata"))
+}
+
+/// Formats seconds since the Unix epoch as ISO 8601 UTC, e.g.
+/// `2024-05-01T12:00:00Z`.
+pub fn utc_timestamp(unix_secs: u64) -> String {
+    let (days, secs) = (unix_secs / 86_400, unix_secs % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
+
+/// The current time as [`utc_timestamp`].
+pub fn now_utc() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    utc_timestamp(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BinWriter;
+    use std::io::Cursor;
+
+    fn sample() -> Metadata {
+        let mut meta = Metadata::default();
+        meta.set(KEY_TITLE, "Bad Apple!!");
+        meta.set(KEY_SOURCE_URL, "https://example.com/v?id=1");
+        meta.set(KEY_TITLE, "Bad Apple!! (PV)");
+        meta.set("note", "");
+        meta
+    }
+
+    #[test]
+    fn set_replaces_existing_keys() {
+        let meta = sample();
+        assert_eq!(meta.entries.len(), 3);
+        assert_eq!(meta.get(KEY_TITLE), Some("Bad Apple!! (PV)"));
+        assert_eq!(meta.get("note"), Some(""));
+        assert_eq!(meta.get(KEY_CREATED), None);
+    }
+
+    #[test]
+    fn metadata_is_stored_in_the_file() {
+        let writer = BinWriter::new(Cursor::new(Vec::new()), 8, 8, 30)
+            .unwrap()
+            .with_metadata(sample());
+        let bytes = writer.finish().unwrap().0.into_inner();
+        let header = FileHeader::parse(&bytes).unwrap();
+        assert_eq!(Metadata::load(&bytes, &header), Ok(sample()));
+
+        let plain = BinWriter::new(Cursor::new(Vec::new()), 8, 8, 30).unwrap();
+        let bytes = plain.finish().unwrap().0.into_inner();
+        let header = FileHeader::parse(&bytes).unwrap();
+        assert!(Metadata::load(&bytes, &header).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_cut_off_entries() {
+        let bytes = sample().to_bytes();
+        let first_entry = 2 + KEY_TITLE.len() + 4 + "Bad Apple!! (PV)".len();
+        for len in [1, 2, 7, first_entry - 1, first_entry + 3, bytes.len() - 1] {
+            assert!(
+                matches!(
+                    Metadata::parse(&bytes[..len]),
+                    Err(FormatError::Truncated { .. })
+                ),
+                "{len} bytes"
+            );
+        }
+        assert_eq!(
+            Metadata::parse(&bytes[..first_entry])
+                .unwrap()
+                .entries
+                .len(),
+            1
+        );
+
+        let mut bad = bytes;
+        bad[2] = 0xC0;
+        assert_eq!(
+            Metadata::parse(&bad),
+            Err(FormatError::InvalidField("metadata"))
+        );
+    }
+
+    #[test]
+    fn formats_utc_timestamps() {
+        assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(utc_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(utc_timestamp(1_714_564_800), "2024-05-01T12:00:00Z");
+    }
+}
@@ -0,0 +1,125 @@
+//! Embedded color table for palette-indexed overlays.
+//!
+//! Files with [`FLAG_PALETTE`](crate::file_header::FLAG_PALETTE) set store
+//! the colors in a `PLTE` chunk, one little-endian `u32` (`0x00RRGGBB`) per
+//! entry. Within a frame, a color marker selects the entry used by the rects
+//! that follow it; rects before any marker are white.
+
+use crate::{chunk, FileHeader, FormatError, PixelRect};
+
+/// Opcode of color markers; the low byte holds the palette index.
+pub const COLOR_OPCODE: u16 = 0x200;
+
+pub const MAX_COLORS: usize = 256;
+
+pub const WHITE: u32 = 0x00FF_FFFF;
+
+pub const fn color_marker(index: u8) -> PixelRect {
+    PixelRect::control(COLOR_OPCODE | index as u16)
+}
+
+/// Palette index selected by `rect`, if it is a color marker.
+#[inline]
+pub fn color_of(rect: &PixelRect) -> Option<u8> {
+    (rect.is_control() && rect.h & 0xFF00 == COLOR_OPCODE).then_some(rect.h as u8)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Palette {
+    /// `0x00RRGGBB` entries.
+    pub colors: Vec<u32>,
+}
+
+impl Palette {
+    pub fn new(colors: Vec<u32>) -> Self {
+        Self { colors }
+    }
+
+    /// Color of `index`; out-of-range entries fall back to white.
+    #[inline]
+    pub fn get(&self, index: u8) -> u32 {
+        self.colors.get(index as usize).copied().unwrap_or(WHITE)
+    }
+
+    /// Loads the palette chunk of a `.bin` file, if it has one.
+    pub fn load(bytes: &[u8], header: &FileHeader) -> Result<Option<Self>, FormatError> {
+        chunk::find_chunk(bytes, header, chunk::TAG_PALETTE)?
+            .map(Self::parse)
+            .transpose()
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, FormatError> {
+        if !data.len().is_multiple_of(4) || data.len() / 4 > MAX_COLORS {
+            return Err(FormatError::InvalidField("palette length"));
+        }
+        let colors = data
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()) & WHITE)
+            .collect();
+        Ok(Self { colors })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.colors.iter().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    /// Index of the entry closest to `rgb`.
+    pub fn nearest(&self, rgb: u32) -> u8 {
+        let dist = |c: u32| {
+            let d = |shift: u32| ((c >> shift) & 0xFF) as i32 - ((rgb >> shift) & 0xFF) as i32;
+            d(16).pow(2) + d(8).pow(2) + d(0).pow(2)
+        };
+        (0..self.colors.len())
+            .min_by_key(|&i| dist(self.colors[i]))
+            .unwrap_or(0) as u8
+    }
+}
+
+/// Premultiplied `0xAARRGGBB` for `rgb` at opacity `alpha`.
+#[inline]
+pub fn premultiplied(rgb: u32, alpha: u8) -> u32 {
+    let a = alpha as u32;
+    let scale = |shift: u32| ((rgb >> shift) & 0xFF) * a / 255;
+    a << 24 | scale(16) << 16 | scale(8) << 8 | scale(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BinWriter;
+    use std::io::Cursor;
+
+    #[test]
+    fn palette_is_stored_in_the_file() {
+        let palette = Palette::new(vec![0xFF0000, 0x00FF00, 0x0000FF]);
+        let writer = BinWriter::new(Cursor::new(Vec::new()), 8, 8, 30)
+            .unwrap()
+            .with_palette(palette.clone())
+            .unwrap();
+        let bytes = writer.finish().unwrap().0.into_inner();
+        let header = FileHeader::parse(&bytes).unwrap();
+        assert_eq!(Palette::load(&bytes, &header), Ok(Some(palette)));
+    }
+
+    #[test]
+    fn parse_checks_the_length() {
+        assert_eq!(
+            Palette::parse(&[0; 6]),
+            Err(FormatError::InvalidField("palette length"))
+        );
+        assert!(Palette::parse(&[0; 4 * (MAX_COLORS + 1)]).is_err());
+        // The unused top byte is masked off
+        assert_eq!(Palette::parse(&[1, 2, 3, 4]).unwrap().colors, [0x030201]);
+    }
+
+    #[test]
+    fn picks_the_nearest_color() {
+        let palette = Palette::new(vec![0x000000, 0xFF0000, 0xFFFFFF]);
+        assert_eq!(palette.nearest(0xE01010), 1);
+        assert_eq!(palette.nearest(0x202020), 0);
+        assert_eq!(palette.get(1), 0xFF0000);
+        assert_eq!(palette.get(9), WHITE);
+        assert_eq!(color_of(&color_marker(2)), Some(2));
+        assert_eq!(premultiplied(0xFF8000, 128), 0x80804000);
+    }
+}
@@ -0,0 +1,492 @@
+//! Footer appended to a patched runner executable.
+//!
+//! The footer sits at the very end of the file and describes where the
+//! embedded chunks (video, audio, ...) live. Layout of the current version,
+//! all fields little-endian:
+//!
+//! ```text
+//! chunk table  chunk_count x { kind u16, codec u16, info u32, offset u64, len u64 }
+//! width        u16
+//! height       u16
+//! chunk_count  u16
+//! version      u16
+//! footer_len   u32     size of the whole footer, chunk table included
+//! reserved     u32
+//! magic        [u8; 8] "PS_PAYLD"
+//! ```
+//!
+//! An executable may hold several video chunks, one per resolution; the
+//! runner plays the one closest to the screen size. Their `info` holds the
+//! frame size, see [`PayloadChunk::video_size`].
+//!
+//! Executables built before the footer was versioned end with a 48-byte
+//! `#[repr(C)]` struct holding the `PS_PATCH` magic; those are still parsed
+//! and reported as version 1.
+
+use crate::FormatError;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+pub const FOOTER_MAGIC: [u8; 8] = *b"PS_PAYLD";
+pub const FOOTER_VERSION: u16 = 2;
+
+const TAIL_SIZE: usize = 24;
+const ENTRY_SIZE: usize = 24;
+
+const LEGACY_MAGIC: [u8; 8] = *b"PS_PATCH";
+const LEGACY_SIZE: usize = 48;
+const LEGACY_MAGIC_OFFSET: usize = 36;
+
+pub const KIND_VIDEO: u16 = 1;
+/// Absent from silent builds.
+pub const KIND_AUDIO: u16 = 2;
+/// [`crate::Metadata`] of the build, stored raw.
+pub const KIND_METADATA: u16 = 3;
+
+/// Chunk data is stored as-is.
+pub const CODEC_RAW: u16 = 0;
+/// Chunk data is compressed with [`crate::compress`].
+pub const CODEC_LZ4: u16 = 1;
+
+/// Codecs of `KIND_AUDIO` chunks. Ogg Vorbis is `0` because it was the only
+/// format before the codec was recorded, when audio was stored as
+/// `CODEC_RAW`.
+pub const AUDIO_OGG: u16 = 0;
+pub const AUDIO_WAV: u16 = 1;
+pub const AUDIO_FLAC: u16 = 2;
+pub const AUDIO_MP3: u16 = 3;
+
+/// File extensions of the supported audio formats, in lookup order.
+pub const AUDIO_EXTENSIONS: [(&str, u16); 4] = [
+    ("ogg", AUDIO_OGG),
+    ("wav", AUDIO_WAV),
+    ("flac", AUDIO_FLAC),
+    ("mp3", AUDIO_MP3),
+];
+
+/// Audio codec of a file extension, case-insensitive.
+pub fn audio_codec_for_extension(ext: &str) -> Option<u16> {
+    AUDIO_EXTENSIONS
+        .iter()
+        .find(|(e, _)| e.eq_ignore_ascii_case(ext))
+        .map(|&(_, codec)| codec)
+}
+
+/// Short name of an audio codec, e.g. `FLAC`.
+pub fn audio_codec_name(codec: u16) -> &'static str {
+    match codec {
+        AUDIO_OGG => "OGG",
+        AUDIO_WAV => "WAV",
+        AUDIO_FLAC => "FLAC",
+        AUDIO_MP3 => "MP3",
+        _ => "unknown",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayloadChunk {
+    pub kind: u16,
+    /// Encoding of the chunk data.
+    pub codec: u16,
+    /// Kind-specific. Video chunks store `width | height << 16`; `0` in
+    /// executables built before multi-resolution payloads.
+    pub info: u32,
+    pub offset: u64,
+    pub len: u64,
+}
+
+impl PayloadChunk {
+    /// Byte range of the data in the file; `None` if the footer holds an
+    /// offset and length that overflow.
+    pub fn range(&self) -> Option<Range<u64>> {
+        Some(self.offset..self.offset.checked_add(self.len)?)
+    }
+
+    /// Frame size recorded for a video chunk, if any.
+    pub fn video_size(&self) -> Option<(u16, u16)> {
+        let (width, height) = (self.info as u16, (self.info >> 16) as u16);
+        (width != 0 && height != 0).then_some((width, height))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadFooter {
+    /// Footer version, `1` for executables with the legacy fixed footer.
+    pub version: u16,
+    pub width: u16,
+    pub height: u16,
+    pub chunks: Vec<PayloadChunk>,
+}
+
+impl PayloadFooter {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            version: FOOTER_VERSION,
+            width,
+            height,
+            chunks: Vec::new(),
+        }
+    }
+
+    pub fn push_chunk(&mut self, kind: u16, codec: u16, offset: u64, len: u64) {
+        self.chunks.push(PayloadChunk {
+            kind,
+            codec,
+            info: 0,
+            offset,
+            len,
+        });
+    }
+
+    /// Adds a video chunk whose frames are `width` x `height`.
+    pub fn push_video(&mut self, codec: u16, width: u16, height: u16, offset: u64, len: u64) {
+        self.chunks.push(PayloadChunk {
+            kind: KIND_VIDEO,
+            codec,
+            info: width as u32 | (height as u32) << 16,
+            offset,
+            len,
+        });
+    }
+
+    /// First chunk of the given kind.
+    pub fn chunk(&self, kind: u16) -> Option<&PayloadChunk> {
+        self.chunks.iter().find(|c| c.kind == kind)
+    }
+
+    /// The first video chunk; what runners without resolution selection
+    /// play.
+    pub fn video(&self) -> Option<&PayloadChunk> {
+        self.chunk(KIND_VIDEO)
+    }
+
+    /// All video chunks, one per resolution.
+    pub fn videos(&self) -> impl Iterator<Item = &PayloadChunk> {
+        self.chunks.iter().filter(|c| c.kind == KIND_VIDEO)
+    }
+
+    /// Frame size of a video chunk, falling back to the footer's size for
+    /// executables that don't record it per chunk.
+    pub fn video_size(&self, video: &PayloadChunk) -> (u16, u16) {
+        video.video_size().unwrap_or((self.width, self.height))
+    }
+
+    /// The video chunk that best fits a `width` x `height` screen: the
+    /// closest aspect ratio first, then the closest frame size. On a tie
+    /// the larger one wins, since scaling down looks better than scaling up.
+    pub fn closest_video(&self, width: u16, height: u16) -> Option<&PayloadChunk> {
+        let screen_aspect = width.max(1) as f64 / height.max(1) as f64;
+        self.videos().min_by_key(|&video| {
+            let (w, h) = self.video_size(video);
+            let aspect = w.max(1) as f64 / h.max(1) as f64;
+            // Ratios within 1% of each other count as the same
+            let aspect_mismatch = ((aspect / screen_aspect).ln().abs() * 100.0).round() as u32;
+            let distance = (w as i32 - width as i32).abs() + (h as i32 - height as i32).abs();
+            (
+                aspect_mismatch,
+                distance,
+                std::cmp::Reverse(w as u32 * h as u32),
+            )
+        })
+    }
+
+    pub fn audio(&self) -> Option<&PayloadChunk> {
+        self.chunk(KIND_AUDIO)
+    }
+
+    pub fn metadata(&self) -> Option<&PayloadChunk> {
+        self.chunk(KIND_METADATA)
+    }
+
+    /// Size of the serialized footer in bytes.
+    pub fn encoded_len(&self) -> usize {
+        self.chunks.len() * ENTRY_SIZE + TAIL_SIZE
+    }
+
+    /// Serializes the footer in the current version.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.encoded_len());
+        for c in &self.chunks {
+            out.extend_from_slice(&c.kind.to_le_bytes());
+            out.extend_from_slice(&c.codec.to_le_bytes());
+            out.extend_from_slice(&c.info.to_le_bytes());
+            out.extend_from_slice(&c.offset.to_le_bytes());
+            out.extend_from_slice(&c.len.to_le_bytes());
+        }
+        out.extend_from_slice(&self.width.to_le_bytes());
+        out.extend_from_slice(&self.height.to_le_bytes());
+        out.extend_from_slice(&(self.chunks.len() as u16).to_le_bytes());
+        out.extend_from_slice(&FOOTER_VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.encoded_len() as u32).to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&FOOTER_MAGIC);
+        out
+    }
+
+    /// Parses the footer from the trailing bytes of a file.
+    ///
+    /// `tail` must end where the file ends. If it is too short,
+    /// [`FormatError::Truncated`] reports how many trailing bytes are needed.
+    pub fn parse(tail: &[u8]) -> Result<Self, FormatError> {
+        if tail.len() >= TAIL_SIZE && tail[tail.len() - 8..] == FOOTER_MAGIC {
+            return Self::parse_current(tail);
+        }
+        if tail.len() >= LEGACY_SIZE {
+            let legacy = &tail[tail.len() - LEGACY_SIZE..];
+            if legacy[LEGACY_MAGIC_OFFSET..LEGACY_MAGIC_OFFSET + 8] == LEGACY_MAGIC {
+                return Ok(Self::parse_legacy(legacy));
+            }
+            return Err(FormatError::BadMagic);
+        }
+        Err(FormatError::Truncated {
+            needed: LEGACY_SIZE,
+            available: tail.len(),
+        })
+    }
+
+    fn parse_current(tail: &[u8]) -> Result<Self, FormatError> {
+        let t = &tail[tail.len() - TAIL_SIZE..];
+        let u16_at = |b: &[u8], o: usize| u16::from_le_bytes([b[o], b[o + 1]]);
+        let u64_at = |b: &[u8], o: usize| u64::from_le_bytes(b[o..o + 8].try_into().unwrap());
+
+        let version = u16_at(t, 6);
+        if version < 2 || version > FOOTER_VERSION {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+
+        let count = u16_at(t, 4) as usize;
+        let footer_len = u32::from_le_bytes(t[8..12].try_into().unwrap()) as usize;
+        if footer_len != count * ENTRY_SIZE + TAIL_SIZE {
+            return Err(FormatError::InvalidField("footer_len"));
+        }
+        if tail.len() < footer_len {
+            return Err(FormatError::Truncated {
+                needed: footer_len,
+                available: tail.len(),
+            });
+        }
+
+        let table = &tail[tail.len() - footer_len..tail.len() - TAIL_SIZE];
+        let chunks = table
+            .chunks_exact(ENTRY_SIZE)
+            .map(|e| PayloadChunk {
+                kind: u16_at(e, 0),
+                codec: u16_at(e, 2),
+                info: u32::from_le_bytes(e[4..8].try_into().unwrap()),
+                offset: u64_at(e, 8),
+                len: u64_at(e, 16),
+            })
+            .collect();
+
+        Ok(Self {
+            version,
+            width: u16_at(t, 0),
+            height: u16_at(t, 2),
+            chunks,
+        })
+    }
+
+    fn parse_legacy(b: &[u8]) -> Self {
+        let u64_at = |o: usize| u64::from_le_bytes(b[o..o + 8].try_into().unwrap());
+        let mut footer = Self {
+            version: 1,
+            width: u16::from_le_bytes([b[32], b[33]]),
+            height: u16::from_le_bytes([b[34], b[35]]),
+            chunks: Vec::new(),
+        };
+        footer.push_chunk(KIND_VIDEO, CODEC_RAW, u64_at(0), u64_at(8));
+        footer.push_chunk(KIND_AUDIO, CODEC_RAW, u64_at(16), u64_at(24));
+        footer
+    }
+
+    /// Reads and validates the footer at the end of `reader`.
+    pub fn read_from<R: Read + Seek>(reader: &mut R) -> io::Result<Self> {
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        let mut wanted = LEGACY_SIZE.max(TAIL_SIZE) as u64;
+
+        loop {
+            let probe = wanted.min(file_len);
+            let mut tail = vec![0u8; probe as usize];
+            reader.seek(SeekFrom::End(-(probe as i64)))?;
+            reader.read_exact(&mut tail)?;
+
+            match Self::parse(&tail) {
+                Ok(footer) => {
+                    let data_end = file_len - footer.stored_len() as u64;
+                    let in_bounds = |c: &PayloadChunk| c.range().is_some_and(|r| r.end <= data_end);
+                    if !footer.chunks.iter().all(in_bounds) {
+                        return Err(invalid_data(FormatError::InvalidField("chunk range")));
+                    }
+                    return Ok(footer);
+                }
+                Err(FormatError::Truncated { needed, .. })
+                    if (needed as u64) > probe && (needed as u64) <= file_len =>
+                {
+                    wanted = needed as u64;
+                }
+                Err(e) => return Err(invalid_data(e)),
+            }
+        }
+    }
+
+    /// Reads the data of `chunk` from the patched file.
+    pub fn read_chunk<R: Read + Seek>(reader: &mut R, chunk: &PayloadChunk) -> io::Result<Vec<u8>> {
+        let mut data = vec![0u8; chunk.len as usize];
+        reader.seek(SeekFrom::Start(chunk.offset))?;
+        reader.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    /// Size of the footer as stored in the file it was read from.
+    fn stored_len(&self) -> usize {
+        if self.version == 1 {
+            LEGACY_SIZE
+        } else {
+            self.encoded_len()
+        }
+    }
+}
+
+fn invalid_data(e: FormatError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const DATA_LEN: usize = 180;
+
+    fn sample_footer() -> PayloadFooter {
+        let mut footer = PayloadFooter::new(1920, 1080);
+        footer.push_video(CODEC_LZ4, 1920, 1080, 0, 100);
+        footer.push_video(CODEC_RAW, 640, 360, 100, 50);
+        footer.push_chunk(KIND_AUDIO, AUDIO_OGG, 150, 30);
+        footer
+    }
+
+    /// An executable of `DATA_LEN` bytes with `footer` appended.
+    fn patched(footer: &PayloadFooter) -> Cursor<Vec<u8>> {
+        let mut file = vec![0xAB; DATA_LEN];
+        file.extend_from_slice(&footer.to_bytes());
+        Cursor::new(file)
+    }
+
+    #[test]
+    fn footer_survives_a_round_trip() {
+        let footer = sample_footer();
+        let bytes = footer.to_bytes();
+        assert_eq!(bytes.len(), footer.encoded_len());
+        assert_eq!(PayloadFooter::parse(&bytes), Ok(footer.clone()));
+
+        let mut file = patched(&footer);
+        let read = PayloadFooter::read_from(&mut file).unwrap();
+        assert_eq!(read, footer);
+        let audio = PayloadFooter::read_chunk(&mut file, read.audio().unwrap()).unwrap();
+        assert_eq!(audio, vec![0xAB; 30]);
+    }
+
+    #[test]
+    fn picks_the_closest_video() {
+        let footer = sample_footer();
+        let size = |video: Option<&PayloadChunk>| footer.video_size(video.unwrap());
+        assert_eq!(footer.videos().count(), 2);
+        assert_eq!(size(footer.closest_video(1366, 768)), (1920, 1080));
+        assert_eq!(size(footer.closest_video(800, 450)), (640, 360));
+
+        // Ties go to the larger video
+        let mut footer = PayloadFooter::new(0, 0);
+        footer.push_video(CODEC_RAW, 800, 800, 0, 1);
+        footer.push_video(CODEC_RAW, 1000, 1000, 1, 1);
+        assert_eq!(footer.closest_video(900, 900), footer.chunks.get(1));
+
+        // A portrait screen prefers the portrait cut over a closer size
+        let mut footer = sample_footer();
+        footer.push_video(CODEC_RAW, 1080, 1920, 180, 10);
+        assert_eq!(size(footer.closest_video(720, 1280)), (1080, 1920));
+        assert_eq!(size(footer.closest_video(1280, 720)), (1920, 1080));
+        assert_eq!(size(footer.closest_video(1170, 2532)), (1080, 1920));
+
+        // Chunks without a recorded size use the footer's
+        let mut footer = PayloadFooter::new(320, 240);
+        footer.push_chunk(KIND_VIDEO, CODEC_RAW, 0, 1);
+        assert_eq!(footer.video_size(&footer.chunks[0]), (320, 240));
+    }
+
+    #[test]
+    fn maps_audio_extensions() {
+        assert_eq!(audio_codec_for_extension("FLAC"), Some(AUDIO_FLAC));
+        assert_eq!(audio_codec_for_extension("ogg"), Some(AUDIO_OGG));
+        assert_eq!(audio_codec_for_extension("aac"), None);
+        for (ext, codec) in AUDIO_EXTENSIONS {
+            assert!(audio_codec_name(codec).eq_ignore_ascii_case(ext));
+        }
+    }
+
+    #[test]
+    fn reads_legacy_footer() {
+        let mut legacy = Vec::new();
+        for v in [0u64, 150, 150, 30] {
+            legacy.extend_from_slice(&v.to_le_bytes());
+        }
+        legacy.extend_from_slice(&1280u16.to_le_bytes());
+        legacy.extend_from_slice(&720u16.to_le_bytes());
+        legacy.extend_from_slice(&LEGACY_MAGIC);
+        legacy.resize(LEGACY_SIZE, 0);
+
+        let mut file = vec![0; DATA_LEN];
+        file.extend_from_slice(&legacy);
+        let footer = PayloadFooter::read_from(&mut Cursor::new(file)).unwrap();
+        assert_eq!(footer.version, 1);
+        assert_eq!((footer.width, footer.height), (1280, 720));
+        assert_eq!(footer.video().and_then(PayloadChunk::range), Some(0..150));
+        assert_eq!(footer.audio().and_then(PayloadChunk::range), Some(150..180));
+    }
+
+    #[test]
+    fn short_tails_are_rejected() {
+        let bytes = sample_footer().to_bytes();
+        for len in 0..bytes.len() {
+            let tail = &bytes[bytes.len() - len..];
+            assert!(PayloadFooter::parse(tail).is_err(), "{len} bytes");
+        }
+
+        let mut file = patched(&sample_footer()).into_inner();
+        file.drain(..file.len() - 40);
+        let err = PayloadFooter::read_from(&mut Cursor::new(file)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn inconsistent_footers_are_rejected() {
+        let mut bytes = sample_footer().to_bytes();
+        let version = bytes.len() - 18;
+        bytes[version] = 3;
+        assert_eq!(
+            PayloadFooter::parse(&bytes),
+            Err(FormatError::UnsupportedVersion(3))
+        );
+
+        let mut bytes = sample_footer().to_bytes();
+        let count = bytes.len() - 20;
+        bytes[count] += 1;
+        assert_eq!(
+            PayloadFooter::parse(&bytes),
+            Err(FormatError::InvalidField("footer_len"))
+        );
+
+        // Chunks must lie within the data before the footer
+        let mut footer = sample_footer();
+        footer.push_chunk(KIND_AUDIO, CODEC_RAW, 170, 20);
+        let err = PayloadFooter::read_from(&mut patched(&footer)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Even when the range wraps around
+        let mut footer = sample_footer();
+        footer.push_chunk(KIND_AUDIO, CODEC_RAW, u64::MAX, 2);
+        assert_eq!(footer.chunks[3].range(), None);
+        let err = PayloadFooter::read_from(&mut patched(&footer)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
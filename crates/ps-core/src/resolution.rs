@@ -0,0 +1,148 @@
+//! Output frame sizes.
+//!
+//! 16:9 sizes go by their usual names (`1080p`), anything else by its
+//! dimensions (`1080x1920` for portrait, `2560x1080` for ultrawide). The
+//! same text is used in file names, so `project_1080x1920.bin` can be
+//! traced back to its size.
+
+use std::{fmt, str::FromStr};
+
+/// Width and height of a video in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Resolution {
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Resolution {
+    pub const HD: Self = Self::widescreen(720);
+    pub const FULL_HD: Self = Self::widescreen(1080);
+    pub const QHD: Self = Self::widescreen(1440);
+    pub const UHD: Self = Self::widescreen(2160);
+
+    /// Sizes offered when none is given.
+    pub const PRESETS: [Self; 4] = [Self::HD, Self::FULL_HD, Self::QHD, Self::UHD];
+
+    pub const fn new(width: u16, height: u16) -> Self {
+        Self { width, height }
+    }
+
+    /// The 16:9 size `height` pixels tall, with the width rounded to an
+    /// even number (`480` gives 854x480).
+    pub const fn widescreen(height: u16) -> Self {
+        let width = (height as u32 * 16 + 9) / 18 * 2;
+        Self::new(width as u16, height)
+    }
+
+    pub fn is_widescreen(&self) -> bool {
+        *self == Self::widescreen(self.height)
+    }
+
+    pub fn is_portrait(&self) -> bool {
+        self.height > self.width
+    }
+
+    #[inline]
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+
+    /// Splits a resolution tag off the end of a file stem, e.g.
+    /// `my_overlay_1080x1920` gives `("my_overlay", 1080x1920)`.
+    pub fn split_suffix(stem: &str) -> Option<(&str, Self)> {
+        let (name, tag) = stem.rsplit_once('_')?;
+        Some((name, tag.parse().ok()?))
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_widescreen() {
+            write!(f, "{}p", self.height)
+        } else {
+            write!(f, "{}x{}", self.width, self.height)
+        }
+    }
+}
+
+/// Accepts `1080p` (16:9) and `WIDTHxHEIGHT`, e.g. `1080x1920`.
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid resolution '{}' (expected e.g. 1080p or 1080x1920)",
+                s
+            )
+        };
+        let s_lower = s.trim().to_ascii_lowercase();
+        let resolution = match s_lower.split_once('x') {
+            Some((w, h)) => Self::new(
+                w.trim().parse().map_err(|_| invalid())?,
+                h.trim().parse().map_err(|_| invalid())?,
+            ),
+            None => {
+                let height: u16 = s_lower
+                    .strip_suffix('p')
+                    .ok_or_else(invalid)?
+                    .parse()
+                    .map_err(|_| invalid())?;
+                if height > u16::MAX / 16 * 9 {
+                    return Err(invalid());
+                }
+                Self::widescreen(height)
+            }
+        };
+        if resolution.width == 0 || resolution.height == 0 {
+            return Err(invalid());
+        }
+        Ok(resolution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_and_dimensions() {
+        assert_eq!("1080p".parse(), Ok(Resolution::FULL_HD));
+        assert_eq!(" 720P ".parse(), Ok(Resolution::HD));
+        assert_eq!("480p".parse(), Ok(Resolution::new(854, 480)));
+        assert_eq!("1080x1920".parse(), Ok(Resolution::new(1080, 1920)));
+        assert_eq!("2560X1080".parse(), Ok(Resolution::new(2560, 1080)));
+        for bad in [
+            "", "p", "1080", "0p", "0x100", "1920x", "70000x10", "40000p",
+        ] {
+            assert!(bad.parse::<Resolution>().is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for res in Resolution::PRESETS
+            .into_iter()
+            .chain([Resolution::new(1080, 1920), Resolution::new(2560, 1080)])
+        {
+            assert_eq!(res.to_string().parse(), Ok(res));
+        }
+        assert_eq!(Resolution::UHD.to_string(), "2160p");
+        assert_eq!(Resolution::new(1080, 1920).to_string(), "1080x1920");
+        assert!(Resolution::new(1080, 1920).is_portrait());
+    }
+
+    #[test]
+    fn splits_file_suffixes() {
+        assert_eq!(
+            Resolution::split_suffix("my_overlay_1080x1920"),
+            Some(("my_overlay", Resolution::new(1080, 1920)))
+        );
+        assert_eq!(
+            Resolution::split_suffix("clip_720p"),
+            Some(("clip", Resolution::HD))
+        );
+        assert_eq!(Resolution::split_suffix("my_overlay"), None);
+        assert_eq!(Resolution::split_suffix("overlay"), None);
+    }
+}
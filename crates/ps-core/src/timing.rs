@@ -0,0 +1,234 @@
+//! Frame timing.
+//!
+//! The header stores the frame rate as a fraction (`30000/1001` for NTSC
+//! 29.97), so long clips stay in sync with their audio. Files with
+//! [`FLAG_TIMESTAMPS`](crate::file_header::FLAG_TIMESTAMPS) set come from
+//! variable frame rate sources and also carry a `TIME` chunk holding the
+//! presentation time of every frame, one little-endian `u32` of
+//! milliseconds each.
+
+use crate::{chunk, file_header::FLAG_TIMESTAMPS, FileHeader, FormatError};
+use std::{fmt, str::FromStr};
+
+/// Frames per second as `num / den`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameRate {
+    pub num: u32,
+    pub den: u32,
+}
+
+impl FrameRate {
+    pub const fn new(num: u32, den: u32) -> Self {
+        Self { num, den }
+    }
+
+    pub const fn from_fps(fps: u16) -> Self {
+        Self::new(fps as u32, 1)
+    }
+
+    #[inline]
+    pub fn as_f64(&self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    /// Nearest whole frame rate, at least 1.
+    pub fn rounded(&self) -> u16 {
+        (self.as_f64().round() as u16).max(1)
+    }
+
+    /// Start time of `frame` in seconds.
+    pub fn time_of(&self, frame: usize) -> f64 {
+        frame as f64 * self.den as f64 / self.num as f64
+    }
+
+    /// Frame shown at `seconds`.
+    pub fn frame_at(&self, seconds: f64) -> usize {
+        (seconds.max(0.0) * self.as_f64()).floor() as usize
+    }
+}
+
+impl fmt::Display for FrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{:.3}", self.as_f64())
+        }
+    }
+}
+
+/// Accepts `30`, `30000/1001` (as printed by ffprobe) and `29.97`.
+impl FromStr for FrameRate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid frame rate '{}'", s);
+        let rate = match s.trim().split_once('/') {
+            Some((num, den)) => Self::new(
+                num.trim().parse().map_err(|_| invalid())?,
+                den.trim().parse().map_err(|_| invalid())?,
+            ),
+            None => {
+                let fps: f64 = s.trim().parse().map_err(|_| invalid())?;
+                if fps.fract() == 0.0 {
+                    Self::new(fps as u32, 1)
+                } else {
+                    Self::new((fps * 1000.0).round() as u32, 1000)
+                }
+            }
+        };
+        if rate.num == 0 || rate.den == 0 {
+            return Err(invalid());
+        }
+        Ok(rate)
+    }
+}
+
+/// When each frame of a file is shown.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Timing {
+    Constant(FrameRate),
+    /// Presentation time of every frame in milliseconds.
+    Variable(Vec<u32>),
+}
+
+impl Timing {
+    /// Reads the timestamp chunk of a `.bin` file, falling back to the
+    /// header's frame rate.
+    pub fn load(bytes: &[u8], header: &FileHeader) -> Result<Self, FormatError> {
+        let data = if header.flags & FLAG_TIMESTAMPS != 0 {
+            chunk::find_chunk(bytes, header, chunk::TAG_TIMESTAMPS)?
+        } else {
+            None
+        };
+        Self::from_chunk(header, data)
+    }
+
+    /// Builds the timing from a timestamp chunk, if there is one.
+    /// Unfinalized files lose their chunks and play at the nominal rate.
+    pub fn from_chunk(header: &FileHeader, data: Option<&[u8]>) -> Result<Self, FormatError> {
+        let Some(data) = data else {
+            return Ok(Self::Constant(header.frame_rate));
+        };
+        if !data.len().is_multiple_of(4) {
+            return Err(FormatError::InvalidField("timestamps"));
+        }
+        let times: Vec<u32> = data
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        if times.windows(2).any(|w| w[1] < w[0]) {
+            return Err(FormatError::InvalidField("timestamps"));
+        }
+        Ok(Self::Variable(times))
+    }
+
+    /// Start time of `frame` in seconds.
+    pub fn time_of(&self, frame: usize) -> f64 {
+        match self {
+            Self::Constant(rate) => rate.time_of(frame),
+            Self::Variable(times) => match times.get(frame) {
+                Some(&ms) => ms as f64 / 1000.0,
+                None => end_of(times) as f64 / 1000.0,
+            },
+        }
+    }
+
+    /// Frame shown at `seconds`. For variable timing this is the frame
+    /// count once the last frame is over.
+    pub fn frame_at(&self, seconds: f64) -> usize {
+        match self {
+            Self::Constant(rate) => rate.frame_at(seconds),
+            Self::Variable(times) => {
+                let ms = (seconds.max(0.0) * 1000.0) as u32;
+                if ms >= end_of(times) {
+                    return times.len();
+                }
+                times.partition_point(|&t| t <= ms).saturating_sub(1)
+            }
+        }
+    }
+}
+
+/// End of the last frame, assuming it lasts as long as the one before.
+fn end_of(times: &[u32]) -> u32 {
+    match times {
+        [.., a, b] => b.saturating_add(b.saturating_sub(*a)),
+        [b] => *b,
+        [] => 0,
+    }
+}
+
+/// Serializes frame times for the `TIME` chunk.
+pub fn timestamps_to_bytes(times: &[u32]) -> Vec<u8> {
+    times.iter().flat_map(|t| t.to_le_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BinWriter;
+    use std::io::Cursor;
+
+    #[test]
+    fn parses_frame_rates() {
+        assert_eq!("30".parse(), Ok(FrameRate::new(30, 1)));
+        assert_eq!(" 30000/1001 ".parse(), Ok(FrameRate::new(30000, 1001)));
+        assert_eq!("29.97".parse(), Ok(FrameRate::new(29970, 1000)));
+        for bad in ["", "abc", "0", "30/0", "0/1", "/1", "-5"] {
+            assert!(bad.parse::<FrameRate>().is_err(), "{bad:?}");
+        }
+
+        let ntsc = FrameRate::new(30000, 1001);
+        assert_eq!(ntsc.rounded(), 30);
+        assert_eq!(ntsc.to_string(), "29.970");
+        assert_eq!(ntsc.frame_at(ntsc.time_of(1000)), 1000);
+    }
+
+    #[test]
+    fn variable_timing_comes_from_the_chunk() {
+        let mut writer = BinWriter::new(Cursor::new(Vec::new()), 8, 8, 30)
+            .unwrap()
+            .with_timestamps()
+            .unwrap();
+        for ms in [0, 40, 100, 90] {
+            writer.write_frame_at(&[], ms).unwrap();
+        }
+        let bytes = writer.finish().unwrap().0.into_inner();
+        let header = FileHeader::parse(&bytes).unwrap();
+
+        // The writer keeps the table monotonic
+        let timing = Timing::load(&bytes, &header).unwrap();
+        assert_eq!(timing, Timing::Variable(vec![0, 40, 100, 100]));
+        assert_eq!(timing.time_of(1), 0.04);
+        assert_eq!(timing.frame_at(0.07), 1);
+        // The last frame repeats the gap before it, here none
+        assert_eq!(timing.frame_at(0.1), 4);
+    }
+
+    #[test]
+    fn files_without_timestamps_are_constant() {
+        let header = FileHeader::new(8, 8, 24);
+        assert_eq!(
+            Timing::from_chunk(&header, None),
+            Ok(Timing::Constant(FrameRate::from_fps(24)))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_timestamps() {
+        let header = FileHeader::new(8, 8, 24);
+        let invalid = Err(FormatError::InvalidField("timestamps"));
+        assert_eq!(Timing::from_chunk(&header, Some(&[0; 6])), invalid);
+        let backwards = timestamps_to_bytes(&[0, 50, 40]);
+        assert_eq!(Timing::from_chunk(&header, Some(&backwards)), invalid);
+    }
+
+    #[test]
+    fn late_timestamps_dont_overflow() {
+        let late = timestamps_to_bytes(&[0, u32::MAX - 10, u32::MAX - 1]);
+        let timing = Timing::from_chunk(&FileHeader::new(8, 8, 24), Some(&late)).unwrap();
+        assert_eq!(timing.time_of(3), u32::MAX as f64 / 1000.0);
+        assert_eq!(timing.frame_at(1e9), 3);
+    }
+}
@@ -0,0 +1,281 @@
+//! Compact rect stream encoding.
+//!
+//! In files with [`FLAG_VARINT`](crate::file_header::FLAG_VARINT) set, each
+//! record is stored as LEB128 varints instead of a fixed 8 bytes:
+//!
+//! ```text
+//! w   varint
+//! h   varint
+//! dx  zigzag varint   only if w != 0
+//! dy  zigzag varint   only if w != 0
+//! ```
+//!
+//! `dx`/`dy` are relative to the previous rect of the same frame (the first
+//! rect is relative to `(0, 0)`). Records with zero width carry no position:
+//! `w == 0, h == 0` ends the frame and `w == 0, h != 0` is a control record.
+
+use crate::{frame::check_bounds, FormatError, FrameIndex, PixelRect};
+
+/// Appends `records` followed by the end-of-frame marker.
+pub fn encode_frame(records: &[PixelRect], out: &mut Vec<u8>) {
+    let (mut px, mut py) = (0i32, 0i32);
+    for r in records.iter().chain([&PixelRect::EOS_MARKER]) {
+        let PixelRect { x, y, w, h } = *r;
+        write_varint(out, w as u32);
+        write_varint(out, h as u32);
+        if w != 0 {
+            write_varint(out, zigzag(x as i32 - px));
+            write_varint(out, zigzag(y as i32 - py));
+            (px, py) = (x as i32, y as i32);
+        }
+    }
+}
+
+/// Decodes one frame from the start of `bytes` into `out`, without the
+/// end-of-frame marker. Returns the number of bytes consumed.
+pub fn decode_frame(
+    bytes: &[u8],
+    out: &mut Vec<PixelRect>,
+    frame: usize,
+) -> Result<usize, FormatError> {
+    out.clear();
+    let mut pos = 0;
+    let (mut px, mut py) = (0i32, 0i32);
+    loop {
+        let mut next = || read_varint(bytes, &mut pos, frame);
+        let w = u16_from(next()?, frame)?;
+        let h = u16_from(next()?, frame)?;
+        if w == 0 {
+            if h == 0 {
+                return Ok(pos);
+            }
+            out.push(PixelRect::control(h));
+            continue;
+        }
+        let bad = || FormatError::BadVarint { frame };
+        let x = px.checked_add(unzigzag(next()?)).ok_or_else(bad)?;
+        let y = py.checked_add(unzigzag(next()?)).ok_or_else(bad)?;
+        out.push(PixelRect {
+            x: u16_from(x as u32, frame)?,
+            y: u16_from(y as u32, frame)?,
+            w,
+            h,
+        });
+        (px, py) = (x, y);
+    }
+}
+
+/// Byte offset of every frame in a varint stream.
+pub(crate) fn scan_offsets(stream: &[u8]) -> Vec<u64> {
+    let mut offsets = Vec::new();
+    let mut scratch = Vec::new();
+    let mut pos = 0;
+    while pos < stream.len() {
+        offsets.push(pos as u64);
+        match decode_frame(&stream[pos..], &mut scratch, offsets.len() - 1) {
+            Ok(used) => pos += used,
+            Err(_) => break,
+        }
+    }
+    offsets
+}
+
+fn write_varint(out: &mut Vec<u8>, mut v: u32) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize, frame: usize) -> Result<u32, FormatError> {
+    let mut v = 0u32;
+    for shift in (0..32).step_by(7) {
+        let Some(&b) = bytes.get(*pos) else {
+            return Err(FormatError::TruncatedFrame { frame });
+        };
+        *pos += 1;
+        v |= ((b & 0x7F) as u32) << shift;
+        if b & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(FormatError::BadVarint { frame })
+}
+
+fn u16_from(v: u32, frame: usize) -> Result<u16, FormatError> {
+    u16::try_from(v).map_err(|_| FormatError::BadVarint { frame })
+}
+
+#[inline]
+fn zigzag(v: i32) -> u32 {
+    ((v << 1) ^ (v >> 31)) as u32
+}
+
+#[inline]
+fn unzigzag(v: u32) -> i32 {
+    (v >> 1) as i32 ^ -((v & 1) as i32)
+}
+
+/// Counterpart of [`FrameReader`](crate::FrameReader) for compact streams.
+pub struct VarintReader<'a> {
+    stream: &'a [u8],
+    pos: usize,
+    frame: usize,
+    width: u16,
+    height: u16,
+    done: bool,
+    rects: Vec<PixelRect>,
+}
+
+impl<'a> VarintReader<'a> {
+    pub fn new(stream: &'a [u8], width: u16, height: u16) -> Self {
+        Self {
+            stream,
+            pos: 0,
+            frame: 0,
+            width,
+            height,
+            done: false,
+            rects: Vec::new(),
+        }
+    }
+
+    /// Index of the frame the next call to `next_frame` returns.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Jumps to `frame` using the index. Returns `false` if the frame is out
+    /// of range, leaving the reader where it was.
+    pub fn seek(&mut self, index: &FrameIndex, frame: usize) -> bool {
+        match index.frame_range(frame) {
+            Some(range) if range.start <= self.stream.len() => {
+                self.pos = range.start;
+                self.frame = frame;
+                self.done = false;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Decodes the next frame. After the first error the reader is
+    /// exhausted.
+    pub fn next_frame(&mut self) -> Option<Result<&[PixelRect], FormatError>> {
+        if self.done || self.pos >= self.stream.len() {
+            self.done = true;
+            return None;
+        }
+        let decoded =
+            decode_frame(&self.stream[self.pos..], &mut self.rects, self.frame).and_then(|used| {
+                check_bounds(&self.rects, self.width, self.height, self.frame)?;
+                Ok(used)
+            });
+        match decoded {
+            Ok(used) => {
+                self.pos += used;
+                self.frame += 1;
+                Some(Ok(&self.rects))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<PixelRect> {
+        let rect = |x, y, w, h| PixelRect { x, y, w, h };
+        vec![
+            rect(10, 20, 30, 40),
+            rect(0, 0, 1, 1),
+            PixelRect::control(7),
+            rect(u16::MAX, u16::MAX, u16::MAX, u16::MAX),
+            rect(5, 60000, 2, 1),
+            PixelRect::control(u16::MAX),
+        ]
+    }
+
+    #[test]
+    fn frame_decodes_to_its_records() {
+        let mut encoded = Vec::new();
+        encode_frame(&records(), &mut encoded);
+        let mut decoded = Vec::new();
+        assert_eq!(decode_frame(&encoded, &mut decoded, 0), Ok(encoded.len()));
+        assert_eq!(decoded, records());
+    }
+
+    #[test]
+    fn reader_walks_the_stream() {
+        let frames = [records(), Vec::new(), records()[..2].to_vec()];
+        let mut stream = Vec::new();
+        for frame in &frames {
+            encode_frame(frame, &mut stream);
+        }
+        let mut reader = VarintReader::new(&stream, 0, 0);
+        for frame in &frames {
+            assert_eq!(reader.next_frame().unwrap().unwrap(), &frame[..]);
+        }
+        assert!(reader.next_frame().is_none());
+
+        let index = FrameIndex::new(scan_offsets(&stream), stream.len() as u64);
+        assert!(reader.seek(&index, 2));
+        assert_eq!(reader.next_frame().unwrap().unwrap(), &frames[2][..]);
+    }
+
+    #[test]
+    fn cut_off_frames_are_truncated() {
+        let mut encoded = Vec::new();
+        encode_frame(&records(), &mut encoded);
+        for len in 0..encoded.len() {
+            let mut decoded = Vec::new();
+            assert_eq!(
+                decode_frame(&encoded[..len], &mut decoded, 3),
+                Err(FormatError::TruncatedFrame { frame: 3 })
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_varints_are_rejected() {
+        // A rect at the far edge, then a step past i32::MAX from it
+        let mut overflow = Vec::new();
+        for v in [1, 1, zigzag(u16::MAX as i32), 0, 1, 1, zigzag(i32::MAX), 0] {
+            write_varint(&mut overflow, v);
+        }
+        let malformed: [&[u8]; 4] = [
+            // Varint longer than 32 bits
+            &[0x80, 0x80, 0x80, 0x80, 0x80, 0x01],
+            // Width beyond u16
+            &[0xFF, 0xFF, 0x04],
+            // x below zero
+            &[1, 1, 1, 0, 0, 0],
+            &overflow,
+        ];
+        for bytes in malformed {
+            assert_eq!(
+                decode_frame(bytes, &mut Vec::new(), 0),
+                Err(FormatError::BadVarint { frame: 0 }),
+                "{bytes:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn reader_checks_bounds() {
+        let mut stream = Vec::new();
+        encode_frame(&records()[..2], &mut stream);
+        let mut reader = VarintReader::new(&stream, 32, 32);
+        assert!(matches!(
+            reader.next_frame(),
+            Some(Err(FormatError::RectOutOfBounds { frame: 0, .. }))
+        ));
+        assert!(reader.next_frame().is_none());
+    }
+}
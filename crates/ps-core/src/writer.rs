@@ -0,0 +1,415 @@
+use crate::{
+    chunk, delta,
+    file_header::{
+        FLAG_DELTA, FLAG_FINALIZED, FLAG_LEVELS, FLAG_PALETTE, FLAG_TIMESTAMPS, FLAG_VARINT,
+    },
+    level, timing,
+    varint::{self, VarintReader},
+    FileHeader, FormatError, FrameDecoder, FrameIndex, FrameRate, FrameReader, Layer, Metadata,
+    Palette, PixelRect,
+};
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// Streams frames into a `.bin` file.
+///
+/// A placeholder header is written up front; [`BinWriter::finish`] seeks back
+/// and fills in the frame count and stream length, then appends any chunks.
+/// A file left unfinished can be continued with [`BinWriter::resume`].
+pub struct BinWriter<W: Write + Seek> {
+    inner: W,
+    header: FileHeader,
+    scratch: Vec<u8>,
+    frame_offsets: Option<Vec<u64>>,
+    delta: Option<DeltaState>,
+    palette: Option<Palette>,
+    timestamps: Option<Vec<u32>>,
+    /// Encoded layer chunks.
+    layers: Vec<Vec<u8>>,
+    metadata: Option<Metadata>,
+}
+
+struct DeltaState {
+    keyframe_interval: u32,
+    previous: Vec<PixelRect>,
+    encoded: Vec<PixelRect>,
+}
+
+impl<W: Write + Seek> BinWriter<W> {
+    pub fn new(mut inner: W, width: u16, height: u16, fps: u16) -> io::Result<Self> {
+        let header = FileHeader::new(width, height, fps);
+        inner.write_all(&header.to_bytes())?;
+        Ok(Self {
+            inner,
+            header,
+            scratch: Vec::new(),
+            frame_offsets: None,
+            delta: None,
+            palette: None,
+            timestamps: None,
+            layers: Vec::new(),
+            metadata: None,
+        })
+    }
+
+    /// Records frame offsets and writes them as an index chunk on finish.
+    pub fn with_frame_index(mut self) -> Self {
+        self.frame_offsets = Some(Vec::new());
+        self
+    }
+
+    /// Stores frames as deltas against the previous frame, with a full
+    /// keyframe every `keyframe_interval` frames.
+    pub fn with_delta(mut self, keyframe_interval: u32) -> io::Result<Self> {
+        self.header.flags |= FLAG_DELTA;
+        self.write_header()?;
+        self.delta = Some(DeltaState {
+            keyframe_interval: keyframe_interval.max(1),
+            previous: Vec::new(),
+            encoded: Vec::new(),
+        });
+        Ok(self)
+    }
+
+    /// Writes rects in the compact varint encoding, see [`crate::varint`].
+    pub fn with_varint(mut self) -> io::Result<Self> {
+        self.header.flags |= FLAG_VARINT;
+        self.write_header()?;
+        Ok(self)
+    }
+
+    /// Marks frames as carrying `levels` intensity levels (2 to 256). Level
+    /// markers in the frames themselves are up to the caller.
+    pub fn with_levels(mut self, levels: u16) -> io::Result<Self> {
+        self.header.flags |= FLAG_LEVELS;
+        self.header.levels = levels.clamp(2, level::MAX_LEVELS);
+        self.write_header()?;
+        Ok(self)
+    }
+
+    /// Stores `palette` as a chunk on finish. Color markers in the frames
+    /// themselves are up to the caller.
+    pub fn with_palette(mut self, palette: Palette) -> io::Result<Self> {
+        self.header.flags |= FLAG_PALETTE;
+        self.write_header()?;
+        self.palette = Some(palette);
+        Ok(self)
+    }
+
+    /// Records the exact frame rate; the header's `fps` becomes its rounded
+    /// value.
+    pub fn with_frame_rate(mut self, rate: FrameRate) -> io::Result<Self> {
+        if rate.num == 0 || rate.den == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "frame rate must be positive",
+            ));
+        }
+        self.header.frame_rate = rate;
+        self.header.fps = rate.rounded();
+        self.write_header()?;
+        Ok(self)
+    }
+
+    /// Stores the presentation time of every frame, for variable frame rate
+    /// sources. Pass the times to [`BinWriter::write_frame_at`].
+    pub fn with_timestamps(mut self) -> io::Result<Self> {
+        self.header.flags |= FLAG_TIMESTAMPS;
+        self.write_header()?;
+        self.timestamps = Some(Vec::new());
+        Ok(self)
+    }
+
+    /// Stores `metadata` as a chunk on finish.
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Appends `layer` as a chunk on finish. Layers are drawn in the order
+    /// they are added.
+    pub fn add_layer(&mut self, layer: &Layer) -> io::Result<()> {
+        if layer.name.len() > u16::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "layer name too long",
+            ));
+        }
+        self.layers.push(layer.to_bytes());
+        Ok(())
+    }
+
+    pub fn header(&self) -> &FileHeader {
+        &self.header
+    }
+
+    /// Continues an interrupted write. `stream` is the rect stream already
+    /// in the file, up to the end of a frame, and `times` the times its
+    /// frames were written at; frames without one get their nominal time.
+    ///
+    /// Configure the writer as for the original write first. The next frame
+    /// is written right after `stream`.
+    pub fn resume(mut self, stream: &[u8], times: &[u32]) -> io::Result<Self> {
+        let (width, height) = (self.header.width, self.header.height);
+        let varint = self.header.flags & FLAG_VARINT != 0;
+        let index = if varint {
+            FrameIndex::scan_varint(stream)
+        } else {
+            FrameIndex::scan(stream)
+        };
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+
+        // Delta frames are encoded against the last frame written
+        if let (Some(state), Some(last)) = (&mut self.delta, index.len().checked_sub(1)) {
+            let mut decoder = if varint {
+                FrameDecoder::new_varint(VarintReader::new(stream, width, height), true)
+            } else {
+                FrameDecoder::new(FrameReader::new(stream, width, height), true)
+            };
+            decoder.seek(&index, last).map_err(invalid)?;
+            let rects = match decoder.next_frame() {
+                Some(rects) => rects.map_err(invalid)?,
+                None => return Err(invalid(FormatError::TruncatedFrame { frame: last })),
+            };
+            state.previous.clear();
+            state.previous.extend_from_slice(rects);
+        }
+
+        for frame in 0..index.len() {
+            if let Some(offsets) = &mut self.frame_offsets {
+                offsets.push(index.frame_range(frame).map_or(0, |r| r.start as u64));
+            }
+            if let Some(table) = &mut self.timestamps {
+                let nominal = self.header.frame_rate.time_of(frame);
+                let time_ms = times
+                    .get(frame)
+                    .copied()
+                    .unwrap_or((nominal * 1000.0).round() as u32);
+                let last = table.last().copied().unwrap_or(0);
+                table.push(time_ms.max(last));
+            }
+        }
+        self.header.frame_count = index.len() as u32;
+        self.header.data_len = stream.len() as u64;
+        self.inner.seek(SeekFrom::Start(
+            (self.header.data_start + stream.len()) as u64,
+        ))?;
+        Ok(self)
+    }
+
+    /// Flushes the frames written so far, e.g. before recording how far a
+    /// conversion got.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Appends one frame followed by its end-of-frame marker.
+    ///
+    /// In delta mode the frame is stored as a delta whenever that is smaller
+    /// and no keyframe is due.
+    pub fn write_frame(&mut self, rects: &[PixelRect]) -> io::Result<()> {
+        let nominal = self
+            .header
+            .frame_rate
+            .time_of(self.header.frame_count as usize);
+        self.write_frame_at(rects, (nominal * 1000.0).round() as u32)
+    }
+
+    /// Like [`BinWriter::write_frame`], shown `time_ms` after the start.
+    /// The time is only stored with [`BinWriter::with_timestamps`].
+    pub fn write_frame_at(&mut self, rects: &[PixelRect], time_ms: u32) -> io::Result<()> {
+        if let Some(times) = &mut self.timestamps {
+            // Keep the table monotonic even if the source isn't
+            let last = times.last().copied().unwrap_or(0);
+            times.push(time_ms.max(last));
+        }
+
+        let mut records = rects;
+        if let Some(state) = &mut self.delta {
+            let keyframe = self
+                .header
+                .frame_count
+                .is_multiple_of(state.keyframe_interval);
+            if !keyframe && delta::encode_delta(&state.previous, rects, &mut state.encoded) {
+                records = &state.encoded;
+            }
+        }
+
+        self.scratch.clear();
+        if self.header.flags & FLAG_VARINT != 0 {
+            varint::encode_frame(records, &mut self.scratch);
+        } else {
+            for rect in records.iter().chain([&PixelRect::EOS_MARKER]) {
+                self.scratch.extend_from_slice(&rect.to_le_bytes());
+            }
+        }
+        if let Some(state) = &mut self.delta {
+            state.previous.clear();
+            state.previous.extend_from_slice(rects);
+        }
+        self.inner.write_all(&self.scratch)?;
+
+        if let Some(offsets) = &mut self.frame_offsets {
+            offsets.push(self.header.data_len);
+        }
+        self.header.frame_count += 1;
+        self.header.data_len += self.scratch.len() as u64;
+        Ok(())
+    }
+
+    /// Writes the trailing chunks, patches the header and returns the
+    /// underlying writer.
+    pub fn finish(mut self) -> io::Result<(W, FileHeader)> {
+        self.header.flags |= FLAG_FINALIZED;
+
+        if let Some(palette) = self.palette.take() {
+            self.scratch.clear();
+            chunk::encode_chunk(&mut self.scratch, chunk::TAG_PALETTE, &palette.to_bytes());
+            self.inner.write_all(&self.scratch)?;
+        }
+
+        if let Some(metadata) = self.metadata.take() {
+            self.scratch.clear();
+            chunk::encode_chunk(&mut self.scratch, chunk::TAG_METADATA, &metadata.to_bytes());
+            self.inner.write_all(&self.scratch)?;
+        }
+
+        for layer in std::mem::take(&mut self.layers) {
+            self.scratch.clear();
+            chunk::encode_chunk(&mut self.scratch, chunk::TAG_LAYER, &layer);
+            self.inner.write_all(&self.scratch)?;
+        }
+
+        if let Some(times) = self.timestamps.take() {
+            self.scratch.clear();
+            chunk::encode_chunk(
+                &mut self.scratch,
+                chunk::TAG_TIMESTAMPS,
+                &timing::timestamps_to_bytes(&times),
+            );
+            self.inner.write_all(&self.scratch)?;
+        }
+
+        if let Some(offsets) = self.frame_offsets.take() {
+            let index = FrameIndex::new(offsets, self.header.data_len);
+            self.scratch.clear();
+            chunk::encode_chunk(&mut self.scratch, chunk::TAG_FRAME_INDEX, &index.to_bytes());
+            self.inner.write_all(&self.scratch)?;
+        }
+
+        self.write_header()?;
+        self.inner.flush()?;
+        Ok((self.inner, self.header))
+    }
+
+    /// Rewrites the header in place, keeping the current write position.
+    fn write_header(&mut self) -> io::Result<()> {
+        let end = self.inner.stream_position()?;
+        self.inner.seek(SeekFrom::Start(0))?;
+        self.inner.write_all(&self.header.to_bytes())?;
+        self.inner.seek(SeekFrom::Start(end))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Frames with a block that walks right and a counter of rects below it.
+    fn frames() -> Vec<Vec<PixelRect>> {
+        (0..20u16)
+            .map(|f| {
+                let mut rects = vec![PixelRect {
+                    x: f * 2,
+                    y: 0,
+                    w: 4,
+                    h: 4,
+                }];
+                rects.extend((0..f % 5).map(|i| PixelRect {
+                    x: i * 3,
+                    y: 10,
+                    w: 2,
+                    h: 2,
+                }));
+                rects
+            })
+            .collect()
+    }
+
+    fn writer(file: Vec<u8>, compact: bool) -> BinWriter<Cursor<Vec<u8>>> {
+        let mut writer = BinWriter::new(Cursor::new(file), 64, 16, 25)
+            .unwrap()
+            .with_frame_index()
+            .with_delta(6)
+            .unwrap()
+            .with_timestamps()
+            .unwrap();
+        if compact {
+            writer = writer.with_varint().unwrap();
+        }
+        writer
+    }
+
+    #[test]
+    fn resumed_writes_match_a_single_pass() {
+        let frames = frames();
+        let times: Vec<u32> = (0..20).map(|f| f * 37).collect();
+        for compact in [false, true] {
+            let mut full = writer(Vec::new(), compact);
+            for (rects, &t) in frames.iter().zip(&times) {
+                full.write_frame_at(rects, t).unwrap();
+            }
+            let expected = full.finish().unwrap().0.into_inner();
+
+            // Stopping anywhere, including mid keyframe interval, and
+            // carrying on from the partial file gives the same bytes
+            for stop in [0, 1, 6, 9, 19] {
+                let mut partial = writer(Vec::new(), compact);
+                for (rects, &t) in frames[..stop].iter().zip(&times) {
+                    partial.write_frame_at(rects, t).unwrap();
+                }
+                let header = *partial.header();
+                let file = partial.inner.into_inner();
+                let stream = file[header.data_range()].to_vec();
+
+                let mut resumed = writer(file, compact)
+                    .resume(&stream, &times[..stop])
+                    .unwrap();
+                assert_eq!(resumed.header().frame_count, stop as u32);
+                for (rects, &t) in frames[stop..].iter().zip(&times[stop..]) {
+                    resumed.write_frame_at(rects, t).unwrap();
+                }
+                let file = resumed.finish().unwrap().0.into_inner();
+                assert!(file == expected, "compact {compact}, stopped at {stop}");
+            }
+        }
+    }
+
+    #[test]
+    fn resume_rejects_a_damaged_stream() {
+        let mut partial = writer(Vec::new(), false);
+        for rects in &frames()[..3] {
+            partial.write_frame(rects).unwrap();
+        }
+        let header = *partial.header();
+        let file = partial.inner.into_inner();
+        let mut stream = file[header.data_range()].to_vec();
+        // The last frame is swapped for one reaching past the frame edge
+        let last = FrameIndex::scan(&stream).frame_range(2).unwrap().start;
+        stream.truncate(last);
+        for rect in [
+            PixelRect {
+                x: 60,
+                y: 0,
+                w: 8,
+                h: 2,
+            },
+            PixelRect::EOS_MARKER,
+        ] {
+            stream.extend_from_slice(&rect.to_le_bytes());
+        }
+        let err = writer(file, false).resume(&stream, &[]).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,117 @@
+//! Snowplow vs greedy rect merging on synthetic 1080p frames.
+//!
+//! Run with `cargo bench -p ps-factory --bench rect_merge`. Rect counts for
+//! each shape are printed before the timings.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ps_core::PixelRect;
+use ps_factory::converter::{extract_rects_greedy, extract_rects_optimized};
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+const THRESHOLD: u8 = 127;
+
+/// A gray frame with `inside(x, y)` drawn white.
+fn frame(inside: impl Fn(f64, f64) -> bool) -> Vec<u8> {
+    let mut buf = vec![0u8; (WIDTH * HEIGHT) as usize];
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            if inside(x as f64, y as f64) {
+                buf[(y * WIDTH + x) as usize] = 255;
+            }
+        }
+    }
+    buf
+}
+
+fn shapes() -> Vec<(&'static str, Vec<u8>)> {
+    let (cx, cy) = (WIDTH as f64 / 2.0, HEIGHT as f64 / 2.0);
+    vec![
+        // Curved edges, where Snowplow is already close to optimal
+        (
+            "disc",
+            frame(|x, y| (x - cx).powi(2) + (y - cy).powi(2) < 450.0f64.powi(2)),
+        ),
+        // Organic silhouettes
+        (
+            "blobs",
+            frame(|x, y| (x / 97.0).sin() + (y / 61.0).cos() + (x / 41.0 + y / 53.0).sin() > 0.8),
+        ),
+        // Glyph-like strokes: stems get cut wherever a bar or diagonal joins
+        (
+            "text",
+            frame(|x, y| {
+                let (gx, gy) = (x % 60.0, y % 90.0);
+                (gx < 8.0 && gy < 70.0)
+                    || (gy < 8.0 && gx < 40.0)
+                    || ((30.0..38.0).contains(&gy) && gx < 30.0)
+                    || ((gx - gy * 0.5 - 10.0).abs() < 4.0 && gy < 70.0)
+            }),
+        ),
+        // Vertical bars joined by rungs
+        (
+            "ladder",
+            frame(|x, y| {
+                let gx = x % 200.0;
+                gx < 20.0 || (100.0..120.0).contains(&gx) || (y % 40.0 < 6.0 && gx < 120.0)
+            }),
+        ),
+        // Noisy edges, where the greedy cover loses and Snowplow is kept
+        (
+            "jittery_edges",
+            frame(|x, y| {
+                let left = 400.0 + (y * 7919.0) % 13.0;
+                let right = 1500.0 - (y * 104729.0) % 11.0;
+                x > left && x < right && (100.0..980.0).contains(&y)
+            }),
+        ),
+    ]
+}
+
+/// Paints `rects`, failing on overlap, to compare coverage.
+fn paint(rects: &[PixelRect]) -> Vec<u8> {
+    let mut buf = vec![0u8; (WIDTH * HEIGHT) as usize];
+    for r in rects {
+        for y in r.y..r.y + r.h {
+            let row = y as usize * WIDTH as usize;
+            for px in &mut buf[row + r.x as usize..row + (r.x + r.w) as usize] {
+                assert_eq!(*px, 0, "overlapping rects");
+                *px = 255;
+            }
+        }
+    }
+    buf
+}
+
+fn rect_merge(c: &mut Criterion) {
+    let mut indices = vec![-1isize; WIDTH as usize];
+    for (name, buf) in shapes() {
+        let snowplow = extract_rects_optimized(&buf, WIDTH, HEIGHT, THRESHOLD, &mut indices);
+        let greedy = extract_rects_greedy(&buf, WIDTH, HEIGHT, THRESHOLD, &mut indices);
+        assert!(
+            paint(&greedy) == paint(&snowplow),
+            "{}: coverage differs",
+            name
+        );
+        println!(
+            "{:<16} snowplow {:>6} rects, greedy {:>6} rects",
+            name,
+            snowplow.len(),
+            greedy.len()
+        );
+
+        let mut group = c.benchmark_group(name);
+        group.bench_function("snowplow", |b| {
+            b.iter(|| {
+                extract_rects_optimized(black_box(&buf), WIDTH, HEIGHT, THRESHOLD, &mut indices)
+            })
+        });
+        group.bench_function("greedy", |b| {
+            b.iter(|| extract_rects_greedy(black_box(&buf), WIDTH, HEIGHT, THRESHOLD, &mut indices))
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, rect_merge);
+criterion_main!(benches);
@@ -1,11 +1,17 @@
 use super::{
-    core::build_single_target,
+    core::{build_multi_target, build_single_target},
     types::{BuildStatus, BuildTarget},
+    utils::group_by_project,
 };
 use anyhow::Result;
 use std::{env, fs, sync::mpsc::Sender, thread};
 
-pub fn run_async(targets: Vec<BuildTarget>, sender: Sender<BuildStatus>) -> Result<()> {
+pub fn run_async(
+    targets: Vec<BuildTarget>,
+    compress: bool,
+    multi: bool,
+    sender: Sender<BuildStatus>,
+) -> Result<()> {
     let current_exe = env::current_exe()?;
     let exe_dir = current_exe.parent().unwrap();
     let template_path = exe_dir.join("ps-runner.exe");
@@ -21,13 +27,27 @@ pub fn run_async(targets: Vec<BuildTarget>, sender: Sender<BuildStatus>) -> Resu
     thread::spawn(move || {
         let _ = sender.send(BuildStatus::Starting);
 
-        for target in targets {
-            let name = format!("{}_{}", target.project, target.resolution);
-            let _ = sender.send(BuildStatus::Building(name));
+        // One exe per target, or per project when packing resolutions together
+        let groups = if multi {
+            group_by_project(targets)
+        } else {
+            targets.into_iter().map(|t| vec![t]).collect()
+        };
 
-            match build_single_target(&target, &template_path, &dist_dir) {
-                Ok(path) => {
-                    let _ = sender.send(BuildStatus::Finished(path));
+        for group in groups {
+            let result = if multi {
+                let _ = sender.send(BuildStatus::Building(format!("{}_multi", group[0].project)));
+                build_multi_target(&group, &template_path, &dist_dir, compress)
+            } else {
+                let target = &group[0];
+                let name = format!("{}_{}", target.project, target.resolution);
+                let _ = sender.send(BuildStatus::Building(name));
+                build_single_target(target, &template_path, &dist_dir, compress)
+            };
+
+            match result {
+                Ok(output) => {
+                    let _ = sender.send(BuildStatus::Finished(output));
                 }
                 Err(e) => {
                     let _ = sender.send(BuildStatus::Error(e.to_string()));
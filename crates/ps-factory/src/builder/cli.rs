@@ -1,11 +1,12 @@
 use super::{
-    core::build_single_target,
+    core::{build_multi_target, build_single_target},
     types::{BuildArgs, BuildTarget},
-    utils::get_available_builds,
+    utils::{format_size, get_available_builds, group_by_project},
 };
 use anyhow::{bail, Context, Result};
 use dialoguer::{theme::ColorfulTheme, MultiSelect};
 use indicatif::{ProgressBar, ProgressStyle};
+use ps_core::Resolution;
 use std::{env, fs};
 
 pub fn run_cli(args: BuildArgs) -> Result<()> {
@@ -33,10 +34,15 @@ pub fn run_cli(args: BuildArgs) -> Result<()> {
         if args.build_all || args.project_name.is_some() || args.resolutions.is_some() {
             // Flag Mode
             println!("Filtering targets based on flags...");
-            let req_res: Option<Vec<String>> = args
+            let req_res: Option<Vec<Resolution>> = args
                 .resolutions
                 .as_ref()
-                .map(|s| s.split(',').map(|r| r.trim().to_string()).collect());
+                .map(|s| {
+                    s.split(',')
+                        .map(|r| r.parse().map_err(anyhow::Error::msg))
+                        .collect::<Result<_>>()
+                })
+                .transpose()?;
 
             all_targets
                 .into_iter()
@@ -86,8 +92,15 @@ pub fn run_cli(args: BuildArgs) -> Result<()> {
     println!("📂 Output directory: {:?}", dist_dir);
     println!(); // Spacing
 
+    // One exe per target, or per project when packing resolutions together
+    let groups = if args.multi {
+        group_by_project(selected_targets)
+    } else {
+        selected_targets.into_iter().map(|t| vec![t]).collect()
+    };
+
     // SETUP PROGRESS BAR
-    let pb = ProgressBar::new(selected_targets.len() as u64);
+    let pb = ProgressBar::new(groups.len() as u64);
     pb.set_style(
         ProgressStyle::with_template(
             "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}",
@@ -96,16 +109,24 @@ pub fn run_cli(args: BuildArgs) -> Result<()> {
         .progress_chars("#>-"),
     );
 
-    for target in selected_targets {
-        let display_name = format!("{} [{}]", target.project, target.resolution);
+    for group in groups {
+        let resolutions: Vec<String> = group.iter().map(|t| t.resolution.to_string()).collect();
+        let display_name = format!("{} [{}]", group[0].project, resolutions.join(", "));
         pb.set_message(format!("Building {}...", display_name));
 
-        match build_single_target(&target, &template_path, &dist_dir) {
-            Ok(path) => {
+        let result = if args.multi {
+            build_multi_target(&group, &template_path, &dist_dir, args.compress)
+        } else {
+            build_single_target(&group[0], &template_path, &dist_dir, args.compress)
+        };
+        match result {
+            Ok(output) => {
                 // Print above the bar
                 pb.println(format!(
-                    "✅ Created: {:?}",
-                    path.file_name().unwrap_or_default()
+                    "✅ Created: {:?} (video {} -> {})",
+                    output.path.file_name().unwrap_or_default(),
+                    format_size(output.video_len),
+                    format_size(output.payload_len)
                 ));
             }
             Err(e) => {
@@ -1,64 +1,177 @@
-use super::types::{BuildTarget, PayloadFooter};
+use super::types::{BuildOutput, BuildTarget};
 use anyhow::{bail, Context, Result};
+use ps_core::{compress, metadata, payload, FileHeader, Metadata, PayloadFooter};
 use std::{
     fs::{self, File},
     io::Write,
-    mem,
-    path::{Path, PathBuf},
-    slice,
-}; // Import from types module
+    path::Path,
+};
 
 /// Reads the template, appends data, and writes the final standalone EXE.
+///
+/// With `compress` the video is stored LZ4-compressed and inflated by the
+/// runner block by block while it plays.
 pub fn build_single_target(
     target: &BuildTarget,
     template_path: &Path,
     output_dir: &Path,
-) -> Result<PathBuf> {
+    compress: bool,
+) -> Result<BuildOutput> {
+    let exe_name = format!("{}_{}.exe", target.project, target.resolution);
+    build_exe(
+        &exe_name,
+        std::slice::from_ref(target),
+        template_path,
+        output_dir,
+        compress,
+    )
+}
+
+/// Packs several resolutions of one project into `<project>_multi.exe`,
+/// sharing a single audio track. The runner plays the stream closest to
+/// the screen size.
+pub fn build_multi_target(
+    targets: &[BuildTarget],
+    template_path: &Path,
+    output_dir: &Path,
+    compress: bool,
+) -> Result<BuildOutput> {
+    let Some(first) = targets.first() else {
+        bail!("No resolutions to pack");
+    };
+    if targets.iter().any(|t| t.project != first.project) {
+        bail!("A multi-resolution exe can only hold one project");
+    }
+    let exe_name = format!("{}_multi.exe", first.project);
+    build_exe(&exe_name, targets, template_path, output_dir, compress)
+}
+
+/// Video data of one target, ready to be appended.
+struct PackedVideo {
+    data: Vec<u8>,
+    codec: u16,
+    raw_len: u64,
+    width: u16,
+    height: u16,
+}
+
+fn build_exe(
+    exe_name: &str,
+    targets: &[BuildTarget],
+    template_path: &Path,
+    output_dir: &Path,
+    compress: bool,
+) -> Result<BuildOutput> {
     // 1. Validation
     if !template_path.exists() {
         bail!("Template not found at {:?}", template_path);
     }
 
-    let exe_name = format!("{}_{}.exe", target.project, target.resolution);
-    let output_path = output_dir.join(&exe_name);
+    let output_path = output_dir.join(exe_name);
 
     // 2. Load Data
     let template_bytes = fs::read(template_path).context("Failed to read template exe")?;
-    let video_data = fs::read(&target.bin_path).context("Failed to read video bin")?;
-    let audio_data = fs::read(&target.audio_path).context("Failed to read audio ogg")?;
 
-    // 3. Calculate Offsets
-    let template_len = template_bytes.len() as u64;
-    let video_len = video_data.len() as u64;
-    let audio_len = audio_data.len() as u64;
-
-    let video_offset = template_len;
-    let audio_offset = template_len + video_len;
-
-    let footer = PayloadFooter {
-        video_offset,
-        video_len,
-        audio_offset,
-        audio_len,
-        width: target.width,
-        height: target.height,
-        magic: *b"PS_PATCH",
+    let mut videos = Vec::with_capacity(targets.len());
+    let mut meta = Metadata::default();
+    for (i, target) in targets.iter().enumerate() {
+        let video_data = fs::read(&target.bin_path).context("Failed to read video bin")?;
+
+        let header = FileHeader::parse(&video_data).context("Invalid video bin")?;
+        if !header.is_finalized() {
+            bail!("Video bin is incomplete (conversion was interrupted)");
+        }
+
+        // Carry the converter's metadata over so it can be read without
+        // touching the (possibly compressed) video
+        if i == 0 {
+            meta = Metadata::load(&video_data, &header).context("Invalid video metadata")?;
+        }
+
+        // Prefer the dimensions recorded by the converter; legacy bins only have the filename.
+        let (width, height) = if header.is_legacy() {
+            (target.resolution.width, target.resolution.height)
+        } else {
+            (header.width, header.height)
+        };
+
+        let raw_len = video_data.len() as u64;
+        let (data, codec) = if compress {
+            let packed = compress::compress(&video_data).context("Failed to compress video")?;
+            (packed, payload::CODEC_LZ4)
+        } else {
+            (video_data, payload::CODEC_RAW)
+        };
+        videos.push(PackedVideo {
+            data,
+            codec,
+            raw_len,
+            width,
+            height,
+        });
+    }
+
+    // One soundtrack for every resolution
+    let audio = match targets.iter().find_map(|t| t.audio_path.as_ref()) {
+        Some(path) => {
+            let codec = path
+                .extension()
+                .and_then(|e| payload::audio_codec_for_extension(&e.to_string_lossy()))
+                .with_context(|| format!("Unsupported audio format: {:?}", path))?;
+            Some((fs::read(path).context("Failed to read audio")?, codec))
+        }
+        None => None,
     };
 
+    meta.set("built", metadata::now_utc());
+    meta.set(
+        "builder_version",
+        concat!("ps-factory ", env!("CARGO_PKG_VERSION")),
+    );
+    if targets.len() > 1 {
+        let resolutions: Vec<String> = targets.iter().map(|t| t.resolution.to_string()).collect();
+        meta.set("resolutions", resolutions.join(", "));
+    }
+    let meta_data = meta.to_bytes();
+
+    // 3. Calculate Offsets
+    let first = &videos[0];
+    let mut footer = PayloadFooter::new(first.width, first.height);
+    let mut offset = template_bytes.len() as u64;
+    for video in &videos {
+        let len = video.data.len() as u64;
+        footer.push_video(video.codec, video.width, video.height, offset, len);
+        offset += len;
+    }
+    if let Some((audio_data, audio_codec)) = &audio {
+        let len = audio_data.len() as u64;
+        footer.push_chunk(payload::KIND_AUDIO, *audio_codec, offset, len);
+        offset += len;
+    }
+    footer.push_chunk(
+        payload::KIND_METADATA,
+        payload::CODEC_RAW,
+        offset,
+        meta_data.len() as u64,
+    );
+
     // 4. Write Output File
     let mut file = File::create(&output_path).context("Failed to create output file")?;
 
     file.write_all(&template_bytes)?;
-    file.write_all(&video_data)?;
-    file.write_all(&audio_data)?;
-
-    let footer_bytes = unsafe {
-        slice::from_raw_parts(
-            &footer as *const PayloadFooter as *const u8,
-            mem::size_of::<PayloadFooter>(),
-        )
-    };
-    file.write_all(footer_bytes)?;
+    for video in &videos {
+        file.write_all(&video.data)?;
+    }
+    if let Some((audio_data, _)) = &audio {
+        file.write_all(audio_data)?;
+    }
+    file.write_all(&meta_data)?;
+
+    file.write_all(&footer.to_bytes())?;
 
-    Ok(output_path)
+    Ok(BuildOutput {
+        path: output_path,
+        video_len: videos.iter().map(|v| v.raw_len).sum(),
+        payload_len: videos.iter().map(|v| v.data.len() as u64).sum(),
+    })
 }
@@ -1,34 +1,32 @@
+use ps_core::Resolution;
 use std::path::PathBuf;
 
-/// The "Magic Footer" that the Runner looks for at the end of the file.
-/// MUST match the struct definition in `ps-runner`.
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct PayloadFooter {
-    pub video_offset: u64,
-    pub video_len: u64,
-    pub audio_offset: u64,
-    pub audio_len: u64,
-    pub width: u16,
-    pub height: u16,
-    pub magic: [u8; 8],
-}
-
 #[derive(Debug, Clone)]
 pub struct BuildTarget {
     pub project: String,
-    pub resolution: String,
-    pub width: u16,
-    pub height: u16,
+    /// Taken from the file name; only used as the frame size for legacy
+    /// bins that don't record their own.
+    pub resolution: Resolution,
     pub bin_path: PathBuf,
-    pub audio_path: PathBuf,
+    /// Soundtrack, if the project has one. Silent overlays are built without.
+    pub audio_path: Option<PathBuf>,
+}
+
+/// Result of a successful build.
+#[derive(Debug, Clone)]
+pub struct BuildOutput {
+    pub path: PathBuf,
+    /// Size of the source .bin files.
+    pub video_len: u64,
+    /// Size of the video streams as embedded in the exe.
+    pub payload_len: u64,
 }
 
 #[derive(Debug, Clone)]
 pub enum BuildStatus {
     Starting,
-    Building(String),  // "Building my_project_1080p.exe..."
-    Finished(PathBuf), // Returns path to the new .exe
+    Building(String),      // "Building my_project_1080p.exe..."
+    Finished(BuildOutput), // Path to the new .exe and payload sizes
     Error(String),
 }
 
@@ -36,4 +34,7 @@ pub struct BuildArgs {
     pub project_name: Option<String>,
     pub resolutions: Option<String>,
     pub build_all: bool,
+    pub compress: bool,
+    /// Pack each project's resolutions into a single exe.
+    pub multi: bool,
 }
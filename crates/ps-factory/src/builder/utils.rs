@@ -1,54 +1,71 @@
 use super::types::BuildTarget;
 use anyhow::Result;
+use ps_core::{payload::AUDIO_EXTENSIONS, Resolution};
 use std::{
     fs,
     path::{Path, PathBuf},
 };
 
 // NEW: Public helper to detect resolution from a filename
-pub fn detect_resolution(filename: &str) -> (String, u16, u16) {
-    let resolutions = vec![
-        ("720p", 1280, 720),
-        ("1080p", 1920, 1080),
-        ("1440p", 2560, 1440),
-        ("2160p", 3840, 2160),
-    ];
+pub fn detect_resolution(filename: &str) -> Resolution {
+    filename
+        .rsplit('_')
+        .find_map(|tag| tag.parse().ok())
+        // Default fallback
+        .unwrap_or(Resolution::FULL_HD)
+}
 
-    for (res_name, w, h) in resolutions {
-        if filename.contains(res_name) {
-            return (res_name.to_string(), w, h);
-        }
+/// Formats a byte count for build reports, e.g. "12.4 MB".
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
     }
-
-    // Default fallback
-    ("1080p".to_string(), 1920, 1080)
 }
 
 // NEW: Public helper to find audio file
-pub fn detect_audio_path(bin_path: &Path) -> PathBuf {
+pub fn detect_audio_path(bin_path: &Path) -> Option<PathBuf> {
     let filename = bin_path.file_stem().unwrap().to_string_lossy();
     let parent = bin_path.parent().unwrap();
 
     // 1. Try exact match (project.ogg)
-    let exact = parent.join(format!("{}.ogg", filename));
-    if exact.exists() {
-        return exact;
+    if let Some(exact) = find_audio(parent, &filename) {
+        return Some(exact);
     }
 
-    // 2. Try stripping resolution tags (project_1080p.bin -> project.ogg)
-    let base_name = filename
-        .replace("_720p", "")
-        .replace("_1080p", "")
-        .replace("_1440p", "")
-        .replace("_2160p", "");
+    // 2. Try stripping the resolution tag (project_1080p.bin -> project.ogg)
+    let (base_name, _) = Resolution::split_suffix(&filename)?;
 
-    let stripped = parent.join(format!("{}.ogg", base_name));
-    if stripped.exists() {
-        return stripped;
-    }
+    // No soundtrack: the overlay is built silent
+    find_audio(parent, base_name)
+}
 
-    // Return empty if not found (builder will error later, or handle gracefully)
-    PathBuf::new()
+/// `<dir>/<stem>.<ext>` for the first supported audio format that exists.
+pub fn find_audio(dir: &Path, stem: &str) -> Option<PathBuf> {
+    AUDIO_EXTENSIONS
+        .iter()
+        .map(|(ext, _)| dir.join(format!("{}.{}", stem, ext)))
+        .find(|path| path.exists())
+}
+
+/// Splits targets into one group per project, keeping their order.
+pub fn group_by_project(targets: Vec<BuildTarget>) -> Vec<Vec<BuildTarget>> {
+    let mut groups: Vec<Vec<BuildTarget>> = Vec::new();
+    for target in targets {
+        match groups.iter_mut().find(|g| g[0].project == target.project) {
+            Some(group) => group.push(target),
+            None => groups.push(vec![target]),
+        }
+    }
+    groups
 }
 
 pub fn get_available_builds(assets_dir: &Path) -> Result<Vec<BuildTarget>> {
@@ -57,40 +74,39 @@ pub fn get_available_builds(assets_dir: &Path) -> Result<Vec<BuildTarget>> {
     }
     let mut targets = Vec::new();
 
-    // We still iterate resolutions here to scan for SPECIFIC files
-    // distinct from the detection logic above
-    let resolutions = vec![
-        ("720p", 1280, 720),
-        ("1080p", 1920, 1080),
-        ("1440p", 2560, 1440),
-        ("2160p", 3840, 2160),
-    ];
-
     for entry in fs::read_dir(assets_dir)? {
         let entry = entry?;
         if entry.path().is_dir() {
             let project_name = entry.file_name().to_string_lossy().to_string();
             let project_dir = entry.path();
-            let audio_path = project_dir.join(format!("{}.ogg", project_name));
+            let audio_path = match find_audio(&project_dir, &project_name) {
+                Some(path) => Some(fs::canonicalize(path)?),
+                None => None,
+            };
 
-            if !audio_path.exists() {
-                continue;
+            // Any <project>_<resolution>.bin, smallest first
+            let mut bins = Vec::new();
+            for file in fs::read_dir(&project_dir)? {
+                let bin_path = file?.path();
+                if bin_path.extension().is_none_or(|ext| ext != "bin") {
+                    continue;
+                }
+                let stem = bin_path.file_stem().unwrap_or_default().to_string_lossy();
+                if let Some((name, resolution)) = Resolution::split_suffix(&stem) {
+                    if name == project_name {
+                        bins.push((resolution, bin_path.clone()));
+                    }
+                }
             }
+            bins.sort_by_key(|(r, _)| (r.width as u32 * r.height as u32, r.width));
 
-            for (res_name, w, h) in &resolutions {
-                let bin_name = format!("{}_{}.bin", project_name, res_name);
-                let bin_path = project_dir.join(&bin_name);
-
-                if bin_path.exists() {
-                    targets.push(BuildTarget {
-                        project: project_name.clone(),
-                        resolution: res_name.to_string(),
-                        width: *w,
-                        height: *h,
-                        bin_path: fs::canonicalize(&bin_path)?,
-                        audio_path: fs::canonicalize(&audio_path)?,
-                    });
-                }
+            for (resolution, bin_path) in bins {
+                targets.push(BuildTarget {
+                    project: project_name.clone(),
+                    resolution,
+                    bin_path: fs::canonicalize(&bin_path)?,
+                    audio_path: audio_path.clone(),
+                });
             }
         }
     }
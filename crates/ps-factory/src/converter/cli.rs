@@ -1,12 +1,26 @@
 use super::core::process_conversion;
+use super::source::{SourceKind, DEFAULT_SEQUENCE_RATE};
 use super::types::*;
-use super::utils::detect_fps;
+use super::utils::{
+    detect_frame_rate, looks_variable, parse_layer_spec, stats_path, MAX_PALETTE_COLORS,
+};
 use anyhow::{bail, Context, Result};
 use dialoguer::{theme::ColorfulTheme, MultiSelect, Select};
 use indicatif::{ProgressBar, ProgressStyle};
+use ps_core::{level, FrameRate, Resolution};
 use std::{env, fs};
 
 pub fn run_cli(args: ConvertArgs) -> Result<()> {
+    if !(2..=level::MAX_LEVELS).contains(&args.levels) {
+        bail!("--levels must be between 2 and {}", level::MAX_LEVELS);
+    }
+    if args
+        .colors
+        .is_some_and(|c| !(1..=MAX_PALETTE_COLORS).contains(&c))
+    {
+        bail!("--colors must be between 1 and {}", MAX_PALETTE_COLORS);
+    }
+
     // 1. Setup Environment
     let current_exe = env::current_exe().context("Failed to get exe path")?;
     let exe_dir = current_exe
@@ -40,10 +54,13 @@ pub fn run_cli(args: ConvertArgs) -> Result<()> {
     };
 
     // 3. Select Resolutions
-    let resolution_names: Vec<String> = match args.resolutions {
-        Some(s) => s.split(',').map(|x| x.trim().to_string()).collect(),
+    let resolutions: Vec<Resolution> = match args.resolutions {
+        Some(s) => s
+            .split(',')
+            .map(|x| x.parse().map_err(anyhow::Error::msg))
+            .collect::<Result<_>>()?,
         None => {
-            let options = vec!["720p", "1080p", "1440p", "2160p"];
+            let options = Resolution::PRESETS;
             let defaults = vec![true, true, false, false];
             let selections = MultiSelect::with_theme(&ColorfulTheme::default())
                 .with_prompt("Select Output Resolutions")
@@ -53,42 +70,75 @@ pub fn run_cli(args: ConvertArgs) -> Result<()> {
             if selections.is_empty() {
                 bail!("Select at least one resolution.");
             }
-            selections.iter().map(|&i| options[i].to_string()).collect()
+            selections.iter().map(|&i| options[i]).collect()
         }
     };
 
-    // 4. Locate Source Video
+    // 4. Locate Source Video (or a `frames/` folder of numbered PNGs)
     let project_dir = assets_root.join(&project_name);
-    let vid_path = ["mkv", "mp4", "avi", "mov", "webm"]
+    let vid_path = ["mkv", "mp4", "avi", "mov", "webm", "y4m"]
         .iter()
         .map(|ext| project_dir.join(format!("{}.{}", project_name, ext)))
+        .chain([project_dir.join("frames")])
         .find(|p| p.exists())
         .context("No video found in project folder")?;
 
-    let fps = detect_fps(&vid_path).unwrap_or(30);
-    println!("Detected: {} FPS", fps);
+    let frame_rate = match args.fps {
+        Some(rate) => rate.parse::<FrameRate>().map_err(anyhow::Error::msg)?,
+        None if SourceKind::detect(&vid_path) == SourceKind::PngSequence => {
+            println!("Image sequence: assuming {} FPS", DEFAULT_SEQUENCE_RATE);
+            DEFAULT_SEQUENCE_RATE
+        }
+        None => detect_frame_rate(&vid_path)
+            .context("Could not detect the frame rate; pass it with --fps")?,
+    };
+    println!(
+        "Frame rate: {} FPS ({}/{})",
+        frame_rate, frame_rate.num, frame_rate.den
+    );
+    if !args.variable_rate && looks_variable(&vid_path) {
+        println!("Warning: source looks variable frame rate; consider --vfr");
+    }
+
+    let layers = args
+        .layers
+        .iter()
+        .map(|spec| parse_layer_spec(spec, &project_dir))
+        .collect::<Result<Vec<_>>>()?;
+    for layer in &layers {
+        println!("Layer '{}': {}", layer.name, layer.input_path.display());
+    }
+
+    let keyframe_interval = args.delta.then(|| {
+        args.keyframe_interval
+            .unwrap_or(frame_rate.rounded() as u32 * 2)
+    });
 
     // 5. Process Loop
-    for res_name in resolution_names {
-        let width = match res_name.as_str() {
-            "720p" => 1280,
-            "1080p" => 1920,
-            "1440p" => 2560,
-            "2160p" => 3840,
-            _ => continue,
-        };
-        let height = width * 9 / 16;
-        let out_path = project_dir.join(format!("{}_{}.bin", project_name, res_name));
+    for resolution in resolutions {
+        let (width, height) = (resolution.width as u32, resolution.height as u32);
+        let out_path = project_dir.join(format!("{}_{}.bin", project_name, resolution));
 
-        println!("\n--- Processing {} ({}x{}) ---", res_name, width, height);
+        println!("\n--- Processing {} ({}x{}) ---", resolution, width, height);
 
         let job = ConvertJob {
             input_path: vid_path.clone(),
             output_path: out_path,
             width,
             height,
-            fps,
+            frame_rate,
+            variable_rate: args.variable_rate,
             use_gpu: args.use_gpu,
+            keyframe_interval,
+            compact: args.compact,
+            levels: args.levels,
+            colors: args.colors,
+            invert: args.invert,
+            merge: args.merge,
+            simplify: args.simplify,
+            filters: args.filters.clone(),
+            layers: layers.clone(),
+            resume: args.resume,
         };
 
         // SETUP CLI PROGRESS BAR
@@ -98,10 +148,11 @@ pub fn run_cli(args: ConvertArgs) -> Result<()> {
         ).unwrap().progress_chars("#>-"));
 
         // RUN LOGIC with CLI Callback
+        let stats_file = stats_path(&job.output_path);
         let pb_clone = pb.clone();
         process_conversion(job, move |status| match status {
             ConverterStatus::Starting => pb_clone.set_message("Starting..."),
-            ConverterStatus::Analyzing(_) => pb_clone.set_message("Analyzing..."),
+            ConverterStatus::Analyzing(message) => pb_clone.set_message(message),
             ConverterStatus::Processing {
                 current_frame,
                 total_frames,
@@ -110,6 +161,18 @@ pub fn run_cli(args: ConvertArgs) -> Result<()> {
                 pb_clone.set_length(total_frames);
                 pb_clone.set_position(current_frame);
             }
+            ConverterStatus::Resumed(frame) => {
+                pb_clone.println(format!("Resuming from frame {}", frame))
+            }
+            ConverterStatus::Simplified(report) => {
+                pb_clone.println(format!("Simplified: {}", report))
+            }
+            ConverterStatus::Stats(stats) => pb_clone.println(format!(
+                "Stats: {}\n  Details: {}",
+                stats,
+                stats_file.display()
+            )),
+            ConverterStatus::Warning(message) => pb_clone.println(format!("Warning: {}", message)),
             ConverterStatus::Finished => pb_clone.finish_with_message("Done!"),
             ConverterStatus::Error(e) => pb_clone.abandon_with_message(format!("Error: {}", e)),
         })?;
@@ -1,18 +1,31 @@
+use super::source::{open_source, PixelFormat, SourceKind};
 use super::types::*;
-use super::utils::{extract_rects_optimized, get_frame_count};
-use anyhow::{Context, Result};
+use super::utils::{
+    checkpoint_path, chroma_key_mask, detect_frame_rate, extract_level_rects,
+    extract_palette_rects, extract_rects_greedy, extract_rects_optimized, ffmpeg_only_filters,
+    filter_chain, generate_palette, get_frame_count, job_fingerprint, load_checkpoint, measure_frame,
+    otsu_threshold, palette_lut, probe_timestamps, read_sidecar, save_checkpoint, scan_frame_stats,
+    simplify_rects, stats_path, summarize_stats, write_stats,
+};
+use anyhow::{bail, Context, Result};
 use crossbeam_channel::{bounded, Receiver, Sender};
-use ps_core::PixelRect;
+use ps_core::{file_header::HEADER_SIZE, layer::LAYER_LOOP, metadata, BinWriter, Layer, PixelRect};
 use rayon::iter::{ParallelBridge, ParallelIterator};
 use std::{
     collections::HashMap,
-    fs::File,
-    io::{BufWriter, Read, Write},
-    process::{Command, Stdio},
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     thread,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
+/// How often progress is checkpointed, at most.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
 /// The high-performance engine.
 /// Accepts a generic `callback` to report progress.
 pub fn process_conversion<F>(job: ConvertJob, callback: F) -> Result<()>
@@ -21,39 +34,151 @@ where
 {
     callback(ConverterStatus::Starting);
 
+    // Layers go first, each into its own temporary .bin
+    let mut layers = Vec::with_capacity(job.layers.len());
+    for (i, source) in job.layers.iter().enumerate() {
+        callback(ConverterStatus::Analyzing(format!(
+            "Converting layer '{}'...",
+            source.name
+        )));
+        // Image sequences have no rate of their own and follow the video
+        let frame_rate = detect_frame_rate(&source.input_path)
+            .or_else(|| {
+                (SourceKind::detect(&source.input_path) == SourceKind::PngSequence)
+                    .then_some(job.frame_rate)
+            })
+            .with_context(|| {
+                format!("Could not detect the frame rate of layer '{}'", source.name)
+            })?;
+        let layer_job = ConvertJob {
+            input_path: source.input_path.clone(),
+            output_path: job.output_path.with_extension(format!("layer{}.bin", i)),
+            frame_rate,
+            variable_rate: false,
+            layers: Vec::new(),
+            resume: false,
+            ..job.clone()
+        };
+        // Layer progress goes into messages, leaving the progress of the
+        // main video to the main video
+        let cb = callback.clone();
+        let name = source.name.clone();
+        convert_stream(&layer_job, Vec::new(), false, move |status| match status {
+            ConverterStatus::Processing {
+                current_frame,
+                total_frames,
+                ..
+            } => cb(ConverterStatus::Analyzing(match total_frames {
+                0 => format!("Converting layer '{}': frame {}", name, current_frame),
+                total => format!(
+                    "Converting layer '{}': frame {}/{}",
+                    name, current_frame, total
+                ),
+            })),
+            ConverterStatus::Simplified(report) => cb(ConverterStatus::Analyzing(format!(
+                "Layer '{}' simplified: {}",
+                name, report
+            ))),
+            ConverterStatus::Stats(_) | ConverterStatus::Finished => {}
+            status => cb(status),
+        })?;
+        let bin = fs::read(&layer_job.output_path)?;
+        let _ = fs::remove_file(&layer_job.output_path);
+        let _ = fs::remove_file(stats_path(&layer_job.output_path));
+        layers.push((source.clone(), bin));
+    }
+
+    convert_stream(&job, layers, true, callback)
+}
+
+/// Converts one source video, embedding the already converted `layers`.
+/// With `checkpoints`, progress is saved so the job can be resumed.
+fn convert_stream<F>(
+    job: &ConvertJob,
+    layers: Vec<(LayerSource, Vec<u8>)>,
+    checkpoints: bool,
+    callback: F,
+) -> Result<()>
+where
+    F: Fn(ConverterStatus) + Send + Clone + 'static,
+{
     // 1. Analyze Video
     callback(ConverterStatus::Analyzing("Detecting Metadata...".into()));
-    let total_frames = get_frame_count(&job.input_path).unwrap_or(0);
+    let kind = SourceKind::detect(&job.input_path);
+    if kind.is_builtin() && (job.variable_rate || job.colors.is_some()) {
+        bail!("Variable frame rate and color palettes need a video source read through ffmpeg");
+    }
+    if job.invert && job.colors.is_some() {
+        bail!("Inverting works on gray frames and can't be combined with a color palette");
+    }
+    if job.filters.chroma_key.is_some() && job.colors.is_some() {
+        bail!("Chroma keying produces a mask and can't be combined with a color palette");
+    }
+    let timestamps = if job.variable_rate {
+        callback(ConverterStatus::Analyzing("Reading Timestamps...".into()));
+        Some(probe_timestamps(&job.input_path).context("Failed to read frame timestamps")?)
+    } else {
+        None
+    };
 
-    // 2. FFmpeg Setup
-    let filters = format!(
-        "scale={w}:{h},format=gray,gblur=sigma=1.0:steps=1,eq=contrast=1000:saturation=0",
-        w = job.width,
-        h = job.height
-    );
+    let palette = match job.colors {
+        Some(size) => {
+            callback(ConverterStatus::Analyzing("Building Palette...".into()));
+            Some(
+                generate_palette(&job.input_path, job.width, job.height, size)
+                    .context("Failed to build a color palette")?,
+            )
+        }
+        None => None,
+    };
 
-    let mut cmd = Command::new("ffmpeg");
-    if job.use_gpu {
-        cmd.arg("-hwaccel").arg("cuda");
+    // 2. Source Setup
+    // Multi-level output keeps the soft edges that the contrast boost would flatten
+    let leveled = palette.is_none() && job.levels > 2;
+    let filters = filter_chain(job, leveled);
+    if kind.is_builtin() {
+        let skipped = ffmpeg_only_filters(job, leveled);
+        if !skipped.is_empty() {
+            callback(ConverterStatus::Warning(format!(
+                "{} is read without ffmpeg; skipping {}",
+                job.input_path.display(),
+                skipped.join(", ")
+            )));
+        }
     }
+    let chroma_key = job.filters.chroma_key;
+    let pixel_format = if palette.is_some() || chroma_key.is_some() {
+        PixelFormat::Rgb24
+    } else {
+        PixelFormat::Gray
+    };
 
-    cmd.arg("-i")
-        .arg(&job.input_path)
-        .arg("-vf")
-        .arg(filters)
-        .arg("-f")
-        .arg("rawvideo")
-        .arg("-pix_fmt")
-        .arg("gray")
-        .arg("-");
-
-    let mut child = cmd
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()
-        .context("Failed to spawn ffmpeg")?;
+    // Frames of an interrupted run of this job are kept
+    let checkpoint_path = checkpoints.then(|| checkpoint_path(&job.output_path));
+    let resume_from = if checkpoints && job.resume {
+        load_checkpoint(job)
+    } else {
+        None
+    };
+    if let (None, Some(path)) = (&resume_from, &checkpoint_path) {
+        let _ = fs::remove_file(path);
+    }
+    let start_frame = resume_from.as_ref().map_or(0, |c| c.frames);
+    if start_frame > 0 {
+        callback(ConverterStatus::Resumed(start_frame));
+    }
 
-    let mut stdout = child.stdout.take().context("Failed to open stdout")?;
+    let mut source = open_source(
+        job,
+        pixel_format,
+        &filters,
+        start_frame,
+        timestamps.as_deref(),
+    )?;
+    let total_frames = source
+        .frame_count()
+        .or_else(|| get_frame_count(&job.input_path))
+        .unwrap_or(0);
 
     // 3. Channel Setup
     let queue_size = 64;
@@ -62,7 +187,7 @@ where
         bounded(queue_size);
     let (tx_recycle, rx_recycle): (Sender<Vec<u8>>, Receiver<Vec<u8>>) = bounded(queue_size);
 
-    let frame_size = (job.width * job.height) as usize;
+    let frame_size = (job.width * job.height) as usize * pixel_format.bytes_per_pixel();
 
     // Pre-fill Recycle Bin
     for _ in 0..queue_size {
@@ -72,48 +197,168 @@ where
     // 4. Writer Thread (Handles Disk I/O + Reporting)
     let output_path = job.output_path.clone();
     let cb_writer = callback.clone();
-    let fps = job.fps;
+    let frame_rate = job.frame_rate;
+    // Built-in sources don't run the filter chain
+    let meta = conversion_metadata(job, (!kind.is_builtin()).then_some(filters.as_str()));
+    let threshold = job.filters.threshold;
+    let keyframe_interval = job.keyframe_interval;
+    let compact = job.compact;
+    let levels = job.levels;
+    let lut = palette.as_ref().map(palette_lut);
+    let colors = palette.as_ref().map_or(0, |p| p.colors.len());
+    let (width, height) = (job.width, job.height);
+    let pixels = (width * height) as usize;
+    let simplify = job.simplify;
+    let fingerprint = job_fingerprint(job);
+    // Set when reading the source failed. The writer then leaves the
+    // partial output and its checkpoint for a resume instead of finishing
+    let read_failed = Arc::new(AtomicBool::new(false));
+    let writer_read_failed = read_failed.clone();
 
     let write_handle = thread::spawn(move || -> Result<()> {
-        let mut file_out = BufWriter::with_capacity(4 * 1024 * 1024, File::create(output_path)?);
-        file_out.write_all(&fps.to_le_bytes())?;
+        // The frames already written, with the file cut back to them
+        let (file, written) = match &resume_from {
+            Some(checkpoint) => {
+                let mut file = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(&output_path)?;
+                file.set_len(HEADER_SIZE as u64 + checkpoint.stream_len)?;
+                let mut stream = Vec::with_capacity(checkpoint.stream_len as usize);
+                file.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
+                file.read_to_end(&mut stream)?;
+                file.rewind()?;
+                (file, Some(stream))
+            }
+            None => (File::create(&output_path)?, None),
+        };
+        let file_out = BufWriter::with_capacity(4 * 1024 * 1024, file);
+        let mut bin = BinWriter::new(file_out, width as u16, height as u16, frame_rate.rounded())?
+            .with_frame_index()
+            .with_frame_rate(frame_rate)?
+            .with_metadata(meta);
+        if timestamps.is_some() {
+            bin = bin.with_timestamps()?;
+        }
+        if let Some(interval) = keyframe_interval {
+            bin = bin.with_delta(interval)?;
+        }
+        if compact {
+            bin = bin.with_varint()?;
+        }
+        if leveled {
+            bin = bin.with_levels(levels)?;
+        }
+        if let Some(palette) = palette {
+            bin = bin.with_palette(palette)?;
+        }
+        if let Some(stream) = &written {
+            let times = timestamps.as_deref().unwrap_or_default();
+            bin = bin
+                .resume(stream, times)
+                .context("The partial output doesn't match its checkpoint")?;
+        }
 
-        let mut next_needed_id = 0;
-        let mut reorder_buffer: HashMap<u64, Vec<PixelRect>> = HashMap::new();
+        // Encodes the exact frames as well, only to measure what
+        // simplifying saved; the frame encoding is all that differs
+        let mut exact = None;
+        if simplify > 0 {
+            let mut counter =
+                BinWriter::new(NullSink, width as u16, height as u16, frame_rate.rounded())?
+                    .with_frame_rate(frame_rate)?;
+            if let Some(interval) = keyframe_interval {
+                counter = counter.with_delta(interval)?;
+            }
+            if compact {
+                counter = counter.with_varint()?;
+            }
+            // Checkpoints fall before keyframes, so the simplified frames
+            // stand in for the exact ones without changing the count
+            if let Some(stream) = &written {
+                counter = counter.resume(stream, &[])?;
+            }
+            exact = Some(counter);
+        }
+        let (mut rects_before, mut rects_after, saved_before) =
+            resume_from.as_ref().map_or((0, 0, 0), |c| {
+                (c.rects_before, c.rects_after, c.saved_bytes)
+            });
+        let mut frame_stats = match &written {
+            Some(stream) => {
+                scan_frame_stats(stream, width, height, keyframe_interval.is_some(), compact)?
+            }
+            None => Vec::new(),
+        };
+        drop(written);
+
+        let mut next_needed_id = start_frame;
+        let mut reorder_buffer: HashMap<u64, (Vec<PixelRect>, Option<Vec<PixelRect>>)> =
+            HashMap::new();
 
         let start_time = Instant::now();
         let mut last_report = Instant::now();
+        let mut last_checkpoint = Instant::now();
 
         for frame in rx_processed {
-            reorder_buffer.insert(frame.id, frame.rects);
+            reorder_buffer.insert(frame.id, (frame.rects, frame.unsimplified));
             let _ = tx_recycle.send(frame.recycled_buffer); // Return buffer immediately
 
-            while let Some(rects) = reorder_buffer.remove(&next_needed_id) {
-                // Bulk write rects (unsafe cast is virtually free)
-                let rect_bytes = unsafe {
-                    std::slice::from_raw_parts(
-                        rects.as_ptr() as *const u8,
-                        rects.len() * std::mem::size_of::<PixelRect>(),
-                    )
-                };
-                file_out.write_all(rect_bytes)?;
-
-                let eos = PixelRect::EOS_MARKER;
-                let eos_bytes = unsafe {
-                    std::slice::from_raw_parts(
-                        &eos as *const PixelRect as *const u8,
-                        std::mem::size_of::<PixelRect>(),
-                    )
-                };
-                file_out.write_all(eos_bytes)?;
+            while let Some((rects, unsimplified)) = reorder_buffer.remove(&next_needed_id) {
+                if let (Some(counter), Some(unsimplified)) = (&mut exact, &unsimplified) {
+                    counter.write_frame(unsimplified)?;
+                    rects_before += unsimplified.iter().filter(|r| !r.is_control()).count() as u64;
+                    rects_after += rects.iter().filter(|r| !r.is_control()).count() as u64;
+                }
+                let stream_len = bin.header().data_len;
+                match timestamps
+                    .as_ref()
+                    .and_then(|t| t.get(next_needed_id as usize))
+                {
+                    Some(&time_ms) => bin.write_frame_at(&rects, time_ms)?,
+                    None => bin.write_frame(&rects)?,
+                }
+                frame_stats.push(measure_frame(
+                    next_needed_id,
+                    &rects,
+                    bin.header().data_len - stream_len,
+                    pixels as u64,
+                ));
 
                 next_needed_id += 1;
 
+                // Checkpoint before keyframes, where nothing depends on
+                // frames that came before
+                let keyframe_due = keyframe_interval
+                    .is_none_or(|i| next_needed_id.is_multiple_of(i.max(1) as u64));
+                let checkpoint_due =
+                    keyframe_due && last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL;
+                if let (true, Some(checkpoint_path)) = (checkpoint_due, &checkpoint_path) {
+                    bin.flush()?;
+                    let saved_bytes = exact.as_ref().map_or(0, |counter| {
+                        counter
+                            .header()
+                            .data_len
+                            .saturating_sub(bin.header().data_len)
+                    });
+                    save_checkpoint(
+                        checkpoint_path,
+                        &Checkpoint {
+                            job: fingerprint.clone(),
+                            frames: next_needed_id,
+                            stream_len: bin.header().data_len,
+                            rects_before,
+                            rects_after,
+                            saved_bytes: saved_before + saved_bytes,
+                        },
+                    )?;
+                    last_checkpoint = Instant::now();
+                }
+
                 // Report Progress (throttled to ~10 times/sec to save CPU)
                 if next_needed_id % 30 == 0 || last_report.elapsed().as_millis() > 100 {
                     let elapsed = start_time.elapsed().as_secs_f64();
                     let speed = if elapsed > 0.0 {
-                        next_needed_id as f64 / elapsed
+                        (next_needed_id - start_frame) as f64 / elapsed
                     } else {
                         0.0
                     };
@@ -127,16 +372,50 @@ where
                 }
             }
         }
+        if writer_read_failed.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        for (source, data) in &layers {
+            bin.add_layer(&Layer {
+                name: &source.name,
+                opacity: source.opacity,
+                flags: if source.looped { LAYER_LOOP } else { 0 },
+                bin: data,
+            })?;
+        }
+        let saved_bytes = exact.map_or(0, |counter| {
+            counter
+                .header()
+                .data_len
+                .saturating_sub(bin.header().data_len)
+        }) + saved_before;
+        let (mut file_out, _) = bin.finish()?;
+        file_out.flush()?;
+        if let Some(path) = &checkpoint_path {
+            let _ = fs::remove_file(path);
+        }
+        let file_bytes = fs::metadata(&output_path)?.len();
+        if simplify > 0 {
+            cb_writer(ConverterStatus::Simplified(SimplifyReport {
+                rects_before,
+                rects_after,
+                bytes_before: file_bytes + saved_bytes,
+                bytes_after: file_bytes,
+            }));
+        }
+        let stats = summarize_stats(&frame_stats, file_bytes);
+        write_stats(&stats_path(&output_path), &stats)?;
+        cb_writer(ConverterStatus::Stats(stats));
         cb_writer(ConverterStatus::Finished);
         Ok(())
     });
 
     // 5. Reader Thread
-    thread::spawn(move || {
-        let mut frame_id = 0;
+    let read_handle = thread::spawn(move || -> Result<()> {
+        let mut frame_id = start_frame;
         loop {
             let mut buffer = rx_recycle.recv().unwrap_or_else(|_| vec![0u8; frame_size]);
-            if stdout.read_exact(&mut buffer).is_err() {
+            if !source.read_frame(&mut buffer)? {
                 break;
             } // EOF
             if tx_raw
@@ -150,30 +429,297 @@ where
             }
             frame_id += 1;
         }
+        Ok(())
     });
 
     // 6. Parallel Compute (Main Thread Logic)
-    let width = job.width;
-    let height = job.height;
-
-    rx_raw.into_iter().par_bridge().for_each(|raw| {
+    let invert = job.invert;
+    let merge = job.merge;
+    rx_raw.into_iter().par_bridge().for_each(|mut raw| {
         SCRATCH_BUFFER.with(|cell| {
             let mut indices = cell.borrow_mut();
             if indices.len() != width as usize {
                 *indices = vec![-1isize; width as usize];
             }
 
-            let rects = extract_rects_optimized(&raw.data, width, height, 127, &mut indices);
+            // Gray stage: the luma frame, or the chroma key mask
+            let gray = match &chroma_key {
+                Some(key) => {
+                    chroma_key_mask(&mut raw.data, pixels, key);
+                    &mut raw.data[..pixels]
+                }
+                None => &mut raw.data[..],
+            };
+            if invert {
+                gray.iter_mut().for_each(|luma| *luma = !*luma);
+            }
+
+            let rects = if let Some(lut) = &lut {
+                extract_palette_rects(gray, width, height, lut, colors, merge, &mut indices)
+            } else if leveled {
+                extract_level_rects(gray, width, height, levels, merge, &mut indices)
+            } else {
+                let threshold = match threshold {
+                    Threshold::Fixed(luma) => luma,
+                    Threshold::Otsu => otsu_threshold(gray).unwrap_or(Threshold::DEFAULT_LUMA),
+                };
+                match merge {
+                    RectMerge::Snowplow => {
+                        extract_rects_optimized(gray, width, height, threshold, &mut indices)
+                    }
+                    RectMerge::Greedy => {
+                        extract_rects_greedy(gray, width, height, threshold, &mut indices)
+                    }
+                }
+            };
+
+            let (rects, unsimplified) = match simplify {
+                0 => (rects, None),
+                tolerance => (
+                    simplify_rects(&rects, tolerance, width, height),
+                    Some(rects),
+                ),
+            };
 
             let _ = tx_processed.send(ProcessedFrame {
                 id: raw.id,
                 rects,
+                unsimplified,
                 recycled_buffer: raw.data,
             });
         });
     });
 
+    // The reader is done once its frames are, and must be heard from
+    // before the writer finishes the file
+    let read_result = read_handle.join().expect("Reader panic");
+    if read_result.is_err() {
+        read_failed.store(true, Ordering::Release);
+    }
     drop(tx_processed);
     write_handle.join().expect("Writer panic")?;
-    Ok(())
+    read_result.context("Failed to read the source")
+}
+
+/// Discards what is written. Lets a [`BinWriter`] count encoded bytes.
+struct NullSink;
+
+impl Write for NullSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for NullSink {
+    fn seek(&mut self, _: SeekFrom) -> io::Result<u64> {
+        Ok(0)
+    }
+}
+
+/// Metadata recorded by the downloader, plus how this file was converted.
+/// `filters` is the ffmpeg filter chain, if one was used.
+fn conversion_metadata(job: &ConvertJob, filters: Option<&str>) -> ps_core::Metadata {
+    let mut meta = read_sidecar(&job.input_path);
+    meta.set(
+        metadata::KEY_TOOL_VERSION,
+        concat!("ps-factory ", env!("CARGO_PKG_VERSION")),
+    );
+    meta.set(metadata::KEY_CREATED, metadata::now_utc());
+    if let Some(name) = job.input_path.file_name() {
+        meta.set("source_file", name.to_string_lossy());
+    }
+
+    let mut settings = vec![format!(
+        "{}x{} @ {} FPS",
+        job.width, job.height, job.frame_rate
+    )];
+    if job.variable_rate {
+        settings.push("variable rate".into());
+    }
+    match job.colors {
+        Some(colors) => settings.push(format!("{} colors", colors)),
+        None if job.levels > 2 => settings.push(format!("{} levels", job.levels)),
+        None => settings.push(match job.filters.threshold {
+            Threshold::Fixed(luma) => format!("threshold {}", luma),
+            Threshold::Otsu => "threshold auto (Otsu)".to_string(),
+        }),
+    }
+    if job.invert {
+        settings.push("inverted".into());
+    }
+    if job.merge != RectMerge::Snowplow {
+        settings.push(format!("{} merge", job.merge));
+    }
+    if job.simplify > 0 {
+        settings.push(format!("simplified (tolerance {})", job.simplify));
+    }
+    if let Some(key) = &job.filters.chroma_key {
+        settings.push(format!(
+            "chroma key #{:06X} (tolerance {}, spill {})",
+            key.color, key.tolerance, key.spill
+        ));
+    }
+    if let Some(interval) = job.keyframe_interval {
+        settings.push(format!("delta (keyframe every {})", interval));
+    }
+    if job.compact {
+        settings.push("compact".into());
+    }
+    meta.set("convert_settings", settings.join(", "));
+    if let Some(filters) = filters {
+        meta.set("filters", filters);
+    }
+    meta
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ps_core::{FileHeader, FrameIndex, FrameRate};
+    use std::path::Path;
+    use std::sync::mpsc;
+
+    /// A 32x16 gray Y4M of a bright block sliding across a dark frame.
+    fn write_clip(path: &Path, frames: usize) {
+        let mut clip = b"YUV4MPEG2 W32 H16 F25:1 Cmono\n".to_vec();
+        for f in 0..frames {
+            clip.extend_from_slice(b"FRAME\n");
+            clip.extend((0..32 * 16).map(|i| {
+                let (x, y) = (i % 32, i / 32);
+                if (f..f + 6).contains(&x) && y % 8 < 5 {
+                    230
+                } else {
+                    20
+                }
+            }));
+        }
+        fs::write(path, clip).unwrap();
+    }
+
+    fn job(dir: &Path) -> ConvertJob {
+        ConvertJob {
+            input_path: dir.join("clip.y4m"),
+            output_path: dir.join("clip.bin"),
+            width: 32,
+            height: 16,
+            frame_rate: FrameRate::new(25, 1),
+            variable_rate: false,
+            use_gpu: false,
+            keyframe_interval: Some(4),
+            compact: true,
+            levels: 2,
+            colors: None,
+            invert: false,
+            merge: RectMerge::Snowplow,
+            simplify: 0,
+            filters: FilterOptions::default(),
+            layers: Vec::new(),
+            resume: false,
+        }
+    }
+
+    /// Runs `job`, returning the output and whether it was resumed.
+    fn convert(job: &ConvertJob) -> (Vec<u8>, Option<u64>) {
+        let (tx, rx) = mpsc::channel();
+        process_conversion(job.clone(), move |status| {
+            let _ = tx.send(status);
+        })
+        .unwrap();
+        let resumed = rx.iter().find_map(|status| match status {
+            ConverterStatus::Resumed(frame) => Some(frame),
+            _ => None,
+        });
+        (fs::read(&job.output_path).unwrap(), resumed)
+    }
+
+    #[test]
+    fn warns_about_filters_builtin_sources_skip() {
+        let dir = std::env::temp_dir().join(format!("ps-skipped-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        write_clip(&dir.join("clip.y4m"), 2);
+        let warnings = |job: ConvertJob| {
+            let (tx, rx) = mpsc::channel();
+            process_conversion(job, move |status| {
+                let _ = tx.send(status);
+            })
+            .unwrap();
+            rx.iter()
+                .filter_map(|status| match status {
+                    ConverterStatus::Warning(message) => Some(message),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert!(warnings(job(&dir)).is_empty());
+        let mut filtered = job(&dir);
+        filtered.filters.blur = 2.0;
+        filtered.filters.extra = "hflip".to_string();
+        let found = warnings(filtered);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].ends_with("skipping blur, extra filters"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn resumes_from_a_checkpoint() {
+        let dir = std::env::temp_dir().join(format!("ps-resume-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        write_clip(&dir.join("clip.y4m"), 24);
+        let job = job(&dir);
+
+        let (full, resumed) = convert(&job);
+        assert_eq!(resumed, None);
+        let header = FileHeader::parse(&full).unwrap();
+        let index = FrameIndex::load_or_scan(&full, &header).unwrap();
+        assert_eq!(header.frame_count, 24);
+
+        for stop in [8, 16] {
+            // An interrupted run: the frames up to the checkpoint, then
+            // part of the next one
+            let stream_len = index.frame_range(stop).unwrap().start as u64;
+            let cut = HEADER_SIZE + stream_len as usize;
+            fs::write(&job.output_path, &full[..cut + 5]).unwrap();
+            let checkpoint = Checkpoint {
+                job: job_fingerprint(&job),
+                frames: stop as u64,
+                stream_len,
+                rects_before: 0,
+                rects_after: 0,
+                saved_bytes: 0,
+            };
+            let checkpoint_path = checkpoint_path(&job.output_path);
+            save_checkpoint(&checkpoint_path, &checkpoint).unwrap();
+
+            // Other settings don't pick it up
+            let other = ConvertJob {
+                keyframe_interval: Some(5),
+                ..job.clone()
+            };
+            assert!(load_checkpoint(&other).is_none());
+
+            let resume = ConvertJob {
+                resume: true,
+                ..job.clone()
+            };
+            let (out, resumed) = convert(&resume);
+            assert_eq!(resumed, Some(stop as u64));
+            assert!(!checkpoint_path.exists());
+
+            let out_header = FileHeader::parse(&out).unwrap();
+            let out_index = FrameIndex::load_or_scan(&out, &out_header).unwrap();
+            assert_eq!(out_header.frame_count, header.frame_count);
+            assert!(out[out_header.data_range()] == full[header.data_range()]);
+            for frame in 0..index.len() {
+                assert_eq!(out_index.frame_range(frame), index.frame_range(frame));
+            }
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
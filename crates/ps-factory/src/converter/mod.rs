@@ -1,6 +1,7 @@
 pub mod async_handler;
 pub mod cli;
 pub mod core;
+pub mod source;
 pub mod types;
 pub mod utils;
 
@@ -8,5 +9,6 @@ pub mod utils;
 pub use async_handler::*;
 pub use cli::*;
 pub use core::*;
+pub use source::*;
 pub use types::*;
 pub use utils::*;
@@ -0,0 +1,636 @@
+//! Where the converter gets its raw frames from.
+//!
+//! Videos are decoded by an `ffmpeg` pipe. Numbered PNG sequences and
+//! uncompressed Y4M files are read in-process, so conversions of those run
+//! without ffmpeg installed. The built-in sources only scale (nearest
+//! neighbour); they skip ffmpeg's blur, contrast and extra filters, which
+//! the threshold makes up for on clean sources. Changed filter settings
+//! are reported as a warning.
+
+use super::types::ConvertJob;
+use anyhow::{bail, Context, Result};
+use ps_core::FrameRate;
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, ErrorKind, Read},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Command, Stdio},
+};
+
+/// Frame rate of PNG sequences unless told otherwise; images carry none.
+pub const DEFAULT_SEQUENCE_RATE: FrameRate = FrameRate::from_fps(30);
+
+/// Layout of the frames a source produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// One luma byte per pixel.
+    Gray,
+    /// Three bytes per pixel, `r, g, b`.
+    Rgb24,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            Self::Gray => 1,
+            Self::Rgb24 => 3,
+        }
+    }
+
+    fn ffmpeg_name(&self) -> &'static str {
+        match self {
+            Self::Gray => "gray",
+            Self::Rgb24 => "rgb24",
+        }
+    }
+}
+
+/// Produces the frames of one conversion, already scaled to the job size.
+pub trait FrameSource: Send {
+    /// Fills `buf` with the next frame. Returns `false` at the end.
+    fn read_frame(&mut self, buf: &mut [u8]) -> Result<bool>;
+
+    /// Number of frames, if known without decoding.
+    fn frame_count(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// How an input path is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    Ffmpeg,
+    Y4m,
+    /// A directory of PNGs, or one PNG of a numbered sequence.
+    PngSequence,
+}
+
+impl SourceKind {
+    pub fn detect(path: &Path) -> Self {
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "y4m" => Self::Y4m,
+            "png" => Self::PngSequence,
+            _ if path.is_dir() => Self::PngSequence,
+            _ => Self::Ffmpeg,
+        }
+    }
+
+    /// Read without ffmpeg.
+    pub fn is_builtin(&self) -> bool {
+        *self != Self::Ffmpeg
+    }
+}
+
+/// Opens the source for `job`, `start` frames in. `filters` only apply to
+/// the ffmpeg pipe; `timestamps` are the frame times of variable rate jobs.
+pub fn open_source(
+    job: &ConvertJob,
+    format: PixelFormat,
+    filters: &str,
+    start: u64,
+    timestamps: Option<&[u32]>,
+) -> Result<Box<dyn FrameSource>> {
+    let size = (job.width as usize, job.height as usize);
+    Ok(match SourceKind::detect(&job.input_path) {
+        SourceKind::Ffmpeg => {
+            let seek = (start > 0).then(|| seek_time(job, start as usize, timestamps));
+            Box::new(FfmpegSource::spawn(job, format, filters, seek)?)
+        }
+        SourceKind::Y4m => {
+            let mut source = Y4mSource::open(&job.input_path)?.scaled(size, format);
+            source.skip(start)?;
+            Box::new(source)
+        }
+        SourceKind::PngSequence => {
+            let mut source = PngSequence::open(&job.input_path, size, format)?;
+            source.next = start as usize;
+            Box::new(source)
+        }
+    })
+}
+
+/// Seconds into the source to seek to for `frame` (at least 1). Lands
+/// halfway back to the frame before, so rounding can't pick either
+/// neighbour.
+fn seek_time(job: &ConvertJob, frame: usize, timestamps: Option<&[u32]>) -> f64 {
+    match timestamps {
+        Some(times) if frame < times.len() => {
+            (times[frame - 1] as f64 + times[frame] as f64) / 2000.0
+        }
+        _ => job.frame_rate.time_of(frame) - 0.5 / job.frame_rate.as_f64(),
+    }
+}
+
+/// Frames piped out of `ffmpeg` as raw video.
+pub struct FfmpegSource {
+    child: Child,
+    stdout: ChildStdout,
+}
+
+impl FfmpegSource {
+    /// Starts decoding `seek` seconds in, if given. Frames before it are
+    /// decoded and dropped, so the first frame is exact.
+    pub fn spawn(
+        job: &ConvertJob,
+        format: PixelFormat,
+        filters: &str,
+        seek: Option<f64>,
+    ) -> Result<Self> {
+        let mut cmd = Command::new("ffmpeg");
+        if job.use_gpu {
+            cmd.arg("-hwaccel").arg("cuda");
+        }
+        if let Some(seconds) = seek {
+            cmd.arg("-ss").arg(format!("{:.6}", seconds));
+        }
+
+        cmd.arg("-i").arg(&job.input_path).arg("-vf").arg(filters);
+        if job.variable_rate {
+            // One output frame per source frame, no duplicates or drops
+            cmd.arg("-fps_mode").arg("passthrough");
+        }
+        cmd.arg("-f")
+            .arg("rawvideo")
+            .arg("-pix_fmt")
+            .arg(format.ffmpeg_name())
+            .arg("-");
+
+        let mut child = cmd
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Failed to spawn ffmpeg")?;
+        let stdout = child.stdout.take().context("Failed to open stdout")?;
+        Ok(Self { child, stdout })
+    }
+}
+
+impl FrameSource for FfmpegSource {
+    fn read_frame(&mut self, buf: &mut [u8]) -> Result<bool> {
+        match self.stdout.read_exact(buf) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for FfmpegSource {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Chroma layout of a Y4M file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Chroma {
+    /// 4:2:0, 4:2:2 or 4:4:4 as horizontal and vertical subsampling shifts.
+    Yuv(u32, u32),
+    Mono,
+}
+
+/// An uncompressed YUV4MPEG2 file with 8-bit samples.
+pub struct Y4mSource {
+    reader: BufReader<File>,
+    width: usize,
+    height: usize,
+    chroma: Chroma,
+    frame_rate: Option<FrameRate>,
+    frame_count: u64,
+    /// Planes of the current frame, Y then U and V.
+    planes: Vec<u8>,
+    out: Option<((usize, usize), PixelFormat)>,
+}
+
+impl Y4mSource {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let mut params = header.trim_end().split(' ');
+        if params.next() != Some("YUV4MPEG2") {
+            bail!("{:?} is not a Y4M file", path);
+        }
+
+        let (mut width, mut height) = (0, 0);
+        let mut chroma = Chroma::Yuv(1, 1);
+        let mut frame_rate = None;
+        for param in params {
+            // Unknown and malformed parameters are skipped, as the format
+            // asks of readers
+            let Some((tag, value)) = param.split_at_checked(1) else {
+                continue;
+            };
+            match tag {
+                "W" => width = value.parse().context("Invalid Y4M width")?,
+                "H" => height = value.parse().context("Invalid Y4M height")?,
+                "F" => frame_rate = value.replace(':', "/").parse().ok(),
+                "C" => {
+                    chroma = match value {
+                        "420" | "420jpeg" | "420paldv" | "420mpeg2" => Chroma::Yuv(1, 1),
+                        "422" => Chroma::Yuv(1, 0),
+                        "444" => Chroma::Yuv(0, 0),
+                        "mono" => Chroma::Mono,
+                        other => bail!("Unsupported Y4M colorspace '{}'", other),
+                    }
+                }
+                _ => {}
+            }
+        }
+        if width == 0 || height == 0 {
+            bail!("Y4M header has no frame size");
+        }
+
+        let mut source = Self {
+            reader,
+            width,
+            height,
+            chroma,
+            frame_rate,
+            frame_count: 0,
+            planes: Vec::new(),
+            out: None,
+        };
+        source.planes = vec![0; source.frame_len()];
+        // Assumes plain "FRAME\n" headers, which is what encoders write
+        let frame_len = (source.frame_len() + 6) as u64;
+        source.frame_count = (file_len - header.len() as u64) / frame_len;
+        Ok(source)
+    }
+
+    pub fn frame_rate(&self) -> Option<FrameRate> {
+        self.frame_rate
+    }
+
+    /// Skips the next `frames` frames without reading them.
+    pub fn skip(&mut self, frames: u64) -> Result<()> {
+        let frame_len = (self.frame_len() + 6) as i64;
+        self.reader.seek_relative(frames as i64 * frame_len)?;
+        Ok(())
+    }
+
+    fn scaled(mut self, size: (usize, usize), format: PixelFormat) -> Self {
+        self.out = Some((size, format));
+        self
+    }
+
+    fn chroma_size(&self) -> (usize, usize) {
+        match self.chroma {
+            Chroma::Yuv(sx, sy) => (
+                (self.width + (1 << sx) - 1) >> sx,
+                (self.height + (1 << sy) - 1) >> sy,
+            ),
+            Chroma::Mono => (0, 0),
+        }
+    }
+
+    fn frame_len(&self) -> usize {
+        let (cw, ch) = self.chroma_size();
+        self.width * self.height + 2 * cw * ch
+    }
+
+    /// RGB of the source pixel at `(x, y)`, BT.601 limited range.
+    fn rgb_at(&self, x: usize, y: usize) -> [u8; 3] {
+        let luma = self.planes[y * self.width + x] as i32;
+        let Chroma::Yuv(sx, sy) = self.chroma else {
+            return [luma as u8; 3];
+        };
+        let (cw, ch) = self.chroma_size();
+        let c = (y >> sy) * cw + (x >> sx);
+        let base = self.width * self.height;
+        let u = self.planes[base + c] as i32 - 128;
+        let v = self.planes[base + cw * ch + c] as i32 - 128;
+
+        let l = 298 * (luma - 16);
+        let clamp = |n: i32| ((n + 128) >> 8).clamp(0, 255) as u8;
+        [
+            clamp(l + 409 * v),
+            clamp(l - 100 * u - 208 * v),
+            clamp(l + 516 * u),
+        ]
+    }
+}
+
+impl FrameSource for Y4mSource {
+    fn read_frame(&mut self, buf: &mut [u8]) -> Result<bool> {
+        let mut marker = String::new();
+        if self.reader.read_line(&mut marker)? == 0 {
+            return Ok(false);
+        }
+        if !marker.starts_with("FRAME") {
+            bail!("Corrupt Y4M frame header");
+        }
+        match self.reader.read_exact(&mut self.planes) {
+            Ok(()) => {}
+            // A partly written last frame
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+
+        let ((dw, dh), format) = self
+            .out
+            .unwrap_or(((self.width, self.height), PixelFormat::Gray));
+        scale_nearest(
+            (self.width, self.height),
+            (dw, dh),
+            |dst, sx, sy| match format {
+                PixelFormat::Gray => buf[dst] = self.planes[sy * self.width + sx],
+                PixelFormat::Rgb24 => {
+                    buf[dst * 3..dst * 3 + 3].copy_from_slice(&self.rgb_at(sx, sy))
+                }
+            },
+        );
+        Ok(true)
+    }
+
+    fn frame_count(&self) -> Option<u64> {
+        Some(self.frame_count)
+    }
+}
+
+/// Numbered PNG images, played in numeric order.
+pub struct PngSequence {
+    paths: Vec<PathBuf>,
+    next: usize,
+    size: (usize, usize),
+    format: PixelFormat,
+}
+
+impl PngSequence {
+    /// `path` is either a directory of PNGs or one image of a sequence such
+    /// as `frame_0001.png`, which picks up its numbered siblings.
+    pub fn open(path: &Path, size: (usize, usize), format: PixelFormat) -> Result<Self> {
+        let paths = sequence_paths(path)?;
+        if paths.is_empty() {
+            bail!("No PNG images found at {:?}", path);
+        }
+        Ok(Self {
+            paths,
+            next: 0,
+            size,
+            format,
+        })
+    }
+}
+
+impl FrameSource for PngSequence {
+    fn read_frame(&mut self, buf: &mut [u8]) -> Result<bool> {
+        let Some(path) = self.paths.get(self.next) else {
+            return Ok(false);
+        };
+        self.next += 1;
+
+        let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder
+            .read_info()
+            .with_context(|| format!("Invalid PNG {:?}", path))?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut pixels)
+            .with_context(|| format!("Invalid PNG {:?}", path))?;
+
+        let channels = info.color_type.samples();
+        let (sw, sh) = (info.width as usize, info.height as usize);
+        let format = self.format;
+        scale_nearest((sw, sh), self.size, |dst, sx, sy| {
+            let px = &pixels[(sy * sw + sx) * channels..][..channels];
+            // Transparent pixels are background
+            let (rgb, alpha) = match px {
+                [l] => ([*l; 3], 255),
+                [l, a] => ([*l; 3], *a),
+                [r, g, b] => ([*r, *g, *b], 255),
+                [r, g, b, a, ..] => ([*r, *g, *b], *a),
+                _ => ([0; 3], 0),
+            };
+            let rgb = rgb.map(|c| (c as u32 * alpha as u32 / 255) as u8);
+            match format {
+                PixelFormat::Gray => buf[dst] = luma(rgb),
+                PixelFormat::Rgb24 => buf[dst * 3..dst * 3 + 3].copy_from_slice(&rgb),
+            }
+        });
+        Ok(true)
+    }
+
+    fn frame_count(&self) -> Option<u64> {
+        Some(self.paths.len() as u64)
+    }
+}
+
+/// Images of the sequence `path` belongs to, ordered by frame number.
+fn sequence_paths(path: &Path) -> Result<Vec<PathBuf>> {
+    let (dir, pattern) = if path.is_dir() {
+        (path, None)
+    } else {
+        let dir = path.parent().context("Image has no parent directory")?;
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        (dir, Some(split_number(&name).0.to_string()))
+    };
+
+    let mut frames: Vec<(u64, PathBuf)> = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("png"))
+        {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let (prefix, number) = split_number(&name);
+        if pattern.as_deref().is_some_and(|p| p != prefix) {
+            continue;
+        }
+        frames.push((number.unwrap_or(0), path.clone()));
+    }
+    frames.sort();
+    Ok(frames.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Splits `frame_0012.png` into `("frame_", Some(12))`.
+fn split_number(name: &str) -> (&str, Option<u64>) {
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    let digits = stem.len() - stem.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let (prefix, number) = stem.split_at(stem.len() - digits);
+    (prefix, number.parse().ok())
+}
+
+/// Calls `put(dst_index, src_x, src_y)` for every destination pixel.
+fn scale_nearest(
+    (sw, sh): (usize, usize),
+    (dw, dh): (usize, usize),
+    mut put: impl FnMut(usize, usize, usize),
+) {
+    for y in 0..dh {
+        let sy = y * sh / dh;
+        for x in 0..dw {
+            put(y * dw + x, x * sw / dw, sy);
+        }
+    }
+}
+
+fn luma([r, g, b]: [u8; 3]) -> u8 {
+    ((299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A fresh scratch directory for one test.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ps-source-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_png(path: &Path, width: u32, height: u32, rgba: &[u8]) {
+        let mut encoder = png::Encoder::new(File::create(path).unwrap(), width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(rgba).unwrap();
+    }
+
+    /// A 4x2 4:2:0 Y4M with `frames` frames of flat luma `16 + 50 * i`.
+    fn write_y4m(path: &Path, frames: u8, extra: &[u8]) {
+        let mut file = File::create(path).unwrap();
+        file.write_all(b"YUV4MPEG2 W4 H2 F30000:1001 Ip A1:1 C420jpeg XYSCSS=420JPEG\n")
+            .unwrap();
+        for i in 0..frames {
+            file.write_all(b"FRAME\n").unwrap();
+            file.write_all(&[16 + 50 * i; 8]).unwrap();
+            file.write_all(&[128; 4]).unwrap();
+        }
+        file.write_all(extra).unwrap();
+    }
+
+    #[test]
+    fn detects_the_source_kind() {
+        assert_eq!(SourceKind::detect(Path::new("a.Y4M")), SourceKind::Y4m);
+        assert_eq!(
+            SourceKind::detect(Path::new("f_001.png")),
+            SourceKind::PngSequence
+        );
+        assert_eq!(
+            SourceKind::detect(Path::new("clip.mp4")),
+            SourceKind::Ffmpeg
+        );
+        assert!(!SourceKind::Ffmpeg.is_builtin());
+        assert_eq!(split_number("frame_0012.png"), ("frame_", Some(12)));
+        assert_eq!(split_number("cover.png"), ("cover", None));
+    }
+
+    #[test]
+    fn reads_y4m_frames() {
+        let dir = scratch_dir("y4m");
+        let path = dir.join("clip.y4m");
+        // The last frame is cut off and dropped
+        write_y4m(&path, 3, b"FRAME\n\x10\x10");
+
+        let mut source = Y4mSource::open(&path).unwrap();
+        assert_eq!(source.frame_rate(), Some(FrameRate::new(30000, 1001)));
+        assert_eq!(source.frame_count(), Some(3));
+
+        let mut gray = [0; 8];
+        assert!(source.read_frame(&mut gray).unwrap());
+        assert_eq!(gray, [16; 8]);
+
+        // Scaled up to 8x4 RGB; luma 66 with neutral chroma is a dark grey
+        let mut source = source.scaled((8, 4), PixelFormat::Rgb24);
+        let mut rgb = [0; 8 * 4 * 3];
+        assert!(source.read_frame(&mut rgb).unwrap());
+        assert!(rgb.iter().all(|&c| c == 58), "{rgb:?}");
+        assert!(source.read_frame(&mut rgb).unwrap());
+        assert!(!source.read_frame(&mut rgb).unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn skips_unknown_y4m_parameters() {
+        let dir = scratch_dir("y4m-params");
+        let path = dir.join("odd.y4m");
+        let mut clip = "YUV4MPEG2 W2  H2 éx Ω XCOLORRANGE=FULL Cmono\n".as_bytes().to_vec();
+        clip.extend_from_slice(b"FRAME\n\x01\x02\x03\x04");
+        fs::write(&path, clip).unwrap();
+
+        let mut source = Y4mSource::open(&path).unwrap();
+        assert_eq!(source.frame_rate(), None);
+        let mut gray = [0; 4];
+        assert!(source.read_frame(&mut gray).unwrap());
+        assert_eq!(gray, [1, 2, 3, 4]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_bad_y4m_headers() {
+        let dir = scratch_dir("y4m-bad");
+        let path = dir.join("bad.y4m");
+        for header in [
+            &b"RIFF W4 H2\n"[..],
+            b"YUV4MPEG2 W4\n",
+            b"YUV4MPEG2 W4 H2 C420p10\n",
+            b"YUV4MPEG2 Wfour H2\n",
+        ] {
+            fs::write(&path, header).unwrap();
+            assert!(
+                Y4mSource::open(&path).is_err(),
+                "{:?}",
+                String::from_utf8_lossy(header)
+            );
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reads_png_sequences_in_order() {
+        let dir = scratch_dir("png");
+        // Frame n is a 2x1 image whose left pixel has red n * 10
+        for n in [10u8, 2, 1] {
+            let rgba = [n * 10, 0, 0, 255, 255, 255, 255, 0];
+            write_png(&dir.join(format!("frame_{}.png", n)), 2, 1, &rgba);
+        }
+        write_png(&dir.join("cover.png"), 2, 1, &[255; 8]);
+
+        let mut seq =
+            PngSequence::open(&dir.join("frame_2.png"), (2, 1), PixelFormat::Rgb24).unwrap();
+        assert_eq!(seq.frame_count(), Some(3));
+        let mut buf = [0; 6];
+        for n in [1, 2, 10] {
+            assert!(seq.read_frame(&mut buf).unwrap());
+            // Transparent pixels come out black
+            assert_eq!(buf, [n * 10, 0, 0, 0, 0, 0]);
+        }
+        assert!(!seq.read_frame(&mut buf).unwrap());
+
+        // A directory takes every image, scaled to the job size
+        let mut seq = PngSequence::open(&dir, (4, 2), PixelFormat::Gray).unwrap();
+        assert_eq!(seq.frame_count(), Some(4));
+        let mut gray = [0; 8];
+        assert!(seq.read_frame(&mut gray).unwrap());
+        assert_eq!(gray, [255; 8]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn empty_or_broken_sequences_fail() {
+        let dir = scratch_dir("png-bad");
+        assert!(PngSequence::open(&dir, (2, 2), PixelFormat::Gray).is_err());
+
+        fs::write(dir.join("frame_1.png"), b"not a png").unwrap();
+        let mut seq = PngSequence::open(&dir, (2, 2), PixelFormat::Gray).unwrap();
+        assert!(seq.read_frame(&mut [0; 4]).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
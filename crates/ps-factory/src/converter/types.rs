@@ -1,6 +1,7 @@
-use ps_core::PixelRect;
-use std::cell::RefCell;
-use std::path::PathBuf; // Ensure ps_core is in your dependencies
+use ps_core::{FrameRate, PixelRect};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::{cell::RefCell, fmt, str::FromStr}; // Ensure ps_core is in your dependencies
 
 /// Defines a single conversion task (One video -> One .bin file)
 #[derive(Debug, Clone)]
@@ -9,8 +10,181 @@ pub struct ConvertJob {
     pub output_path: PathBuf,
     pub width: u32,
     pub height: u32,
-    pub fps: u16,
+    pub frame_rate: FrameRate,
+    /// Keep the source's own frame timestamps instead of a constant rate.
+    pub variable_rate: bool,
     pub use_gpu: bool,
+    /// Store frames as deltas with a keyframe every N frames.
+    /// `None` writes every frame in full.
+    pub keyframe_interval: Option<u32>,
+    /// Write rects in the compact varint encoding.
+    pub compact: bool,
+    /// Intensity levels for anti-aliased edges; `2` or less keeps the
+    /// classic 1-bit output.
+    pub levels: u16,
+    /// Convert colors to a palette of up to N entries, at most
+    /// [`MAX_PALETTE_COLORS`](super::MAX_PALETTE_COLORS). Takes precedence
+    /// over `levels`.
+    pub colors: Option<u16>,
+    /// Treat dark pixels as foreground, for dark content on a light
+    /// background such as line art or scanned pages.
+    pub invert: bool,
+    pub merge: RectMerge,
+    /// How far, in pixels, rect edges may move to save rects; `0` keeps
+    /// the output pixel-exact. See [`simplify_rects`](super::simplify_rects).
+    pub simplify: u8,
+    pub filters: FilterOptions,
+    /// Extra layers drawn over the main video, converted with the same
+    /// settings.
+    pub layers: Vec<LayerSource>,
+    /// Pick up an interrupted conversion of the same job from its
+    /// checkpoint instead of starting over.
+    pub resume: bool,
+}
+
+/// How source frames are cleaned up before rects are extracted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterOptions {
+    /// Luma at which pixels are drawn in 1-bit output.
+    pub threshold: Threshold,
+    /// Gaussian blur sigma; `0` disables the blur.
+    pub blur: f32,
+    /// Contrast boost for 1-bit output, up to ffmpeg's maximum of 1000;
+    /// `1` disables it.
+    pub contrast: f32,
+    /// Extra ffmpeg filters appended to the chain, e.g. `hqdn3d,negate`.
+    /// Empty for none.
+    pub extra: String,
+    /// Take the foreground from colour distance to a key colour instead of
+    /// from luminance.
+    pub chroma_key: Option<ChromaKey>,
+}
+
+impl Default for FilterOptions {
+    fn default() -> Self {
+        Self {
+            threshold: Threshold::Fixed(Threshold::DEFAULT_LUMA),
+            blur: 1.0,
+            contrast: 1000.0,
+            extra: String::new(),
+            chroma_key: None,
+        }
+    }
+}
+
+/// Green/blue-screen keying. Distances are measured in the CbCr plane with
+/// colours at full brightness, so shadows on the screen are keyed along
+/// with it; they range up to about 180.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChromaKey {
+    /// `0xRRGGBB` of the screen.
+    pub color: u32,
+    /// Pixels this close to the key are background.
+    pub tolerance: u8,
+    /// Width of the band beyond `tolerance` where edges tinted by the
+    /// screen fade in. `0` gives hard edges.
+    pub spill: u8,
+}
+
+impl Default for ChromaKey {
+    fn default() -> Self {
+        Self {
+            color: 0x00FF00,
+            tolerance: 40,
+            spill: 20,
+        }
+    }
+}
+
+/// Where 1-bit output splits foreground from background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Threshold {
+    /// Pixels at or above this luma are drawn.
+    Fixed(u8),
+    /// Picked per frame from its histogram with Otsu's method, for grey or
+    /// gradient backgrounds. Skips the contrast boost, which would leave
+    /// nothing to pick from. Frames without a clear split, such as fades
+    /// and blank cards, use [`Threshold::DEFAULT_LUMA`].
+    ///
+    /// There is no per-scene pass: frames of one shot have near-identical
+    /// histograms and so near-identical thresholds, and a cut changes the
+    /// threshold on the very frame it happens without a scene detector.
+    Otsu,
+}
+
+impl Threshold {
+    /// Luma used when none is given, and by [`Threshold::Otsu`] on frames
+    /// it can't split.
+    pub const DEFAULT_LUMA: u8 = 127;
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed(luma) => write!(f, "{}", luma),
+            Self::Otsu => write!(f, "auto"),
+        }
+    }
+}
+
+/// Accepts a luma such as `127`, or `auto` for [`Threshold::Otsu`].
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "auto" | "otsu" => Ok(Self::Otsu),
+            luma => luma
+                .parse()
+                .map(Self::Fixed)
+                .map_err(|_| format!("invalid threshold '{}' (0-255 or auto)", s)),
+        }
+    }
+}
+
+/// How runs of drawn pixels are merged into rects.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RectMerge {
+    /// Extends a rect down while the next row has a run with exactly the
+    /// same x and width. Fastest, and rects stay stable between frames.
+    #[default]
+    Snowplow,
+    /// Also tries a greedy maximal-rectangle cover and keeps it when it
+    /// needs fewer rects. Saves a third or more on text, line art and other
+    /// vertical strokes, and is never worse than Snowplow, but converts
+    /// several times slower.
+    Greedy,
+}
+
+impl fmt::Display for RectMerge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Snowplow => write!(f, "snowplow"),
+            Self::Greedy => write!(f, "greedy"),
+        }
+    }
+}
+
+impl FromStr for RectMerge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "snowplow" => Ok(Self::Snowplow),
+            "greedy" => Ok(Self::Greedy),
+            _ => Err(format!("invalid merge '{}' (snowplow or greedy)", s)),
+        }
+    }
+}
+
+/// A layer converted from its own source video.
+#[derive(Debug, Clone)]
+pub struct LayerSource {
+    pub name: String,
+    pub input_path: PathBuf,
+    pub opacity: u8,
+    /// Restart when it ends instead of holding the last frame.
+    pub looped: bool,
 }
 
 /// Status updates sent from the Core Logic to the CLI or GUI
@@ -23,14 +197,144 @@ pub enum ConverterStatus {
         total_frames: u64,
         fps_speed: f64, // Processing speed
     },
+    /// An interrupted conversion is picked up at this frame.
+    Resumed(u64),
+    /// Sent before `Finished` when the output was simplified.
+    Simplified(SimplifyReport),
+    /// Sent before `Finished`. Also saved as JSON next to the output, see
+    /// [`stats_path`](super::stats_path).
+    Stats(ConversionStats),
+    /// Something the conversion can't honour but works around, e.g. a
+    /// setting the source type ignores.
+    Warning(String),
     Finished,
     Error(String),
 }
 
+/// What lossy simplification saved over the exact output.
+#[derive(Debug, Clone, Copy)]
+pub struct SimplifyReport {
+    pub rects_before: u64,
+    pub rects_after: u64,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl fmt::Display for SimplifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let saved = |before: u64, after: u64| {
+            100.0 * before.saturating_sub(after) as f64 / before.max(1) as f64
+        };
+        write!(
+            f,
+            "{} -> {} rects (-{:.1}%), {:.1} -> {:.1} KB (-{:.1}%)",
+            self.rects_before,
+            self.rects_after,
+            saved(self.rects_before, self.rects_after),
+            self.bytes_before as f64 / 1024.0,
+            self.bytes_after as f64 / 1024.0,
+            saved(self.bytes_before, self.bytes_after)
+        )
+    }
+}
+
+/// How far a conversion got, saved next to its partial output. Frames up
+/// to here are fully written; see [`checkpoint_path`](super::checkpoint_path).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The job and source the output belongs to. Only the same job resumes.
+    pub job: String,
+    pub frames: u64,
+    /// Length of the rect stream holding `frames`.
+    pub stream_len: u64,
+    /// Simplification totals so far, see [`SimplifyReport`].
+    pub rects_before: u64,
+    pub rects_after: u64,
+    pub saved_bytes: u64,
+}
+
+/// Size and drawing cost of one frame of the output.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct FrameStats {
+    pub frame: u64,
+    pub rects: u32,
+    /// Encoded size in the rect stream.
+    pub bytes: u32,
+    /// Share of the frame's pixels drawn, `0` to `1`.
+    pub coverage: f32,
+}
+
+/// Mean, percentiles and maximum of one per-frame value.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Spread {
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+/// Per-frame statistics of a conversion, for finding out why an output is
+/// large or slow to draw. Layers aren't included.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversionStats {
+    pub frames: u64,
+    pub rects: u64,
+    /// Size of the rect stream, the part that grows with the frames.
+    pub stream_bytes: u64,
+    pub file_bytes: u64,
+    pub rects_per_frame: Spread,
+    pub bytes_per_frame: Spread,
+    pub coverage: Spread,
+    /// The frames with the most rects, the slowest to draw, most first.
+    pub most_rects: Vec<FrameStats>,
+    /// The frames taking the most space, largest first.
+    pub largest: Vec<FrameStats>,
+}
+
+impl fmt::Display for ConversionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} frames, {:.0} rects/frame (p99 {:.0}, max {:.0}",
+            self.frames,
+            self.rects_per_frame.mean,
+            self.rects_per_frame.p99,
+            self.rects_per_frame.max
+        )?;
+        if let Some(worst) = self.most_rects.first() {
+            write!(f, " at frame {}", worst.frame)?;
+        }
+        write!(
+            f,
+            "), {:.1} KB/frame (max {:.1}), {:.1}% covered",
+            self.bytes_per_frame.mean / 1024.0,
+            self.bytes_per_frame.max / 1024.0,
+            self.coverage.mean * 100.0
+        )
+    }
+}
+
 pub struct ConvertArgs {
     pub project_name: Option<String>,
     pub resolutions: Option<String>,
+    /// Overrides the detected frame rate, e.g. `30000/1001` or `29.97`.
+    pub fps: Option<String>,
+    pub variable_rate: bool,
     pub use_gpu: bool,
+    pub delta: bool,
+    /// Defaults to two seconds of video.
+    pub keyframe_interval: Option<u32>,
+    pub compact: bool,
+    pub levels: u16,
+    pub colors: Option<u16>,
+    pub invert: bool,
+    pub merge: RectMerge,
+    pub simplify: u8,
+    pub filters: FilterOptions,
+    /// `NAME=PATH[,opacity=PERCENT][,loop]`, paths relative to the project.
+    pub layers: Vec<String>,
+    pub resume: bool,
 }
 
 // Internal structures for the pipeline
@@ -42,10 +346,12 @@ pub struct RawFrame {
 pub struct ProcessedFrame {
     pub id: u64,
     pub rects: Vec<PixelRect>,
+    /// The exact rects, kept to measure what simplifying saved.
+    pub unsimplified: Option<Vec<PixelRect>>,
     pub recycled_buffer: Vec<u8>,
 }
 
 // Thread-local scratch buffer for Snowplow algorithm
 thread_local! {
-    pub static SCRATCH_BUFFER: RefCell<Vec<isize>> = RefCell::new(Vec::new());
+    pub static SCRATCH_BUFFER: RefCell<Vec<isize>> = const { RefCell::new(Vec::new()) };
 }
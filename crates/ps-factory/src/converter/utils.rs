@@ -1,6 +1,103 @@
-use ps_core::PixelRect;
-use std::path::Path;
-use std::process::Command;
+use super::source::{SourceKind, Y4mSource};
+use super::types::{
+    Checkpoint, ChromaKey, ConversionStats, ConvertJob, FilterOptions, FrameStats, LayerSource, RectMerge, Spread,
+    Threshold,
+};
+use anyhow::{bail, Context, Result};
+use ps_core::{
+    delta, file_header::HEADER_SIZE, level, palette, varint::VarintReader, FrameDecoder,
+    FrameIndex, FrameRate, FrameReader, Metadata, Palette, PixelRect,
+};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::UNIX_EPOCH;
+
+/// Parses a colour such as `00ff00` or `#00B140` into `0xRRGGBB`.
+pub fn parse_rgb_hex(s: &str) -> Result<u32, String> {
+    let hex = s.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("invalid colour '{}' (expected RRGGBB)", s));
+    }
+    u32::from_str_radix(hex, 16).map_err(|_| format!("invalid colour '{}' (expected RRGGBB)", s))
+}
+
+/// Chroma (Cb, Cr) of an RGB pixel brought to full brightness, BT.601.
+/// Normalising first keeps a shadowed screen close to the lit one.
+fn chroma(r: u8, g: u8, b: u8) -> (f32, f32) {
+    let scale = 255.0 / r.max(g).max(b).max(1) as f32;
+    let (r, g, b) = (r as f32 * scale, g as f32 * scale, b as f32 * scale);
+    (
+        -0.168_736 * r - 0.331_264 * g + 0.5 * b,
+        0.5 * r - 0.418_688 * g - 0.081_312 * b,
+    )
+}
+
+/// Turns an `rgb24` frame into a gray foreground mask in place: the first
+/// `pixels` bytes become `0` for the key colour, `255` for foreground and a
+/// ramp across the spill band.
+pub fn chroma_key_mask(frame: &mut [u8], pixels: usize, key: &ChromaKey) {
+    let [_, kr, kg, kb] = key.color.to_be_bytes();
+    let (key_cb, key_cr) = chroma(kr, kg, kb);
+    let tolerance = key.tolerance as f32;
+    let spill = key.spill as f32;
+
+    for i in 0..pixels {
+        // Reads stay ahead of writes: pixel i is stored at 3i
+        let (cb, cr) = chroma(frame[i * 3], frame[i * 3 + 1], frame[i * 3 + 2]);
+        let distance = ((cb - key_cb).powi(2) + (cr - key_cr).powi(2)).sqrt();
+        frame[i] = if distance <= tolerance {
+            0
+        } else if distance >= tolerance + spill {
+            255
+        } else {
+            ((distance - tolerance) / spill * 255.0) as u8
+        };
+    }
+}
+
+/// Class means closer than this, in luma, are one shade with noise on it.
+const OTSU_MIN_CONTRAST: f64 = 32.0;
+/// Splits whose between-class standard deviation is below this, in luma,
+/// only cut off a handful of stray pixels.
+const OTSU_MIN_SPREAD: f64 = 4.0;
+
+/// Otsu's threshold for a gray frame: the split of its histogram with the
+/// largest between-class variance. `None` for frames without two distinct
+/// classes, such as flat or noisy ones, where any split would be noise.
+pub fn otsu_threshold(buffer: &[u8]) -> Option<u8> {
+    let mut histogram = [0u64; 256];
+    for &p in buffer {
+        histogram[p as usize] += 1;
+    }
+    let total = buffer.len() as f64;
+    let sum: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(i, &n)| i as f64 * n as f64)
+        .sum();
+
+    let (mut best, mut best_variance, mut best_diff) = (u8::MAX, 0.0, 0.0);
+    let (mut weight_bg, mut sum_bg) = (0.0, 0.0);
+    for (t, &n) in histogram.iter().enumerate() {
+        weight_bg += n as f64;
+        sum_bg += t as f64 * n as f64;
+        let weight_fg = total - weight_bg;
+        if weight_bg == 0.0 || weight_fg == 0.0 {
+            continue;
+        }
+        let mean_diff = sum_bg / weight_bg - (sum - sum_bg) / weight_fg;
+        let variance = weight_bg * weight_fg * mean_diff * mean_diff;
+        if variance > best_variance {
+            // Everything above `t` is foreground
+            (best, best_variance, best_diff) = ((t + 1) as u8, variance, mean_diff.abs());
+        }
+    }
+    let spread = best_variance.sqrt() / total;
+    (best_diff >= OTSU_MIN_CONTRAST && spread >= OTSU_MIN_SPREAD).then_some(best)
+}
 
 // Unsafe Snowplow Algorithm (Kept exactly as optimized)
 pub fn extract_rects_optimized(
@@ -8,7 +105,7 @@ pub fn extract_rects_optimized(
     width: u32,
     height: u32,
     threshold: u8,
-    active_indices: &mut Vec<isize>,
+    active_indices: &mut [isize],
 ) -> Vec<PixelRect> {
     let w = width as usize;
     let h = height as usize;
@@ -75,33 +172,652 @@ pub fn extract_rects_optimized(
     boxes
 }
 
-pub fn detect_fps(path: &Path) -> Option<u16> {
+/// 1-bit extraction for [`RectMerge::Greedy`]: the greedy cover, or the
+/// Snowplow one when that needs fewer rects.
+pub fn extract_rects_greedy(
+    buffer: &[u8],
+    width: u32,
+    height: u32,
+    threshold: u8,
+    active_indices: &mut [isize],
+) -> Vec<PixelRect> {
+    let w = width as usize;
+    let (greedy, _) = greedy_boxes(width, height, |x, y| (buffer[y * w + x] >= threshold) as u8);
+    let snowplow = extract_rects_optimized(buffer, width, height, threshold, active_indices);
+    if greedy.len() < snowplow.len() {
+        greedy
+    } else {
+        snowplow
+    }
+}
+
+/// Greedy maximal-rectangle cover of per-pixel tags, tag `0` being empty.
+/// From each uncovered pixel in reading order it takes the largest rect of
+/// that pixel's tag anchored there, so vertical strokes that Snowplow cuts
+/// at every row where a neighbouring run changes stay whole. Covers
+/// exactly the same pixels as the Snowplow pass, without overlap.
+fn greedy_boxes(
+    width: u32,
+    height: u32,
+    tag_at: impl Fn(usize, usize) -> u8,
+) -> (Vec<PixelRect>, Vec<u8>) {
+    let w = width as usize;
+    let h = height as usize;
+    let mut covered = vec![false; w * h];
+    let mut boxes: Vec<PixelRect> = Vec::with_capacity(4000);
+    let mut box_tags: Vec<u8> = Vec::with_capacity(4000);
+
+    // Length of the uncovered run of `tag` at (x, y), at most `limit`
+    let run = |covered: &[bool], x: usize, y: usize, tag: u8, limit: usize| {
+        let row = y * w;
+        (0..limit)
+            .find(|&n| covered[row + x + n] || tag_at(x + n, y) != tag)
+            .unwrap_or(limit)
+    };
+
+    for y in 0..h {
+        let mut x = 0;
+        while x < w {
+            let tag = tag_at(x, y);
+            if tag == 0 || covered[y * w + x] {
+                x += 1;
+                continue;
+            }
+
+            // Narrow the first row's run going down, keeping the best area
+            let mut span = run(&covered, x, y, tag, w - x);
+            let (mut best_w, mut best_h) = (span, 1);
+            let mut rows = 1;
+            while y + rows < h {
+                span = run(&covered, x, y + rows, tag, span);
+                if span == 0 {
+                    break;
+                }
+                rows += 1;
+                if span * rows > best_w * best_h {
+                    (best_w, best_h) = (span, rows);
+                }
+            }
+
+            for row in y..y + best_h {
+                covered[row * w + x..row * w + x + best_w].fill(true);
+            }
+            boxes.push(PixelRect {
+                x: x as u16,
+                y: y as u16,
+                w: best_w as u16,
+                h: best_h as u16,
+            });
+            box_tags.push(tag);
+            x += best_w;
+        }
+    }
+    (boxes, box_tags)
+}
+
+/// Rects over per-pixel tags, merged with `merge`; tag `0` is empty.
+/// Returns the rects grouped by tag, each group led by `marker(tag)`.
+fn extract_tagged_rects(
+    width: u32,
+    height: u32,
+    max_tag: u8,
+    merge: RectMerge,
+    active_indices: &mut [isize],
+    tag_at: impl Fn(usize, usize) -> u8,
+    marker: impl Fn(u8) -> PixelRect,
+) -> Vec<PixelRect> {
+    let (boxes, box_tags) = match merge {
+        RectMerge::Snowplow => snowplow_boxes(width, height, active_indices, &tag_at),
+        RectMerge::Greedy => {
+            let greedy = greedy_boxes(width, height, &tag_at);
+            let snowplow = snowplow_boxes(width, height, active_indices, &tag_at);
+            if greedy.0.len() < snowplow.0.len() {
+                greedy
+            } else {
+                snowplow
+            }
+        }
+    };
+
+    // Group by tag so each group needs a single marker
+    let mut records = Vec::with_capacity(boxes.len() + max_tag as usize);
+    for tag in 1..=max_tag {
+        let start = records.len();
+        records.push(marker(tag));
+        records.extend(
+            boxes
+                .iter()
+                .zip(&box_tags)
+                .filter(|(_, &t)| t == tag)
+                .map(|(b, _)| *b),
+        );
+        if records.len() == start + 1 {
+            records.pop();
+        }
+    }
+    records
+}
+
+/// Snowplow pass over per-pixel tags: runs only merge with boxes of the
+/// same tag.
+fn snowplow_boxes(
+    width: u32,
+    height: u32,
+    active_indices: &mut [isize],
+    tag_at: impl Fn(usize, usize) -> u8,
+) -> (Vec<PixelRect>, Vec<u8>) {
+    let w = width as usize;
+    let h = height as usize;
+    active_indices.fill(-1);
+
+    let mut boxes: Vec<PixelRect> = Vec::with_capacity(4000);
+    let mut box_tags: Vec<u8> = Vec::with_capacity(4000);
+
+    for y in 0..h {
+        let mut x = 0;
+
+        while x < w {
+            let tag = tag_at(x, y);
+            if tag == 0 {
+                active_indices[x] = -1;
+                x += 1;
+                continue;
+            }
+
+            let start_x = x;
+            while x < w && tag_at(x, y) == tag {
+                x += 1;
+            }
+
+            let run_width = (x - start_x) as u16;
+            let active_idx = active_indices[start_x];
+            let mut merged = false;
+
+            if active_idx != -1 {
+                let idx = active_idx as usize;
+                let b = &mut boxes[idx];
+                if box_tags[idx] == tag
+                    && b.y + b.h == (y as u16)
+                    && b.x == start_x as u16
+                    && b.w == run_width
+                {
+                    b.h += 1;
+                    merged = true;
+                }
+            }
+
+            if !merged {
+                active_indices[start_x] = boxes.len() as isize;
+                boxes.push(PixelRect {
+                    x: start_x as u16,
+                    y: y as u16,
+                    w: run_width,
+                    h: 1,
+                });
+                box_tags.push(tag);
+            }
+        }
+    }
+    (boxes, box_tags)
+}
+
+/// Snowplow pass for multi-level output: pixels are quantised to `levels`
+/// intensity levels, each level becoming its own group of rects.
+pub fn extract_level_rects(
+    buffer: &[u8],
+    width: u32,
+    height: u32,
+    levels: u16,
+    merge: RectMerge,
+    active_indices: &mut [isize],
+) -> Vec<PixelRect> {
+    let mut lut = [0u8; 256];
+    for (v, l) in lut.iter_mut().enumerate() {
+        *l = level::quantize(v as u8, levels);
+    }
+    let w = width as usize;
+    extract_tagged_rects(
+        width,
+        height,
+        level::full_level(levels),
+        merge,
+        active_indices,
+        |x, y| lut[buffer[y * w + x] as usize],
+        level::level_marker,
+    )
+}
+
+/// Index into a [`palette_lut`] for an RGB pixel (5 bits per channel).
+#[inline]
+fn rgb15(px: &[u8]) -> usize {
+    ((px[0] as usize >> 3) << 10) | ((px[1] as usize >> 3) << 5) | (px[2] as usize >> 3)
+}
+
+/// Most colors a converted palette holds. Pixels are tagged with
+/// `index + 1` in a byte, `0` being the background.
+pub const MAX_PALETTE_COLORS: u16 = 255;
+
+/// Maps every 15-bit color to `palette index + 1`, or `0` where black (the
+/// background) is closer than any palette entry. `palette` holds at most
+/// [`MAX_PALETTE_COLORS`] entries.
+pub fn palette_lut(palette: &Palette) -> Vec<u8> {
+    let mut candidates = vec![0x000000];
+    candidates.extend_from_slice(&palette.colors);
+    let candidates = Palette::new(candidates);
+
+    (0..1usize << 15)
+        .map(|i| {
+            let expand = |v: usize| ((v & 0x1F) << 3 | (v & 0x1F) >> 2) as u32;
+            let rgb = expand(i >> 10) << 16 | expand(i >> 5) << 8 | expand(i);
+            candidates.nearest(rgb)
+        })
+        .collect()
+}
+
+/// Snowplow pass for palette output over an `rgb24` frame.
+pub fn extract_palette_rects(
+    buffer: &[u8],
+    width: u32,
+    height: u32,
+    lut: &[u8],
+    colors: usize,
+    merge: RectMerge,
+    active_indices: &mut [isize],
+) -> Vec<PixelRect> {
+    let w = width as usize;
+    extract_tagged_rects(
+        width,
+        height,
+        colors as u8,
+        merge,
+        active_indices,
+        |x, y| {
+            let i = (y * w + x) * 3;
+            lut[rgb15(&buffer[i..i + 3])]
+        },
+        |tag| palette::color_marker(tag - 1),
+    )
+}
+
+/// Lossy clean-up of extracted rects, where `tolerance` is how far an edge
+/// may move, in pixels. Within each group of rects (see [`delta::groups`])
+/// shapes smaller than one grid cell are dropped, edges are snapped to a
+/// grid of `2 * tolerance`, and rects that snapping made identical
+/// neighbours are merged. Snapping grows rects into their neighbours, so
+/// each one is then clipped to the cells no earlier rect covers; translucent
+/// levels would otherwise be blended twice.
+pub fn simplify_rects(
+    records: &[PixelRect],
+    tolerance: u8,
+    width: u32,
+    height: u32,
+) -> Vec<PixelRect> {
+    let grid = 2 * tolerance as u32;
+    let mut coverage = Coverage::new(grid, width, height);
+    let mut out = Vec::with_capacity(records.len());
+    for (controls, rects) in delta::groups(records) {
+        let kept = drop_specks(rects, grid * grid);
+        let snapped = kept
+            .iter()
+            .map(|r| {
+                let (x, w) = snap_span(r.x, r.w, grid, width);
+                let (y, h) = snap_span(r.y, r.h, grid, height);
+                PixelRect { x, y, w, h }
+            })
+            .collect();
+        let mut clipped = Vec::new();
+        for rect in merge_snapped(snapped) {
+            coverage.clip(rect, &mut clipped);
+        }
+        // A group that lost all its rects loses its controls too
+        if !clipped.is_empty() {
+            out.extend_from_slice(controls);
+            out.extend(clipped);
+        }
+    }
+    out
+}
+
+/// The cells of the simplify grid drawn so far. Snapped rects always cover
+/// whole cells, the last row and column of which may be cut short by the
+/// frame edge.
+struct Coverage {
+    grid: u32,
+    width: u32,
+    height: u32,
+    cols: usize,
+    cells: Vec<bool>,
+}
+
+impl Coverage {
+    fn new(grid: u32, width: u32, height: u32) -> Self {
+        let cols = width.div_ceil(grid) as usize;
+        let rows = height.div_ceil(grid) as usize;
+        Self {
+            grid,
+            width,
+            height,
+            cols,
+            cells: vec![false; cols * rows],
+        }
+    }
+
+    /// Appends the parts of the snapped `rect` that aren't covered yet to
+    /// `out`, as few rects as stacking equal runs of cells gives, and marks
+    /// them covered.
+    fn clip(&mut self, rect: PixelRect, out: &mut Vec<PixelRect>) {
+        let g = self.grid;
+        let (x0, x1) = (
+            rect.x as u32 / g,
+            (rect.x as u32 + rect.w as u32).div_ceil(g),
+        );
+        let (y0, y1) = (
+            rect.y as u32 / g,
+            (rect.y as u32 + rect.h as u32).div_ceil(g),
+        );
+        let to_rect = |(a, b): (u32, u32), top: u32, bottom: u32| PixelRect {
+            x: (a * g) as u16,
+            y: (top * g) as u16,
+            w: ((b * g).min(self.width) - a * g) as u16,
+            h: ((bottom * g).min(self.height) - top * g) as u16,
+        };
+
+        // Free runs of the row above, with the row they started on
+        let mut open: Vec<((u32, u32), u32)> = Vec::new();
+        for cy in y0..y1 {
+            let row = cy as usize * self.cols;
+            let mut runs = Vec::new();
+            let mut cx = x0;
+            while cx < x1 {
+                if self.cells[row + cx as usize] {
+                    cx += 1;
+                    continue;
+                }
+                let start = cx;
+                while cx < x1 && !self.cells[row + cx as usize] {
+                    cx += 1;
+                }
+                runs.push((start, cx));
+            }
+            for &(run, top) in &open {
+                if !runs.contains(&run) {
+                    out.push(to_rect(run, top, cy));
+                }
+            }
+            open = runs
+                .into_iter()
+                .map(|run| {
+                    let top = open.iter().find(|(r, _)| *r == run).map_or(cy, |o| o.1);
+                    (run, top)
+                })
+                .collect();
+        }
+        for (run, top) in open {
+            out.push(to_rect(run, top, y1));
+        }
+        for cy in y0..y1 {
+            let row = cy as usize * self.cols;
+            self.cells[row + x0 as usize..row + x1 as usize].fill(true);
+        }
+    }
+}
+
+/// Drops rects belonging to shapes (8-connected) of less than `min_area`
+/// pixels.
+fn drop_specks(rects: &[PixelRect], min_area: u32) -> Vec<PixelRect> {
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    let mut by_bottom: HashMap<u16, Vec<usize>> = HashMap::new();
+    let mut by_right: HashMap<u16, Vec<usize>> = HashMap::new();
+    for (i, r) in rects.iter().enumerate() {
+        by_bottom.entry(r.y + r.h).or_default().push(i);
+        by_right.entry(r.x + r.w).or_default().push(i);
+    }
+
+    // Join each rect with the ones ending right above it or left of it,
+    // corners included
+    let mut parent: Vec<usize> = (0..rects.len()).collect();
+    for (i, r) in rects.iter().enumerate() {
+        let above = by_bottom.get(&{ r.y }).into_iter().flatten().filter(|&&j| {
+            let a = &rects[j];
+            a.x <= r.x + r.w && r.x <= a.x + a.w
+        });
+        let left = by_right.get(&{ r.x }).into_iter().flatten().filter(|&&j| {
+            let a = &rects[j];
+            a.y <= r.y + r.h && r.y <= a.y + a.h
+        });
+        for &j in above.chain(left) {
+            let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+            parent[a] = b;
+        }
+    }
+
+    let mut area: HashMap<usize, u32> = HashMap::new();
+    for (i, r) in rects.iter().enumerate() {
+        *area.entry(root(&mut parent, i)).or_default() += r.w as u32 * r.h as u32;
+    }
+    rects
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| area[&root(&mut parent, i)] >= min_area)
+        .map(|(_, r)| *r)
+        .collect()
+}
+
+/// Snaps both ends of `start..start + len` to the nearest multiple of
+/// `grid`, or `limit`. Spans that would vanish keep the cell around their
+/// centre so thin strokes survive.
+fn snap_span(start: u16, len: u16, grid: u32, limit: u32) -> (u16, u16) {
+    let snap = |v: u32| ((v + grid / 2) / grid * grid).min(limit);
+    let (mut lo, mut hi) = (snap(start as u32), snap(start as u32 + len as u32));
+    if hi <= lo {
+        let centre = (start as u32 + len as u32 / 2).min(limit.saturating_sub(1));
+        lo = centre / grid * grid;
+        hi = (lo + grid).min(limit);
+    }
+    (lo as u16, (hi - lo) as u16)
+}
+
+/// Merges touching or overlapping rects that share their columns, then
+/// those that share their rows.
+fn merge_snapped(mut rects: Vec<PixelRect>) -> Vec<PixelRect> {
+    rects.sort_unstable_by_key(|r| (r.x, r.w, r.y));
+    let mut stacked: Vec<PixelRect> = Vec::with_capacity(rects.len());
+    for r in rects {
+        match stacked.last_mut() {
+            Some(a) if a.x == r.x && a.w == r.w && r.y <= a.y + a.h => {
+                a.h = (r.y + r.h).max(a.y + a.h) - a.y;
+            }
+            _ => stacked.push(r),
+        }
+    }
+
+    stacked.sort_unstable_by_key(|r| (r.y, r.h, r.x));
+    let mut merged: Vec<PixelRect> = Vec::with_capacity(stacked.len());
+    for r in stacked {
+        match merged.last_mut() {
+            Some(a) if a.y == r.y && a.h == r.h && r.x <= a.x + a.w => {
+                a.w = (r.x + r.w).max(a.x + a.w) - a.x;
+            }
+            _ => merged.push(r),
+        }
+    }
+    merged
+}
+
+/// The ffmpeg filter chain for `job`. `leveled` output keeps the soft
+/// edges that the contrast boost would flatten.
+pub fn filter_chain(job: &ConvertJob, leveled: bool) -> String {
+    let f = &job.filters;
+    let mut chain = vec![format!("scale={}:{}", job.width, job.height)];
+    if f.chroma_key.is_some() {
+        // Keying needs the colours; the mask is built after decoding
+        if f.blur > 0.0 {
+            chain.push(format!("gblur=sigma={}:steps=1", f.blur));
+        }
+    } else if job.colors.is_none() {
+        chain.push("format=gray".to_string());
+        if f.blur > 0.0 {
+            chain.push(format!("gblur=sigma={}:steps=1", f.blur));
+        }
+        if !leveled && f.contrast != 1.0 && f.threshold != Threshold::Otsu {
+            chain.push(format!("eq=contrast={}:saturation=0", f.contrast));
+        }
+    }
+    if !f.extra.trim().is_empty() {
+        chain.push(f.extra.trim().to_string());
+    }
+    chain.join(",")
+}
+
+/// The filters of [`filter_chain`] that `job` set to something other than
+/// the defaults or off. Built-in sources aren't read through ffmpeg and
+/// skip them.
+pub fn ffmpeg_only_filters(job: &ConvertJob, leveled: bool) -> Vec<&'static str> {
+    let f = &job.filters;
+    let default = FilterOptions::default();
+    let mut filters = Vec::new();
+    if f.blur > 0.0 && f.blur != default.blur && (f.chroma_key.is_some() || job.colors.is_none()) {
+        filters.push("blur");
+    }
+    if f.chroma_key.is_none()
+        && job.colors.is_none()
+        && !leveled
+        && f.contrast != 1.0
+        && f.contrast != default.contrast
+        && f.threshold != Threshold::Otsu
+    {
+        filters.push("contrast");
+    }
+    if !f.extra.trim().is_empty() {
+        filters.push("extra filters");
+    }
+    filters
+}
+
+/// Picks up to `size` colors for the whole video with ffmpeg's palettegen.
+/// Near-black entries are background and left out.
+pub fn generate_palette(path: &Path, width: u32, height: u32, size: u16) -> Option<Palette> {
+    let output = Command::new("ffmpeg")
+        .arg("-i")
+        .arg(path)
+        .args([
+            "-vf",
+            &format!(
+                "scale={}:{},palettegen=max_colors={}",
+                width,
+                height,
+                size.saturating_add(1).clamp(4, 256)
+            ),
+            "-frames:v",
+            "1",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgb24",
+            "-",
+        ])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    let mut colors: Vec<u32> = Vec::new();
+    for px in output.stdout.chunks_exact(3) {
+        let (r, g, b) = (px[0] as u32, px[1] as u32, px[2] as u32);
+        let luma = (299 * r + 587 * g + 114 * b) / 1000;
+        let rgb = r << 16 | g << 8 | b;
+        if luma >= 40 && !colors.contains(&rgb) {
+            colors.push(rgb);
+        }
+    }
+    colors.truncate(size.min(MAX_PALETTE_COLORS) as usize);
+    (!colors.is_empty()).then(|| Palette::new(colors))
+}
+
+fn probe_stream_rate(path: &Path, entry: &str) -> Option<FrameRate> {
     let output = Command::new("ffprobe")
-        .args(&[
+        .args([
             "-v",
             "error",
             "-select_streams",
             "v:0",
             "-show_entries",
-            "stream=r_frame_rate",
+            entry,
             "-of",
             "default=noprint_wrappers=1:nokey=1",
         ])
         .arg(path)
         .output()
         .ok()?;
-    let out = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    if out.contains('/') {
-        let parts: Vec<&str> = out.split('/').collect();
-        Some((parts[0].parse::<f64>().ok()? / parts[1].parse::<f64>().ok()?).round() as u16)
-    } else {
-        out.parse::<f64>().ok().map(|f| f.round() as u16)
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Exact frame rate of the first video stream, e.g. `30000/1001`.
+/// Image sequences have none.
+pub fn detect_frame_rate(path: &Path) -> Option<FrameRate> {
+    match SourceKind::detect(path) {
+        SourceKind::Ffmpeg => probe_stream_rate(path, "stream=r_frame_rate"),
+        SourceKind::Y4m => Y4mSource::open(path).ok()?.frame_rate(),
+        SourceKind::PngSequence => None,
+    }
+}
+
+/// Heuristic: the average rate of VFR sources differs noticeably from
+/// their nominal rate.
+pub fn looks_variable(path: &Path) -> bool {
+    if SourceKind::detect(path).is_builtin() {
+        return false;
     }
+    match (
+        detect_frame_rate(path),
+        probe_stream_rate(path, "stream=avg_frame_rate"),
+    ) {
+        (Some(r), Some(avg)) => (r.as_f64() - avg.as_f64()).abs() > 0.01 * r.as_f64(),
+        _ => false,
+    }
+}
+
+/// Presentation time of every video frame in milliseconds, relative to
+/// the first frame. Read from packet headers, so nothing gets decoded.
+pub fn probe_timestamps(path: &Path) -> Option<Vec<u32>> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "packet=pts_time",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(path)
+        .output()
+        .ok()?;
+    // Packets come in decode order
+    let mut times: Vec<f64> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| l.trim().trim_end_matches(',').parse().ok())
+        .collect();
+    times.sort_by(f64::total_cmp);
+    let first = *times.first()?;
+    Some(
+        times
+            .iter()
+            .map(|t| ((t - first) * 1000.0).round() as u32)
+            .collect(),
+    )
 }
 
 pub fn get_frame_count(path: &Path) -> Option<u64> {
     let output = Command::new("ffprobe")
-        .args(&[
+        .args([
             "-v",
             "error",
             "-select_streams",
@@ -121,7 +837,7 @@ pub fn get_frame_count(path: &Path) -> Option<u64> {
         return Some(c);
     }
     let d = Command::new("ffprobe")
-        .args(&[
+        .args([
             "-v",
             "error",
             "-show_entries",
@@ -133,5 +849,430 @@ pub fn get_frame_count(path: &Path) -> Option<u64> {
         .output()
         .ok()?;
     let dur: f64 = String::from_utf8_lossy(&d.stdout).trim().parse().ok()?;
-    Some((dur * detect_fps(path)? as f64).round() as u64)
+    Some((dur * detect_frame_rate(path)?.as_f64()).round() as u64)
+}
+
+/// Parses `NAME=PATH[,opacity=PERCENT][,loop]`; relative paths are resolved
+/// against `base_dir`. Names are limited to ASCII letters, digits, `_` and
+/// `-`.
+pub fn parse_layer_spec(spec: &str, base_dir: &Path) -> Result<LayerSource> {
+    let mut parts = spec.split(',');
+    let (name, path) = parts
+        .next()
+        .and_then(|p| p.split_once('='))
+        .with_context(|| format!("Layer '{}' must look like NAME=PATH", spec))?;
+    if name.is_empty() {
+        bail!("Layer '{}' has no name", spec);
+    }
+    if !name
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+    {
+        bail!(
+            "Invalid layer name '{}' (use letters, digits, '_' and '-')",
+            name
+        );
+    }
+    let mut layer = LayerSource {
+        name: name.to_string(),
+        input_path: base_dir.join(path),
+        opacity: 255,
+        looped: false,
+    };
+    for option in parts {
+        match option.trim().split_once('=') {
+            Some(("opacity", v)) => {
+                let percent: u8 = v
+                    .trim_end_matches('%')
+                    .parse()
+                    .ok()
+                    .filter(|p| *p <= 100)
+                    .with_context(|| format!("Invalid opacity '{}' for layer '{}'", v, name))?;
+                layer.opacity = (percent as u32 * 255 / 100) as u8;
+            }
+            None if option.trim() == "loop" => layer.looped = true,
+            _ => bail!("Unknown option '{}' for layer '{}'", option, name),
+        }
+    }
+    if !layer.input_path.exists() {
+        bail!("Layer source not found: {}", layer.input_path.display());
+    }
+    Ok(layer)
+}
+
+/// Where the checkpoint of a conversion writing `output` is kept.
+pub fn checkpoint_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".checkpoint");
+    PathBuf::from(path)
+}
+
+/// Identifies `job` and the state of its source, so a checkpoint is only
+/// resumed by the conversion that wrote it.
+pub fn job_fingerprint(job: &ConvertJob) -> String {
+    let source = fs::metadata(&job.input_path).ok();
+    let modified = source
+        .as_ref()
+        .and_then(|m| m.modified().ok())
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs());
+    let job = ConvertJob {
+        resume: false,
+        ..job.clone()
+    };
+    format!(
+        "{:?}; source {} bytes, modified {}",
+        job,
+        source.map_or(0, |m| m.len()),
+        modified
+    )
+}
+
+/// The checkpoint of `job`, if its partial output is still there.
+pub fn load_checkpoint(job: &ConvertJob) -> Option<Checkpoint> {
+    let json = fs::read_to_string(checkpoint_path(&job.output_path)).ok()?;
+    let checkpoint: Checkpoint = serde_json::from_str(&json).ok()?;
+    let written = fs::metadata(&job.output_path).ok()?.len();
+    (checkpoint.job == job_fingerprint(job)
+        && written >= HEADER_SIZE as u64 + checkpoint.stream_len)
+        .then_some(checkpoint)
+}
+
+/// Replaces the checkpoint at `path` in one step, so a crash mid-write
+/// leaves the previous one.
+pub fn save_checkpoint(path: &Path, checkpoint: &Checkpoint) -> Result<()> {
+    let tmp = path.with_extension("checkpoint.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(checkpoint)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Where the statistics of a conversion writing `output` are saved.
+pub fn stats_path(output: &Path) -> PathBuf {
+    output.with_extension("stats.json")
+}
+
+/// Statistics of one frame of `pixels` pixels, `bytes` long once encoded.
+pub fn measure_frame(frame: u64, rects: &[PixelRect], bytes: u64, pixels: u64) -> FrameStats {
+    let drawn = rects.iter().filter(|r| !r.is_control());
+    let area: u64 = drawn.clone().map(|r| r.w as u64 * r.h as u64).sum();
+    FrameStats {
+        frame,
+        rects: drawn.count() as u32,
+        bytes: bytes as u32,
+        coverage: (area as f64 / pixels.max(1) as f64).min(1.0) as f32,
+    }
+}
+
+/// Statistics of every frame in `stream`, a rect stream written with the
+/// `delta` and `compact` settings of a [`ConvertJob`].
+pub fn scan_frame_stats(
+    stream: &[u8],
+    width: u32,
+    height: u32,
+    delta: bool,
+    compact: bool,
+) -> Result<Vec<FrameStats>> {
+    let (w, h) = (width as u16, height as u16);
+    let (index, mut decoder) = if compact {
+        let reader = VarintReader::new(stream, w, h);
+        (
+            FrameIndex::scan_varint(stream),
+            FrameDecoder::new_varint(reader, delta),
+        )
+    } else {
+        let reader = FrameReader::new(stream, w, h);
+        (FrameIndex::scan(stream), FrameDecoder::new(reader, delta))
+    };
+
+    let mut stats = Vec::with_capacity(index.len());
+    for frame in 0..index.len() {
+        let rects = decoder.next_frame().context("Rect stream ended early")??;
+        let bytes = index.frame_range(frame).map_or(0, |r| r.len());
+        stats.push(measure_frame(
+            frame as u64,
+            rects,
+            bytes as u64,
+            width as u64 * height as u64,
+        ));
+    }
+    Ok(stats)
+}
+
+/// Totals, spreads and worst frames of `frames`, written to a file of
+/// `file_bytes`.
+pub fn summarize_stats(frames: &[FrameStats], file_bytes: u64) -> ConversionStats {
+    const WORST_FRAMES: usize = 10;
+
+    let spread = |value: fn(&FrameStats) -> f64| {
+        let mut values: Vec<f64> = frames.iter().map(value).collect();
+        values.sort_by(f64::total_cmp);
+        let Some(&max) = values.last() else {
+            return Spread::default();
+        };
+        let round = |v: f64| (v * 1e4).round() / 1e4;
+        // Nearest rank
+        let at = |p: f64| {
+            let rank = (p * values.len() as f64).ceil() as usize;
+            round(values[rank.clamp(1, values.len()) - 1])
+        };
+        Spread {
+            mean: round(values.iter().sum::<f64>() / values.len() as f64),
+            p50: at(0.5),
+            p90: at(0.9),
+            p99: at(0.99),
+            max: round(max),
+        }
+    };
+    // Stable, so the earliest of equal frames come first
+    let worst = |key: fn(&FrameStats) -> u32| {
+        let mut sorted = frames.to_vec();
+        sorted.sort_by_key(|f| Reverse(key(f)));
+        sorted.truncate(WORST_FRAMES);
+        sorted
+    };
+
+    ConversionStats {
+        frames: frames.len() as u64,
+        rects: frames.iter().map(|f| f.rects as u64).sum(),
+        stream_bytes: frames.iter().map(|f| f.bytes as u64).sum(),
+        file_bytes,
+        rects_per_frame: spread(|f| f.rects as f64),
+        bytes_per_frame: spread(|f| f.bytes as f64),
+        coverage: spread(|f| f.coverage as f64),
+        most_rects: worst(|f| f.rects),
+        largest: worst(|f| f.bytes),
+    }
+}
+
+pub fn write_stats(path: &Path, stats: &ConversionStats) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(stats)?)?;
+    Ok(())
+}
+
+/// Metadata the downloader left next to `video` (`<name>.meta.json`);
+/// empty if there is none.
+pub fn read_sidecar(video: &Path) -> Metadata {
+    let mut meta = Metadata::default();
+    let map: Option<serde_json::Map<String, serde_json::Value>> =
+        fs::read_to_string(video.with_extension("meta.json"))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok());
+    for (key, value) in map.into_iter().flatten() {
+        if let Some(value) = value.as_str() {
+            meta.set(&key, value);
+        }
+    }
+    meta
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn otsu_splits_between_the_peaks() {
+        // Dark background around 20-40, bright shapes around 200-220
+        let mut frame: Vec<u8> = (0..900).map(|i| 20 + (i % 21) as u8).collect();
+        frame.extend((0..300).map(|i| 200 + (i % 21) as u8));
+        let t = otsu_threshold(&frame).unwrap();
+        assert!((41..=200).contains(&t), "threshold {t}");
+
+        // A lone bright pixel still separates from the background
+        let mut frame = vec![10; 100];
+        frame[7] = 250;
+        assert!((11..=250).contains(&otsu_threshold(&frame).unwrap()));
+    }
+
+    #[test]
+    fn otsu_gives_up_without_two_classes() {
+        // A single shade, grey with sensor noise, and one hot pixel in an
+        // otherwise black frame
+        let noisy: Vec<u8> = (0..4000u32).map(|i| 120 + (i * 7919 % 13) as u8).collect();
+        let mut hot = vec![0; 100_000];
+        hot[500] = 255;
+        for frame in [vec![90; 500], noisy, hot] {
+            assert_eq!(otsu_threshold(&frame), None);
+        }
+    }
+
+    /// Draws tagged rects onto a `w` x `h` grid, failing on any overlap.
+    fn paint(boxes: &[PixelRect], tags: &[u8], w: usize, h: usize) -> Vec<u8> {
+        let mut grid = vec![0; w * h];
+        for (b, &tag) in boxes.iter().zip(tags) {
+            let (bx, by, bw, bh) = (b.x as usize, b.y as usize, b.w as usize, b.h as usize);
+            assert!(bw > 0 && bh > 0 && bx + bw <= w && by + bh <= h);
+            for y in by..by + bh {
+                for cell in &mut grid[y * w + bx..y * w + bx + bw] {
+                    assert_eq!(*cell, 0, "rects overlap at ({}, {})", bx, y);
+                    *cell = tag;
+                }
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn greedy_cover_matches_the_pixels() {
+        let (w, h) = (37, 23);
+        // Blobs of three tags with holes, deterministic but irregular
+        let tags: Vec<u8> = (0..w * h)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                ((x / 5 + y / 3 + (x * y) % 7 / 5) % 4) as u8
+            })
+            .collect();
+        let tag_at = |x: usize, y: usize| tags[y * w + x];
+
+        let (boxes, box_tags) = greedy_boxes(w as u32, h as u32, tag_at);
+        assert_eq!(paint(&boxes, &box_tags, w, h), tags);
+
+        let mut active = vec![0; w];
+        let (plowed, plowed_tags) = snowplow_boxes(w as u32, h as u32, &mut active, tag_at);
+        assert_eq!(paint(&plowed, &plowed_tags, w, h), tags);
+    }
+
+    #[test]
+    fn greedy_keeps_strokes_whole() {
+        // A vertical bar with serifs on every other row: Snowplow restarts
+        // the bar on every row, the greedy cover needs one rect for it
+        let (w, h) = (8usize, 6usize);
+        let frame: Vec<u8> = (0..w * h)
+            .map(|i| if i % w < [3, 1][i / w % 2] { 255 } else { 0 })
+            .collect();
+        let mut active = vec![0; w];
+        let snowplow = extract_rects_optimized(&frame, w as u32, h as u32, 128, &mut active);
+        let greedy = extract_rects_greedy(&frame, w as u32, h as u32, 128, &mut active);
+        assert!(
+            greedy.len() < snowplow.len(),
+            "{} vs {}",
+            greedy.len(),
+            snowplow.len()
+        );
+
+        let expected: Vec<u8> = frame.iter().map(|&p| (p >= 128) as u8).collect();
+        assert_eq!(paint(&greedy, &vec![1; greedy.len()], w, h), expected);
+    }
+
+    #[test]
+    fn parses_layer_specs() {
+        let dir = std::env::temp_dir().join(format!("ps-layers-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("rain.y4m"), b"").unwrap();
+
+        let layer = parse_layer_spec("rain_fx-2=rain.y4m,opacity=50%,loop", &dir).unwrap();
+        assert_eq!(layer.name, "rain_fx-2");
+        assert_eq!(layer.input_path, dir.join("rain.y4m"));
+        assert_eq!((layer.opacity, layer.looped), (127, true));
+
+        for spec in [
+            "rain.y4m",
+            "=rain.y4m",
+            "../rain=rain.y4m",
+            "a/b=rain.y4m",
+            "a\\b=rain.y4m",
+            "ra.in=rain.y4m",
+            "régn=rain.y4m",
+            "rain=rain.y4m,opacity=101",
+            "rain=rain.y4m,fast",
+            "rain=missing.y4m",
+        ] {
+            assert!(parse_layer_spec(spec, &dir).is_err(), "{spec:?}");
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn rect(x: u16, y: u16, w: u16, h: u16) -> PixelRect {
+        PixelRect { x, y, w, h }
+    }
+
+    #[test]
+    fn simplify_snaps_and_drops_specks() {
+        let records = [
+            level::level_marker(1),
+            // One shape: a block and a ledge split at x = 3
+            rect(1, 1, 6, 7),
+            rect(0, 9, 3, 3),
+            rect(3, 9, 5, 3),
+            // A lone pixel, smaller than a grid cell
+            rect(12, 12, 1, 1),
+            // A group with nothing worth keeping loses its marker too
+            level::level_marker(2),
+            rect(14, 0, 1, 2),
+            // A one pixel line keeps the cell around it
+            level::level_marker(3),
+            rect(10, 0, 1, 18),
+        ];
+        let simplified = simplify_rects(&records, 2, 16, 20);
+        assert_eq!(
+            simplified,
+            [
+                level::level_marker(1),
+                rect(0, 0, 8, 8),
+                rect(0, 8, 8, 4),
+                level::level_marker(3),
+                rect(8, 0, 4, 20),
+            ]
+        );
+        assert_eq!(snap_span(14, 1, 4, 16), (12, 4));
+        assert_eq!(snap_span(15, 1, 4, 14), (12, 2));
+    }
+
+    #[test]
+    fn simplified_rects_never_overlap() {
+        // A one pixel line between two levels widens to a whole cell,
+        // which the level after it must not draw again
+        let records = [
+            level::level_marker(1),
+            rect(0, 0, 13, 40),
+            level::level_marker(2),
+            rect(13, 0, 1, 40),
+            level::level_marker(3),
+            rect(14, 0, 16, 40),
+        ];
+        let simplified = simplify_rects(&records, 3, 30, 40);
+        assert_eq!(
+            simplified,
+            [
+                level::level_marker(1),
+                rect(0, 0, 12, 40),
+                level::level_marker(2),
+                rect(12, 0, 6, 40),
+                level::level_marker(3),
+                rect(18, 0, 12, 40),
+            ]
+        );
+
+        // Within a group, rects of different heights can't be merged and
+        // are clipped instead
+        let records = [rect(13, 0, 1, 40), rect(14, 0, 16, 20)];
+        let simplified = simplify_rects(&records, 3, 30, 40);
+        let covered = paint(&simplified, &vec![1; simplified.len()], 30, 40);
+        assert_eq!(
+            covered.iter().filter(|&&t| t == 1).count(),
+            18 * 18 + 6 * 22
+        );
+    }
+
+    #[test]
+    fn chroma_key_masks_the_screen() {
+        let key = ChromaKey {
+            color: parse_rgb_hex("#00FF00").unwrap(),
+            tolerance: 40,
+            spill: 100,
+        };
+        // Lit screen, shadowed screen, red, magenta, blue-green edge, then
+        // trailing bytes the mask must not touch
+        let mut frame = vec![
+            0, 255, 0, 0, 90, 0, 255, 0, 0, 255, 0, 255, 0, 255, 128, 7, 7,
+        ];
+        chroma_key_mask(&mut frame, 5, &key);
+        assert_eq!(frame[..4], [0, 0, 255, 255]);
+        assert!((1..=254).contains(&frame[4]), "edge {}", frame[4]);
+        assert_eq!(frame[15..], [7, 7]);
+
+        assert_eq!(parse_rgb_hex("00b140"), Ok(0x00B140));
+        assert!(parse_rgb_hex("0b140").is_err());
+        assert!(parse_rgb_hex("00g140").is_err());
+    }
 }
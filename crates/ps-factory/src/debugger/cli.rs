@@ -46,7 +46,7 @@ pub fn run_cli(_args: DebugArgs) -> Result<()> {
         // Pick File
         let bins: Vec<_> = fs::read_dir(&p_path)?
             .filter_map(|e| e.ok())
-            .filter(|e| e.path().extension().map_or(false, |ext| ext == "bin"))
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "bin"))
             .collect();
         let b_names: Vec<String> = bins
             .iter()
@@ -70,7 +70,7 @@ pub fn run_cli(_args: DebugArgs) -> Result<()> {
 
         let exes: Vec<_> = fs::read_dir(&dist_dir)?
             .filter_map(|e| e.ok())
-            .filter(|e| e.path().extension().map_or(false, |ext| ext == "exe"))
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "exe"))
             .collect();
 
         if exes.is_empty() {
@@ -93,10 +93,14 @@ pub fn run_cli(_args: DebugArgs) -> Result<()> {
         file_path: target_path,
     };
 
-    process_debug_session(job, |status| {
-        if let DebugStatus::Error(e) = status {
-            eprintln!("Error: {}", e);
+    process_debug_session(job, |status| match status {
+        DebugStatus::Info(entries) => {
+            for (key, value) in entries {
+                println!("  {}: {}", key, value);
+            }
         }
+        DebugStatus::Error(e) => eprintln!("Error: {}", e),
+        _ => {}
     })?;
 
     Ok(())
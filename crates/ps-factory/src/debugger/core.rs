@@ -1,15 +1,21 @@
-use super::types::{DebugJob, DebugStatus, PayloadFooter};
+use super::types::{DebugJob, DebugStatus};
 use super::utils::draw_rect;
 use anyhow::{bail, Context, Result};
-use byteorder::{LittleEndian, ReadBytesExt};
-use minifb::{Key, Window, WindowOptions};
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+use ps_core::{
+    compress, metadata, payload, Brush, FileHeader, FrameDecoder, FrameIndex, Metadata, Palette,
+    PayloadFooter, Timing,
+};
 use std::{
     fs::File,
-    io::{BufReader, Read, Seek, SeekFrom},
-    mem, thread,
+    io::Read,
+    thread,
     time::{Duration, Instant},
 };
 
+/// How far the arrow keys jump when scrubbing.
+const SEEK_SECONDS: f64 = 5.0;
+
 pub fn process_debug_session<F>(job: DebugJob, callback: F) -> Result<()>
 where
     F: Fn(DebugStatus),
@@ -20,45 +26,71 @@ where
 
     // DETECT MODE: .bin (Raw) vs .exe (Container)
     let file_size = f.metadata()?.len();
-    let is_exe = job.file_path.extension().map_or(false, |e| e == "exe");
+    let is_exe = job.file_path.extension().is_some_and(|e| e == "exe");
 
-    let (mut reader, width, height, fps) = if is_exe {
+    let (video_data, footer_size, stored_meta) = if is_exe {
         // --- EXE MODE: READ FOOTER ---
-        if file_size < mem::size_of::<PayloadFooter>() as u64 {
-            bail!("File too small to be a valid Pixel Shell EXE");
-        }
-
-        f.seek(SeekFrom::End(-(mem::size_of::<PayloadFooter>() as i64)))?;
-        let mut footer_buf = [0u8; mem::size_of::<PayloadFooter>()];
-        f.read_exact(&mut footer_buf)?;
-
-        let footer: PayloadFooter = unsafe { std::mem::transmute(footer_buf) };
-
-        if &footer.magic != b"PS_PATCH" {
-            bail!("Invalid EXE: Magic signature 'PS_PATCH' not found.");
-        }
-
-        // Seek to Video Data
-        f.seek(SeekFrom::Start(footer.video_offset))?;
-
-        // Read FPS Header from the video blob
-        let fps = f.read_u16::<LittleEndian>()?;
+        let footer = PayloadFooter::read_from(&mut f)
+            .context("Invalid EXE: no Pixel Shell payload footer found")?;
+        let video = footer.video().context("EXE payload has no video chunk")?;
+        let stored_meta = match footer.metadata() {
+            Some(chunk) => Some(
+                Metadata::parse(&PayloadFooter::read_chunk(&mut f, chunk)?)
+                    .context("Invalid metadata")?,
+            ),
+            None => None,
+        };
+
+        // Load the embedded video blob
+        let video_data = PayloadFooter::read_chunk(&mut f, video)?;
+        let video_data = match video.codec {
+            payload::CODEC_RAW => video_data,
+            payload::CODEC_LZ4 => {
+                compress::decompress(&video_data).context("Corrupt compressed video")?
+            }
+            other => bail!("Unsupported video codec {}", other),
+        };
 
-        (
-            BufReader::new(f),
-            footer.width as usize,
-            footer.height as usize,
-            fps,
-        )
+        (video_data, Some(footer.video_size(video)), stored_meta)
     } else {
         // --- BIN MODE: RAW READ ---
-        let fps = f.read_u16::<LittleEndian>()?;
-        (BufReader::new(f), 1920, 1080, fps) // Default res for raw bin if unknown
+        let mut video_data = Vec::with_capacity(file_size as usize);
+        f.read_to_end(&mut video_data)?;
+        (video_data, None, None)
+    };
+
+    let header = FileHeader::parse(&video_data).context("Invalid video data")?;
+    let fps = header.fps;
+    let (width, height) = match footer_size {
+        Some((w, h)) => (w as usize, h as usize),
+        None if !header.is_legacy() => (header.width as usize, header.height as usize),
+        None => (1920, 1080), // Legacy bins don't record their resolution
+    };
+    let mut frames = FrameDecoder::for_file(&video_data, &header);
+    let index = FrameIndex::load_or_scan(&video_data, &header).context("Invalid frame index")?;
+    let palette = Palette::load(&video_data, &header).context("Invalid palette")?;
+    let mut brush = Brush::new(&header, palette.as_ref());
+    let timing = Timing::load(&video_data, &header).context("Invalid frame timestamps")?;
+    let meta = match stored_meta {
+        Some(meta) => meta,
+        None => Metadata::load(&video_data, &header).context("Invalid metadata")?,
+    };
+    callback(DebugStatus::Info(meta.entries.clone()));
+    let rate = match &timing {
+        Timing::Constant(rate) => format!("{} FPS", rate),
+        Timing::Variable(_) => format!("~{} FPS, variable", fps),
     };
 
     // SETUP WINDOW
     let mut window = Window::new(
-        &format!("Debug View - {} FPS ({}x{})", fps, width, height),
+        &format!(
+            "Debug View - {}{} ({}x{}) [Space: pause, Left/Right: seek]",
+            meta.get(metadata::KEY_TITLE)
+                .map_or(String::new(), |t| format!("{} - ", t)),
+            rate,
+            width,
+            height
+        ),
         width,
         height,
         WindowOptions {
@@ -69,41 +101,61 @@ where
     .context("Unable to create debug window")?;
 
     let mut buffer: Vec<u32> = vec![0; width * height];
-    let frame_duration = Duration::from_secs_f64(1.0 / fps as f64);
     let mut frame_idx = 0;
-    let mut rect_buf = [0u8; 8];
+    let mut shown = None;
+    let mut rect_count = 0;
+    let mut paused = false;
 
     // RENDER LOOP
     while window.is_open() && !window.is_key_down(Key::Escape) {
         let start_time = Instant::now();
-        buffer.fill(0xFF000000);
-        let mut rect_count = 0;
 
-        loop {
-            if reader.read_exact(&mut rect_buf).is_err() {
-                callback(DebugStatus::Finished);
-                return Ok(());
-            }
+        // Seeking / scrubbing via the frame index
+        if window.is_key_pressed(Key::Space, KeyRepeat::No) {
+            paused = !paused;
+        }
+        if window.is_key_pressed(Key::Right, KeyRepeat::Yes) {
+            let target = timing.frame_at(timing.time_of(frame_idx) + SEEK_SECONDS);
+            frame_idx = target.max(frame_idx + 1).min(index.len().saturating_sub(1));
+        }
+        if window.is_key_pressed(Key::Left, KeyRepeat::Yes) {
+            frame_idx = timing.frame_at(timing.time_of(frame_idx) - SEEK_SECONDS);
+        }
+        if window.is_key_pressed(Key::Home, KeyRepeat::No) {
+            frame_idx = 0;
+        }
 
-            let x = u16::from_le_bytes(rect_buf[0..2].try_into().unwrap());
-            let y = u16::from_le_bytes(rect_buf[2..4].try_into().unwrap());
-            let w = u16::from_le_bytes(rect_buf[4..6].try_into().unwrap());
-            let h = u16::from_le_bytes(rect_buf[6..8].try_into().unwrap());
-
-            if w == 0 && h == 0 {
-                break;
-            } // Frame End
-
-            rect_count += 1;
-            draw_rect(
-                &mut buffer,
-                width,
-                height,
-                x as usize,
-                y as usize,
-                w as usize,
-                h as usize,
-            );
+        // Only decode when the frame changed; delta files can't be re-read
+        // in place, and a paused view doesn't need to be redrawn.
+        if shown != Some(frame_idx) {
+            if frames.frame() != frame_idx {
+                let found = frames
+                    .seek(&index, frame_idx)
+                    .with_context(|| format!("Corrupt video data at frame {}", frame_idx))?;
+                if !found {
+                    callback(DebugStatus::Finished);
+                    return Ok(());
+                }
+            }
+            let rects = match frames.next_frame() {
+                Some(rects) => {
+                    rects.with_context(|| format!("Corrupt video data at frame {}", frame_idx))?
+                }
+                None => break,
+            };
+
+            buffer.fill(0xFF000000);
+            rect_count = 0;
+            brush.reset();
+            for r in rects {
+                if brush.apply(r) {
+                    continue;
+                }
+                rect_count += 1;
+                // Premultiplied over the black background
+                draw_rect(&mut buffer, width, height, r, brush.color() | 0xFF000000);
+            }
+            shown = Some(frame_idx);
         }
 
         if frame_idx % fps as usize == 0 {
@@ -114,7 +166,15 @@ where
         }
 
         window.update_with_buffer(&buffer, width, height)?;
-        frame_idx += 1;
+        // Hold each frame until the next one is due
+        let frame_duration = Duration::from_secs_f64(if paused {
+            1.0 / fps as f64
+        } else {
+            (timing.time_of(frame_idx + 1) - timing.time_of(frame_idx)).max(0.0)
+        });
+        if !paused {
+            frame_idx += 1;
+        }
 
         let elapsed = start_time.elapsed();
         if elapsed < frame_duration {
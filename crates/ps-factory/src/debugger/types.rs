@@ -8,7 +8,12 @@ pub struct DebugJob {
 #[derive(Debug, Clone)]
 pub enum DebugStatus {
     Starting,
-    Playing { frame: usize, rect_count: usize },
+    /// Metadata entries of the file, sent once it is loaded.
+    Info(Vec<(String, String)>),
+    Playing {
+        frame: usize,
+        rect_count: usize,
+    },
     Finished,
     Error(String),
 }
@@ -17,16 +22,3 @@ pub struct DebugArgs {
     pub project_name: Option<String>,
     pub file_name: Option<String>,
 }
-
-// Struct matching the one in Builder/Runner
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct PayloadFooter {
-    pub video_offset: u64,
-    pub video_len: u64,
-    pub audio_offset: u64,
-    pub audio_len: u64,
-    pub width: u16,
-    pub height: u16,
-    pub magic: [u8; 8],
-}
@@ -1,13 +1,19 @@
+use ps_core::PixelRect;
+
 // Helper function to draw rectangles on the buffer
 pub fn draw_rect(
     buffer: &mut [u32],
     screen_w: usize,
     screen_h: usize,
-    x: usize,
-    y: usize,
-    w: usize,
-    h: usize,
+    rect: &PixelRect,
+    color: u32,
 ) {
+    let (x, y, w, h) = (
+        rect.x as usize,
+        rect.y as usize,
+        rect.w as usize,
+        rect.h as usize,
+    );
     let right = (x + w).min(screen_w);
     let bottom = (y + h).min(screen_h);
     // Bounds check to prevent crashes on bad data
@@ -19,7 +25,7 @@ pub fn draw_rect(
         let row_start = r * screen_w;
         // This is safe because of the min checks above, but buffer must be sized correctly
         if row_start + right <= buffer.len() {
-            buffer[row_start + x..row_start + right].fill(0xFFFFFFFF);
+            buffer[row_start + x..row_start + right].fill(color);
         }
     }
 }
@@ -1,6 +1,6 @@
 use super::core::process_download;
 use super::types::{DownloadArgs, DownloadJob, DownloadStatus};
-use super::utils::{check_dependencies, resolve_fps, resolve_resolution};
+use super::utils::{check_dependencies, resolve_audio_format, resolve_fps, resolve_resolution};
 use anyhow::Result;
 use dialoguer::{theme::ColorfulTheme, Input};
 use indicatif::{ProgressBar, ProgressStyle};
@@ -16,8 +16,10 @@ pub fn run_cli(args: DownloadArgs) -> Result<()> {
             .interact_text()?,
     };
 
-    let (width, height) = resolve_resolution(args.resolution)?;
+    let resolution = resolve_resolution(args.resolution)?;
+    let (width, height) = (resolution.width as u32, resolution.height as u32);
     let fps = resolve_fps(args.fps)?;
+    let audio_format = resolve_audio_format(args.audio_format)?;
 
     let project_name: String = match args.project_name {
         Some(name) => name,
@@ -34,6 +36,7 @@ pub fn run_cli(args: DownloadArgs) -> Result<()> {
         height,
         fps,
         use_gpu: false,
+        audio_format,
     };
 
     println!(
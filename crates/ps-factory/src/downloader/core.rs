@@ -1,6 +1,9 @@
 use super::types::{DownloadJob, DownloadStatus};
-use super::utils::{get_video_info, parse_ffmpeg_time};
+use super::utils::{
+    audio_encoder_args, get_video_info, has_audio_stream, parse_ffmpeg_time, write_sidecar,
+};
 use anyhow::{bail, Context, Result};
+use ps_core::{metadata, payload::AUDIO_EXTENSIONS, Metadata};
 use std::{
     env, fs,
     io::{BufRead, BufReader, Read},
@@ -79,14 +82,15 @@ where
     fs::create_dir_all(&output_dir)?;
 
     let temp_raw = output_dir.join("temp_raw.mp4");
+    let temp_title = output_dir.join("temp_title.txt");
     let final_video = output_dir.join(format!("{}.mkv", job.project_name));
-    let final_audio = output_dir.join(format!("{}.ogg", job.project_name));
+    let final_audio = output_dir.join(format!("{}.{}", job.project_name, job.audio_format));
 
     // 2. Download (yt-dlp)
     callback(DownloadStatus::Downloading(0.0));
 
     let mut child = Command::new("yt-dlp")
-        .args(&[
+        .args([
             "-f",
             "bestvideo+bestaudio/best",
             &job.url,
@@ -95,6 +99,9 @@ where
             "--merge-output-format",
             "mp4",
             "--no-playlist",
+            "--print-to-file",
+            "%(title)s",
+            temp_title.to_str().unwrap(),
             "--newline", // Crucial for parsing
             "--progress",
             "--no-warnings",
@@ -106,17 +113,15 @@ where
 
     if let Some(stdout) = child.stdout.take() {
         let reader = BufReader::new(stdout);
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                // Parse: "[download]  45.0% of..."
-                if line.starts_with("[download]") && line.contains('%') {
-                    if let Some(start) = line.find(']') {
-                        let remainder = &line[start + 1..];
-                        if let Some(end) = remainder.find('%') {
-                            let pct_str = remainder[..end].trim();
-                            if let Ok(pct) = pct_str.parse::<f32>() {
-                                callback(DownloadStatus::Downloading(pct / 100.0));
-                            }
+        for line in reader.lines().map_while(Result::ok) {
+            // Parse: "[download]  45.0% of..."
+            if line.starts_with("[download]") && line.contains('%') {
+                if let Some(start) = line.find(']') {
+                    let remainder = &line[start + 1..];
+                    if let Some(end) = remainder.find('%') {
+                        let pct_str = remainder[..end].trim();
+                        if let Ok(pct) = pct_str.parse::<f32>() {
+                            callback(DownloadStatus::Downloading(pct / 100.0));
                         }
                     }
                 }
@@ -161,7 +166,7 @@ where
         .arg(&temp_raw)
         .arg("-vf")
         .arg(&filter_str)
-        .args(&[
+        .args([
             "-c:v",
             "libx264",
             "-preset",
@@ -184,30 +189,57 @@ where
     // Force 100% update
     callback(DownloadStatus::ProcessingVideo(1.0));
 
-    // 6. Extract Audio
-    callback(DownloadStatus::ExtractingAudio(0.0));
-
-    let mut ffmpeg_audio = Command::new("ffmpeg");
-    ffmpeg_audio
-        .arg("-i")
-        .arg(&temp_raw)
-        .args(&["-vn", "-acodec", "libvorbis", "-q:a", "5", "-y"])
-        .arg(&final_audio);
-
-    run_ffmpeg_progress(
-        ffmpeg_audio,
-        duration,
-        &callback,
-        DownloadStatus::ExtractingAudio,
-    )
-    .context("Audio extraction failed")?;
+    // 6. Extract Audio (silent sources become silent overlays)
+    // Don't let a previous download's soundtrack end up in the build
+    for (ext, _) in AUDIO_EXTENSIONS {
+        let stale = output_dir.join(format!("{}.{}", job.project_name, ext));
+        if stale.exists() {
+            fs::remove_file(&stale)?;
+        }
+    }
 
-    // Force 100% update
-    callback(DownloadStatus::ExtractingAudio(1.0));
+    if has_audio_stream(&temp_raw)? {
+        callback(DownloadStatus::ExtractingAudio(0.0));
+
+        let mut ffmpeg_audio = Command::new("ffmpeg");
+        ffmpeg_audio
+            .arg("-i")
+            .arg(&temp_raw)
+            .arg("-vn")
+            .args(audio_encoder_args(&job.audio_format))
+            .arg("-y")
+            .arg(&final_audio);
+
+        run_ffmpeg_progress(
+            ffmpeg_audio,
+            duration,
+            &callback,
+            DownloadStatus::ExtractingAudio,
+        )
+        .context("Audio extraction failed")?;
+
+        // Force 100% update
+        callback(DownloadStatus::ExtractingAudio(1.0));
+    }
 
-    // 7. Cleanup
-    if temp_raw.exists() {
-        let _ = fs::remove_file(temp_raw);
+    // 7. Record where the video came from, for the converter to embed
+    let mut meta = Metadata::default();
+    if let Ok(title) = fs::read_to_string(&temp_title) {
+        meta.set(metadata::KEY_TITLE, title.trim());
+    }
+    meta.set(metadata::KEY_SOURCE_URL, job.url.as_str());
+    meta.set("downloaded", metadata::now_utc());
+    meta.set(
+        "download_settings",
+        format!("{}x{} @ {} FPS", job.width, job.height, job.fps),
+    );
+    write_sidecar(&final_video, &meta).context("Failed to write metadata")?;
+
+    // 8. Cleanup
+    for temp in [temp_raw, temp_title] {
+        if temp.exists() {
+            let _ = fs::remove_file(temp);
+        }
     }
 
     callback(DownloadStatus::Finished(output_dir.clone()));
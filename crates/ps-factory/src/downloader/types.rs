@@ -9,6 +9,8 @@ pub struct DownloadJob {
     pub height: u32,
     pub fps: u32,
     pub use_gpu: bool,
+    /// Extension of the extracted soundtrack, e.g. `ogg` or `flac`.
+    pub audio_format: String,
 }
 
 #[derive(Debug, Clone)]
@@ -45,4 +47,5 @@ pub struct DownloadArgs {
     pub resolution: Option<String>,
     pub fps: Option<u32>,
     pub project_name: Option<String>,
+    pub audio_format: Option<String>,
 }
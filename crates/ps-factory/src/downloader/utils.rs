@@ -1,6 +1,8 @@
 use super::types::FFProbeOutput;
 use anyhow::{bail, Context, Result};
 use dialoguer::{theme::ColorfulTheme, Select};
+use ps_core::{payload, Metadata, Resolution};
+use std::fs;
 use std::path::Path;
 use std::process::Command;
 
@@ -25,7 +27,7 @@ pub fn parse_ffmpeg_time(time_str: &str) -> Option<f64> {
 
 pub fn get_video_info(path: &Path) -> Result<(u32, u32, u32, f64)> {
     let output = Command::new("ffprobe")
-        .args(&[
+        .args([
             "-v",
             "quiet",
             "-print_format",
@@ -67,33 +69,30 @@ pub fn get_video_info(path: &Path) -> Result<(u32, u32, u32, f64)> {
     bail!("No video stream found in downloaded file.")
 }
 
-pub fn resolve_resolution(arg: Option<String>) -> Result<(u32, u32)> {
-    match arg.as_deref() {
-        Some("720p") => Ok((1280, 720)),
-        Some("1080p") => Ok((1920, 1080)),
-        Some("1440p") => Ok((2560, 1440)),
-        Some("2160p") => Ok((3840, 2160)),
-        Some(other) => {
-            println!(
-                "Warning: Unknown resolution '{}', defaulting to 1080p",
-                other
-            );
-            Ok((1920, 1080))
-        }
+/// Whether the file has a soundtrack at all.
+pub fn has_audio_stream(path: &Path) -> Result<bool> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "quiet", "-print_format", "json", "-show_streams"])
+        .arg(path)
+        .output()?;
+
+    let parsed: FFProbeOutput =
+        serde_json::from_slice(&output.stdout).context("Failed to parse ffprobe output")?;
+    Ok(parsed.streams.iter().any(|s| s.codec_type == "audio"))
+}
+
+/// Any `1080p` or `WIDTHxHEIGHT` size; asks for a preset when not given.
+pub fn resolve_resolution(arg: Option<String>) -> Result<Resolution> {
+    match arg {
+        Some(s) => s.parse().map_err(anyhow::Error::msg),
         None => {
-            let resolutions = vec!["720p", "1080p", "1440p", "2160p"];
+            let resolutions = Resolution::PRESETS;
             let idx = Select::with_theme(&ColorfulTheme::default())
                 .with_prompt("Select Target Resolution")
                 .default(1)
                 .items(&resolutions)
                 .interact()?;
-            match idx {
-                0 => Ok((1280, 720)),
-                1 => Ok((1920, 1080)),
-                2 => Ok((2560, 1440)),
-                3 => Ok((3840, 2160)),
-                _ => Ok((1920, 1080)),
-            }
+            Ok(resolutions[idx])
         }
     }
 }
@@ -118,3 +117,40 @@ pub fn resolve_fps(arg: Option<u32>) -> Result<u32> {
         }
     }
 }
+
+/// Soundtrack format, `ogg` unless asked otherwise.
+pub fn resolve_audio_format(arg: Option<String>) -> Result<String> {
+    let format = arg.unwrap_or_else(|| "ogg".to_string()).to_lowercase();
+    if payload::audio_codec_for_extension(&format).is_none() {
+        bail!(
+            "Unsupported audio format '{}' (expected ogg, wav, flac or mp3)",
+            format
+        );
+    }
+    Ok(format)
+}
+
+/// FFmpeg encoder arguments for an audio format from `resolve_audio_format`.
+pub fn audio_encoder_args(format: &str) -> &'static [&'static str] {
+    match format {
+        "wav" => &["-acodec", "pcm_s16le"],
+        "flac" => &["-acodec", "flac"],
+        "mp3" => &["-acodec", "libmp3lame", "-q:a", "2"],
+        _ => &["-acodec", "libvorbis", "-q:a", "5"],
+    }
+}
+
+/// Saves `meta` as JSON next to `video` (`<name>.meta.json`), where the
+/// converter picks it up.
+pub fn write_sidecar(video: &Path, meta: &Metadata) -> Result<()> {
+    let map: serde_json::Map<String, serde_json::Value> = meta
+        .entries
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone().into()))
+        .collect();
+    fs::write(
+        video.with_extension("meta.json"),
+        serde_json::to_string_pretty(&map)?,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,14 @@
+use super::{core::inspect_file, types::InspectArgs};
+use anyhow::Result;
+
+pub fn run_cli(args: InspectArgs) -> Result<()> {
+    let report = inspect_file(&args.file)?;
+    let rows = report.rows();
+    let width = rows.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+
+    println!("📄 {}", report.path.display());
+    for (key, value) in rows {
+        println!("  {:<width$}  {}", key, value, width = width);
+    }
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use super::types::FileReport;
+use anyhow::{bail, Context, Result};
+use ps_core::{
+    chunk::{self, Chunks},
+    layer, payload, BlockDecoder, FileHeader, Metadata, PayloadFooter, Timing,
+};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// Largest header we expect; enough to parse any `.bin` prefix.
+const HEADER_PROBE: u64 = 4096;
+
+/// Reads the header and trailing chunks of a `.bin` or built `.exe`
+/// without loading the rect stream.
+pub fn inspect_file(path: &Path) -> Result<FileReport> {
+    let mut f = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let is_exe = path.extension().is_some_and(|e| e == "exe");
+
+    let (header, trailer, codec, audio_codec, streams, stored_meta) = if is_exe {
+        let footer = PayloadFooter::read_from(&mut f)
+            .context("Invalid EXE: no Pixel Shell payload footer found")?;
+        let video = *footer.video().context("EXE payload has no video chunk")?;
+        let stored_meta = match footer.metadata() {
+            Some(chunk) => {
+                let data = PayloadFooter::read_chunk(&mut f, chunk)?;
+                Some(Metadata::parse(&data).context("Invalid metadata")?)
+            }
+            None => None,
+        };
+        let (header, trailer) = match video.codec {
+            payload::CODEC_RAW => read_bin_parts(&mut f, video.offset, video.len)?,
+            payload::CODEC_LZ4 => {
+                let data = PayloadFooter::read_chunk(&mut f, &video)?;
+                let frames = BlockDecoder::new(&data).context("Corrupt compressed video")?;
+                (*frames.header(), frames.trailer()?)
+            }
+            other => bail!("Unsupported video codec {}", other),
+        };
+        let audio_codec = footer.audio().map(|a| a.codec);
+        let streams = footer.videos().map(|v| footer.video_size(v)).collect();
+        (
+            header,
+            trailer,
+            Some(video.codec),
+            Some(audio_codec),
+            streams,
+            stored_meta,
+        )
+    } else {
+        let len = f.metadata()?.len();
+        let (header, trailer) = read_bin_parts(&mut f, 0, len)?;
+        (header, trailer, None, None, Vec::new(), None)
+    };
+
+    let chunks = || Chunks::trailing(&trailer);
+    let metadata = match stored_meta {
+        Some(meta) => meta,
+        None => match chunks().find(chunk::TAG_METADATA)? {
+            Some(data) => Metadata::parse(data).context("Invalid metadata")?,
+            None => Metadata::default(),
+        },
+    };
+    let timing = Timing::from_chunk(&header, chunks().find(chunk::TAG_TIMESTAMPS)?)
+        .context("Invalid frame timestamps")?;
+    let layers = layer::collect(chunks())
+        .context("Invalid layer")?
+        .iter()
+        .map(|l| l.name.to_string())
+        .collect();
+
+    Ok(FileReport {
+        path: path.to_path_buf(),
+        header,
+        timing,
+        codec,
+        audio_codec,
+        streams,
+        layers,
+        metadata,
+    })
+}
+
+/// Header and trailing chunk bytes of the raw `.bin` at `offset`.
+fn read_bin_parts<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+    len: u64,
+) -> Result<(FileHeader, Vec<u8>)> {
+    let mut prefix = vec![0u8; HEADER_PROBE.min(len) as usize];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut prefix)?;
+    let header = FileHeader::parse_prefix(&prefix, len as usize).context("Invalid video data")?;
+
+    let stream_end = header.data_range().end as u64;
+    let mut trailer = vec![0u8; (len - stream_end) as usize];
+    reader.seek(SeekFrom::Start(offset + stream_end))?;
+    reader.read_exact(&mut trailer)?;
+    Ok((header, trailer))
+}
@@ -0,0 +1,7 @@
+pub mod cli;
+pub mod core;
+pub mod types;
+
+pub use cli::*;
+pub use core::*;
+pub use types::*;
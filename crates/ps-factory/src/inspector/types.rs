@@ -0,0 +1,99 @@
+use ps_core::{
+    file_header::{FLAG_DELTA, FLAG_LEVELS, FLAG_PALETTE, FLAG_TIMESTAMPS, FLAG_VARINT},
+    payload, FileHeader, Metadata, Timing,
+};
+use std::path::PathBuf;
+
+/// What `inspect_file` found out about a `.bin` or built `.exe`.
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub header: FileHeader,
+    pub timing: Timing,
+    /// Codec of the embedded video; `None` for `.bin` files.
+    pub codec: Option<u16>,
+    /// Soundtrack of a built `.exe`: `Some(None)` when it is silent,
+    /// `None` for `.bin` files.
+    pub audio_codec: Option<Option<u16>>,
+    /// Frame sizes of every video stream of a built `.exe`; the report
+    /// describes the first.
+    pub streams: Vec<(u16, u16)>,
+    pub layers: Vec<String>,
+    pub metadata: Metadata,
+}
+
+impl FileReport {
+    /// Human-readable `(label, value)` rows: format details first, then
+    /// the metadata entries.
+    pub fn rows(&self) -> Vec<(String, String)> {
+        let h = &self.header;
+        let mut rows = vec![
+            (
+                "Resolution".to_string(),
+                format!("{}x{}", h.width, h.height),
+            ),
+            (
+                "Frame rate".to_string(),
+                match &self.timing {
+                    Timing::Constant(rate) => {
+                        format!("{} FPS ({}/{})", rate, rate.num, rate.den)
+                    }
+                    Timing::Variable(_) => format!("variable (~{} FPS)", h.fps),
+                },
+            ),
+            ("Frames".to_string(), h.frame_count.to_string()),
+            (
+                "Duration".to_string(),
+                format!("{:.1}s", self.timing.time_of(h.frame_count as usize)),
+            ),
+            ("Encoding".to_string(), self.encoding()),
+        ];
+        if self.streams.len() > 1 {
+            let sizes: Vec<String> = self
+                .streams
+                .iter()
+                .map(|(w, h)| format!("{}x{}", w, h))
+                .collect();
+            rows.push(("Streams".to_string(), sizes.join(", ")));
+        }
+        if let Some(audio) = self.audio_codec {
+            let audio = audio.map_or("none", payload::audio_codec_name);
+            rows.push(("Audio".to_string(), audio.to_string()));
+        }
+        if !self.layers.is_empty() {
+            rows.push(("Layers".to_string(), self.layers.join(", ")));
+        }
+        rows.extend(self.metadata.entries.iter().cloned());
+        rows
+    }
+
+    fn encoding(&self) -> String {
+        let h = &self.header;
+        let mut parts = vec![format!("format v{}", h.version)];
+        let flags = [
+            (FLAG_DELTA, "delta"),
+            (FLAG_VARINT, "compact"),
+            (FLAG_PALETTE, "palette"),
+            (FLAG_TIMESTAMPS, "timestamps"),
+        ];
+        if h.flags & FLAG_LEVELS != 0 {
+            parts.push(format!("{} levels", h.levels));
+        }
+        for (flag, name) in flags {
+            if h.flags & flag != 0 {
+                parts.push(name.to_string());
+            }
+        }
+        if !h.is_finalized() {
+            parts.push("INCOMPLETE".to_string());
+        }
+        if self.codec == Some(payload::CODEC_LZ4) {
+            parts.push("LZ4".to_string());
+        }
+        parts.join(", ")
+    }
+}
+
+pub struct InspectArgs {
+    pub file: PathBuf,
+}
@@ -2,4 +2,5 @@ pub mod builder;
 pub mod converter;
 pub mod debugger;
 pub mod downloader;
+pub mod inspector;
 pub mod runner;
@@ -5,7 +5,7 @@ use super::{
 };
 use anyhow::Result;
 use dialoguer::{theme::ColorfulTheme, Select};
-use std::{env, thread, time::Duration};
+use std::env;
 
 pub fn run_cli(args: RunArgs) -> Result<()> {
     // 1. Setup Paths
@@ -46,6 +46,7 @@ pub fn run_cli(args: RunArgs) -> Result<()> {
     if is_silent {
         #[cfg(target_os = "windows")]
         unsafe {
+            use std::{thread, time::Duration};
             use windows::Win32::System::Console::GetConsoleWindow;
             use windows::Win32::UI::WindowsAndMessaging::{ShowWindow, SW_HIDE};
 
@@ -14,7 +14,7 @@ pub fn resolve_target(dist_dir: &Path, target_hint: Option<&str>) -> Result<Path
         .filter_map(|e| e.ok())
         .filter(|e| {
             let p = e.path();
-            p.is_file() && (p.extension().map_or(false, |ext| ext == "exe") || cfg!(unix))
+            p.is_file() && (p.extension().is_some_and(|ext| ext == "exe") || cfg!(unix))
         })
         .map(|e| (e.file_name().to_string_lossy().to_string(), e.path()))
         .collect();